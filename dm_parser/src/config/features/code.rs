@@ -1,11 +1,13 @@
+use std::{collections::HashMap, path::PathBuf};
+
+use dm_utils::code_highlighting::{
+    add_aliases, errors::CodeBlockError, find_theme, load_syntaxes, load_themes, stylesheet,
+    use_syntaxes, use_themes,
+};
 use serde::{Deserialize, Serialize};
+use tracing::warn;
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
-pub enum LineClassStrategy {
-    None,
-    Basic,
-    Full,
-}
+pub use dm_utils::code_highlighting::{DarkModeStrategy, LineClassStrategy, ThemeChoices};
 
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -26,6 +28,26 @@ pub struct CodeOptions {
     ///
     /// @default "markdown"
     default_language_for_inline: Option<String>,
+    /// Whether inline code with a language hint -- `` `let x = 1;{rust}` ``
+    /// -- is highlighted; the hint is removed either way.
+    ///
+    /// @default true
+    highlight_inline: Option<bool>,
+    /// Whether inline code _without_ a language hint is highlighted as the
+    /// `default_language_for_inline`.
+    ///
+    /// @default false
+    highlight_unhinted_inline: Option<bool>,
+    /// The characters which open the language hint at the end of inline
+    /// code.
+    ///
+    /// @default "{"
+    inline_language_open: Option<String>,
+    /// The characters which close the language hint at the end of inline
+    /// code.
+    ///
+    /// @default "}"
+    inline_language_close: Option<String>,
 
     /// Every line in a code block will be wrapped by an HTML tag
     /// and to provide style utility we can apply a set of classes.
@@ -41,7 +63,14 @@ pub struct CodeOptions {
     /// Note: if you need more than this then checkout the `code_line` hook.
     line_class_strategy: Option<LineClassStrategy>,
 
+    /// Whether a code block can have a heading bar; it shows the block's
+    /// `title="..."`, the language badge and the clipboard button
+    ///
+    /// @default true
     allow_code_block_heading: Option<bool>,
+    /// Whether a code block can have a `footer="..."` below the code
+    ///
+    /// @default true
     allow_code_block_footer: Option<bool>,
     /// Allows to turn on/off the feature of highlighting lines in code;
     /// this is just a "default" as individual code blocks can explicitly
@@ -57,6 +86,8 @@ pub struct CodeOptions {
     show_language: Option<bool>,
 
     /// Flag indicating whether a clipboard icon
+    ///
+    /// @default false
     show_clipboard: Option<bool>,
 
     /// Allows to turn on/off the feature of _highlighting_ lines in code;
@@ -66,11 +97,59 @@ pub struct CodeOptions {
     highlight_lines: Option<bool>,
 
     /// Allows user to choose a code theme suitable for light mode
-    /// in the browser.
+    /// in the browser; this is the name of one of the `ThemeChoices` or of
+    /// a `.tmTheme` file in `theme_dirs` (without the extension).
+    ///
+    /// @default "base16-ocean.light"
     theme_light: Option<String>,
     /// Allows user to choose a code theme suitable for dark mode
     /// in the browser.
+    ///
+    /// @default "base16-ocean.dark"
     theme_dark: Option<String>,
+    /// Directories (relative to the project's root) which are searched
+    /// for `.tmTheme` files which can then be used as `theme_light` or
+    /// `theme_dark`.
+    theme_dirs: Option<Vec<String>>,
+    /// How the stylesheet for code tells the dark theme's rules apart
+    /// from the light theme's; either with a `prefers-color-scheme` media
+    /// query or by the `.dark` class being on a parent element.
+    ///
+    /// @default "mediaQuery"
+    dark_mode: Option<DarkModeStrategy>,
+
+    /// Whether VueJS's `{{ }}` interpolation is escaped inside of code
+    /// blocks and inline code so that code samples are shown as they were
+    /// written; a code block's language can be prefixed with `!` to
+    /// reverse this (e.g. ` ```!vue `).
+    ///
+    /// @default true
+    escape_code_interpolation: Option<bool>,
+
+    /// Whether comments such as `// [!code ++]`, `// [!code --]`,
+    /// `// [!code focus]`, `// [!code error]` and `// [!code warning]` are
+    /// taken out of code blocks and turned into classes on their lines;
+    /// code blocks with a `diff-{lang}` language (e.g. ` ```diff-rust `)
+    /// treat the `+` and `-` at the start of lines the same way.
+    ///
+    /// @default true
+    code_annotations: Option<bool>,
+
+    /// Directories (relative to the project's root) which are searched
+    /// for `.sublime-syntax` grammars; these are added to the built-in
+    /// grammars so languages such as TSX, Vue or TOML can be highlighted.
+    syntax_dirs: Option<Vec<String>>,
+    /// A file where the compiled grammars are cached between runs; it is
+    /// rebuilt whenever a grammar in `syntax_dirs` is newer than it.
+    ///
+    /// ```json
+    /// { "syntaxDump": ".darkmatter/syntaxes.packdump" }
+    /// ```
+    syntax_dump: Option<String>,
+    /// Maps the languages written in a code fence to the name of a
+    /// grammar (e.g. `{ "tsx": "TypeScriptReact" }`); these take precedence
+    /// over the built-in aliases.
+    language_aliases: Option<HashMap<String, String>>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -80,18 +159,27 @@ pub struct CodeConfig {
     /// choice is unknown.
     ///
     /// @default "plain"
-    default_language_for_unknown: String,
+    pub default_language_for_unknown: String,
     /// The _language_ to use for code blocks when no language
     /// is specified.
     ///
     /// @default "plain"
-    default_language_for_unspecified: String,
+    pub default_language_for_unspecified: String,
 
     /// The _language_ to use for code blocks in an _inline_ code
     /// segment and no language has been specified (or it is not recognized).
     ///
     /// @default "markdown"
-    default_language_for_inline: String,
+    pub default_language_for_inline: String,
+    /// Whether inline code with a language hint is highlighted
+    pub highlight_inline: bool,
+    /// Whether inline code without a language hint is highlighted as the
+    /// `default_language_for_inline`
+    pub highlight_unhinted_inline: bool,
+    /// The characters which open the language hint of inline code
+    pub inline_language_open: String,
+    /// The characters which close the language hint of inline code
+    pub inline_language_close: String,
 
     /// Every line in a code block will be wrapped by an HTML tag
     /// and to provide style utility we can apply a set of classes.
@@ -105,38 +193,62 @@ pub struct CodeConfig {
     /// to indicate the kind of tokens which are found on the line.
     ///
     /// Note: if you need more than this then checkout the `code_line` hook.
-    line_class_strategy: LineClassStrategy,
+    pub line_class_strategy: LineClassStrategy,
 
-    allow_code_block_heading: bool,
-    allow_code_block_footer: bool,
+    pub allow_code_block_heading: bool,
+    pub allow_code_block_footer: bool,
     /// Allows to turn on/off the feature of highlighting lines in code;
     /// this is just a "default" as individual code blocks can explicitly
     /// ask for line numbers with the `#` modifier
     ///
     /// @default false
-    line_numbers: bool,
+    pub line_numbers: bool,
 
     /// Flag indicating whether to display the language name in the upper right
     /// of the code block.
     ///
     /// @default true
-    show_language: bool,
+    pub show_language: bool,
 
     /// Flag indicating whether a clipboard icon
-    show_clipboard: bool,
+    pub show_clipboard: bool,
 
     /// Allows to turn on/off the feature of _highlighting_ lines in code;
     /// lines will never be highlighted unless the page has instructions to
     /// highlight particular lines but this allows all highlights to be
     /// explicitly turned off
-    highlight_lines: bool,
+    pub highlight_lines: bool,
 
     /// Allows user to choose a code theme suitable for light mode
     /// in the browser.
-    theme_light: String,
+    pub theme_light: String,
     /// Allows user to choose a code theme suitable for dark mode
     /// in the browser.
-    theme_dark: String,
+    pub theme_dark: String,
+    /// Directories which are searched for `.tmTheme` files
+    pub theme_dirs: Vec<String>,
+    /// How the dark theme's rules are scoped in the stylesheet for code
+    pub dark_mode: DarkModeStrategy,
+
+    /// Whether VueJS's `{{ }}` interpolation is escaped inside of code
+    /// blocks and inline code; a code block's language can be prefixed
+    /// with `!` to reverse this.
+    ///
+    /// @default true
+    pub escape_code_interpolation: bool,
+
+    /// Whether `// [!code ...]` comments and the lines of `diff-{lang}`
+    /// code blocks are turned into classes on their lines
+    ///
+    /// @default true
+    pub code_annotations: bool,
+
+    /// Directories which are searched for `.sublime-syntax` grammars
+    pub syntax_dirs: Vec<String>,
+    /// A file where the compiled grammars are cached between runs
+    pub syntax_dump: Option<String>,
+    /// Maps the languages written in a code fence to the name of a grammar
+    pub language_aliases: HashMap<String, String>,
 }
 
 impl CodeConfig {
@@ -145,6 +257,10 @@ impl CodeConfig {
             default_language_for_unknown: String::from("plain"),
             default_language_for_unspecified: String::from("plain"),
             default_language_for_inline: String::from("markdown"),
+            highlight_inline: true,
+            highlight_unhinted_inline: false,
+            inline_language_open: String::from("{"),
+            inline_language_close: String::from("}"),
             line_class_strategy: LineClassStrategy::Basic,
             allow_code_block_heading: true,
             allow_code_block_footer: true,
@@ -152,8 +268,15 @@ impl CodeConfig {
             show_language: true,
             show_clipboard: false,
             highlight_lines: true,
-            theme_light: String::from(""),
-            theme_dark: String::from(""),
+            theme_light: String::from(ThemeChoices::Base16OceanLight.name()),
+            theme_dark: String::from(ThemeChoices::Base16OceanDark.name()),
+            theme_dirs: vec![],
+            dark_mode: DarkModeStrategy::MediaQuery,
+            escape_code_interpolation: true,
+            code_annotations: true,
+            syntax_dirs: vec![],
+            syntax_dump: None,
+            language_aliases: HashMap::new(),
         }
     }
 
@@ -169,6 +292,18 @@ impl CodeConfig {
         if let Some(default_language_for_inline) = options.default_language_for_inline {
             config.default_language_for_inline = default_language_for_inline;
         }
+        if let Some(highlight_inline) = options.highlight_inline {
+            config.highlight_inline = highlight_inline;
+        }
+        if let Some(highlight_unhinted_inline) = options.highlight_unhinted_inline {
+            config.highlight_unhinted_inline = highlight_unhinted_inline;
+        }
+        if let Some(inline_language_open) = options.inline_language_open {
+            config.inline_language_open = inline_language_open;
+        }
+        if let Some(inline_language_close) = options.inline_language_close {
+            config.inline_language_close = inline_language_close;
+        }
         if let Some(line_class_strategy) = options.line_class_strategy {
             config.line_class_strategy = line_class_strategy;
         }
@@ -199,7 +334,82 @@ impl CodeConfig {
         if let Some(theme_dark) = options.theme_dark {
             config.theme_dark = theme_dark;
         }
+        if let Some(theme_dirs) = options.theme_dirs {
+            config.theme_dirs = theme_dirs;
+        }
+        if let Some(dark_mode) = options.dark_mode {
+            config.dark_mode = dark_mode;
+        }
+        if let Some(escape_code_interpolation) = options.escape_code_interpolation {
+            config.escape_code_interpolation = escape_code_interpolation;
+        }
+        if let Some(code_annotations) = options.code_annotations {
+            config.code_annotations = code_annotations;
+        }
+        if let Some(syntax_dirs) = options.syntax_dirs {
+            config.syntax_dirs = syntax_dirs;
+        }
+        if let Some(syntax_dump) = options.syntax_dump {
+            config.syntax_dump = Some(syntax_dump);
+        }
+        if let Some(language_aliases) = options.language_aliases {
+            config.language_aliases = language_aliases;
+        }
 
         config
     }
+
+    /// Registers the configured grammars, aliases and themes with the
+    /// highlighter. These are shared by the whole process so -- rather than
+    /// each time a configuration is built -- this is done once before the
+    /// first page is parsed; anything which fails to load is logged and
+    /// the built-in grammars and themes are used in its place.
+    pub fn init(&self) {
+        if let Err(e) = self.load_syntaxes() {
+            warn!("{}", e);
+        }
+        if let Err(e) = self.load_themes() {
+            warn!("{}", e);
+        }
+    }
+
+    /// Merges the grammars in `syntax_dirs` into the syntax set used to
+    /// highlight code and registers the `language_aliases`; with neither
+    /// configured the built-in grammars are left as they are.
+    pub fn load_syntaxes(&self) -> Result<(), CodeBlockError> {
+        add_aliases(&self.language_aliases);
+        if self.syntax_dirs.is_empty() {
+            return Ok(());
+        }
+
+        let dirs: Vec<PathBuf> = self.syntax_dirs.iter().map(PathBuf::from).collect();
+        let dump = self.syntax_dump.as_ref().map(PathBuf::from);
+        use_syntaxes(load_syntaxes(&dirs, dump.as_deref())?);
+
+        Ok(())
+    }
+
+    /// Adds the `.tmTheme` files in `theme_dirs` to the themes which code
+    /// can be styled with
+    pub fn load_themes(&self) -> Result<(), CodeBlockError> {
+        if self.theme_dirs.is_empty() {
+            return Ok(());
+        }
+
+        let dirs: Vec<PathBuf> = self.theme_dirs.iter().map(PathBuf::from).collect();
+        use_themes(load_themes(&dirs)?);
+
+        Ok(())
+    }
+
+    /// The stylesheet for highlighted code; the rules of `theme_light` and
+    /// `theme_dark` are put in a single stylesheet with the dark rules
+    /// scoped according to `dark_mode`
+    pub fn stylesheet(&self) -> Result<String, CodeBlockError> {
+        stylesheet(
+            &find_theme(&self.theme_light)?,
+            &find_theme(&self.theme_dark)?,
+            self.dark_mode,
+        )
+    }
 }
@@ -1,21 +1,84 @@
 use serde::{Deserialize, Serialize};
 
+/// How a collapsible list item is rendered
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum CollapsibleOutput {
+    /// the item's sub-list is wrapped in a `<details>` element with the
+    /// item's own content as the `<summary>`; this needs no JS or CSS
+    Details,
+    /// the `<li>` is given classes which identify it as collapsible and
+    /// whether it starts expanded; leaving the behavior to the page
+    Classes,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
-pub struct CollapsibleOptions {}
+pub struct CollapsibleOptions {
+    /// Whether the `+` and `*` list markers make an item collapsible
+    ///
+    /// @default true
+    enabled: Option<bool>,
+    /// @default "details"
+    output: Option<CollapsibleOutput>,
+    /// The class given to every collapsible list item
+    ///
+    /// @default "collapsible"
+    class: Option<String>,
+    /// The class given to a collapsible item which starts expanded
+    /// (only used with the `classes` output)
+    ///
+    /// @default "expanded"
+    expanded_class: Option<String>,
+    /// The class given to a collapsible item which starts collapsed
+    /// (only used with the `classes` output)
+    ///
+    /// @default "collapsed"
+    collapsed_class: Option<String>,
+}
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
-pub struct CollapsibleConfig {}
+pub struct CollapsibleConfig {
+    pub enabled: bool,
+    pub output: CollapsibleOutput,
+    pub class: String,
+    pub expanded_class: String,
+    pub collapsed_class: String,
+}
 
 impl Default for CollapsibleConfig {
     fn default() -> Self {
-        CollapsibleConfig {}
+        CollapsibleConfig {
+            enabled: true,
+            output: CollapsibleOutput::Details,
+            class: String::from("collapsible"),
+            expanded_class: String::from("expanded"),
+            collapsed_class: String::from("collapsed"),
+        }
     }
 }
 
 impl CollapsibleConfig {
     pub fn with_options(options: CollapsibleOptions) -> Self {
-        CollapsibleConfig::default()
+        let mut config = CollapsibleConfig::default();
+
+        if let Some(enabled) = options.enabled {
+            config.enabled = enabled;
+        }
+        if let Some(output) = options.output {
+            config.output = output;
+        }
+        if let Some(class) = options.class {
+            config.class = class;
+        }
+        if let Some(expanded_class) = options.expanded_class {
+            config.expanded_class = expanded_class;
+        }
+        if let Some(collapsed_class) = options.collapsed_class {
+            config.collapsed_class = collapsed_class;
+        }
+
+        config
     }
 }
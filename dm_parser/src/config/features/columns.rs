@@ -1,21 +1,101 @@
 use serde::{Deserialize, Serialize};
 
+/// How the layout of a column container is expressed in the output
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum ColumnOutput {
+    /// CSS classes (e.g., `columns columns-2` and `column column-40`)
+    /// which the site's stylesheet is expected to provide a grid for
+    Classes,
+    /// inline styles which need no stylesheet at all
+    Inline,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
-pub struct ColumnOptions {}
+pub struct ColumnOptions {
+    /// Whether the `::: N columns` syntax is recognized
+    ///
+    /// @default true
+    enabled: Option<bool>,
+    /// @default "classes"
+    output: Option<ColumnOutput>,
+    /// The class given to the column container
+    ///
+    /// @default "columns"
+    class: Option<String>,
+    /// The class given to each column
+    ///
+    /// @default "column"
+    column_class: Option<String>,
+    /// The gap between columns (only used with `inline` output)
+    ///
+    /// @default "1rem"
+    gap: Option<String>,
+    /// Whether columns can be placed inside of another column
+    ///
+    /// @default true
+    allow_nesting: Option<bool>,
+    /// The container width below which the columns are stacked on top
+    /// of one another; set to `null` to never stack.
+    ///
+    /// @default "640px"
+    stack_below: Option<Option<String>>,
+}
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
-pub struct ColumnConfig {}
+pub struct ColumnConfig {
+    pub enabled: bool,
+    pub output: ColumnOutput,
+    pub class: String,
+    pub column_class: String,
+    pub gap: String,
+    pub allow_nesting: bool,
+    /// The width below which columns stack on top of one another
+    pub stack_below: Option<String>,
+}
 
 impl Default for ColumnConfig {
     fn default() -> Self {
-        ColumnConfig {}
+        ColumnConfig {
+            enabled: true,
+            output: ColumnOutput::Classes,
+            class: String::from("columns"),
+            column_class: String::from("column"),
+            gap: String::from("1rem"),
+            allow_nesting: true,
+            stack_below: Some(String::from("640px")),
+        }
     }
 }
 
 impl ColumnConfig {
     pub fn with_options(options: ColumnOptions) -> Self {
-        ColumnConfig::default()
+        let mut config = ColumnConfig::default();
+
+        if let Some(enabled) = options.enabled {
+            config.enabled = enabled;
+        }
+        if let Some(output) = options.output {
+            config.output = output;
+        }
+        if let Some(class) = options.class {
+            config.class = class;
+        }
+        if let Some(column_class) = options.column_class {
+            config.column_class = column_class;
+        }
+        if let Some(gap) = options.gap {
+            config.gap = gap;
+        }
+        if let Some(allow_nesting) = options.allow_nesting {
+            config.allow_nesting = allow_nesting;
+        }
+        if let Some(stack_below) = options.stack_below {
+            config.stack_below = stack_below;
+        }
+
+        config
     }
 }
@@ -1,21 +1,92 @@
+use std::collections::HashMap;
+
 use serde::{Deserialize, Serialize};
 
+/// How an expanded emoji is written into the page
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum EmojiOutput {
+    /// the unicode character on its own
+    Unicode,
+    /// the unicode character wrapped in a `<span>` with the
+    /// configured class and an accessible label
+    Span,
+}
+
+/// A project defined emoji shortcode
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum CustomEmoji {
+    /// a shortcode which expands to text (typically a unicode character)
+    Unicode(String),
+    /// a shortcode which expands to an image
+    Image { image: String },
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
-pub struct EmojiOptions {}
+pub struct EmojiOptions {
+    /// Whether shortcodes such as `:smile:` are expanded into emoji
+    ///
+    /// @default true
+    enabled: Option<bool>,
+    /// Whether emoji are output as the unicode character or wrapped
+    /// in a `<span>`
+    ///
+    /// @default "unicode"
+    output: Option<EmojiOutput>,
+    /// The class given to the `<span>` (or `<img>` for image based
+    /// emoji) which wraps an emoji
+    ///
+    /// @default "emoji"
+    class: Option<String>,
+    /// Shortcodes -- in addition to those bundled from GitHub's
+    /// _gemoji_ -- which the project defines. A custom shortcode takes
+    /// precedence over a bundled one with the same name.
+    ///
+    /// ```json
+    /// { "party-parrot": { "image": "/emoji/parrot.gif" }, "shrug": "¯\\_(ツ)_/¯" }
+    /// ```
+    custom: Option<HashMap<String, CustomEmoji>>,
+}
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
-pub struct EmojiConfig {}
+pub struct EmojiConfig {
+    pub enabled: bool,
+    pub output: EmojiOutput,
+    pub class: String,
+    pub custom: HashMap<String, CustomEmoji>,
+}
 
 impl Default for EmojiConfig {
     fn default() -> Self {
-        EmojiConfig {}
+        EmojiConfig {
+            enabled: true,
+            output: EmojiOutput::Unicode,
+            class: String::from("emoji"),
+            custom: HashMap::new(),
+        }
     }
 }
 
 impl EmojiConfig {
     pub fn with_options(options: EmojiOptions) -> Self {
-        EmojiConfig::default()
+        let mut config = EmojiConfig::default();
+
+        if let Some(enabled) = options.enabled {
+            config.enabled = enabled;
+        }
+        if let Some(output) = options.output {
+            config.output = output;
+        }
+        if let Some(class) = options.class {
+            config.class = class;
+        }
+        if let Some(custom) = options.custom {
+            config.custom = custom;
+        }
+
+        config
     }
 }
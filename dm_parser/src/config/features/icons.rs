@@ -0,0 +1,66 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IconOptions {
+    /// Whether Iconify tags such as `<mdi:account-edit />` are turned
+    /// into icons
+    ///
+    /// @default true
+    enabled: Option<bool>,
+    /// The directory which holds the Iconify JSON collections (one
+    /// `{collection}.json` file each) used to inline an icon's SVG when
+    /// the output is HTML
+    ///
+    /// @default "node_modules/@iconify/json/json"
+    collections: Option<String>,
+    /// The class given to every icon
+    ///
+    /// @default "icon"
+    class: Option<String>,
+    /// The size of an inlined SVG relative to the surrounding font
+    ///
+    /// @default 1.2
+    scale: Option<f32>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct IconConfig {
+    pub enabled: bool,
+    pub collections: String,
+    pub class: String,
+    pub scale: f32,
+}
+
+impl Default for IconConfig {
+    fn default() -> Self {
+        IconConfig {
+            enabled: true,
+            collections: String::from("node_modules/@iconify/json/json"),
+            class: String::from("icon"),
+            scale: 1.2,
+        }
+    }
+}
+
+impl IconConfig {
+    pub fn with_options(options: IconOptions) -> Self {
+        let mut config = IconConfig::default();
+
+        if let Some(enabled) = options.enabled {
+            config.enabled = enabled;
+        }
+        if let Some(collections) = options.collections {
+            config.collections = collections;
+        }
+        if let Some(class) = options.class {
+            config.class = class;
+        }
+        if let Some(scale) = options.scale {
+            config.scale = scale;
+        }
+
+        config
+    }
+}
@@ -1,21 +1,185 @@
 use serde::{Deserialize, Serialize};
 
+/// The formats which responsive image variants can be created in
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum VariantFormat {
+    Webp,
+    Jpeg,
+    Png,
+}
+
+/// The kind of low quality placeholder which is computed for an image
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum PlaceholderKind {
+    /// no placeholder
+    None,
+    /// a tiny blurred image which is inlined as a base64 data URI and
+    /// set as the image's background
+    DataUri,
+    /// a [blurhash](https://blurha.sh/) which is set as the image's
+    /// `data-blurhash` attribute for client side code to decode
+    Blurhash,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
-pub struct ImageOptions {}
+pub struct ImageOptions {
+    /// The directory which images with an absolute path (e.g., `/logo.png`)
+    /// are served from.
+    ///
+    /// @default "public"
+    public_dir: Option<String>,
+    /// Whether local images have their intrinsic `width` and `height`
+    /// read from the image file and added to the `<img>` tag so that
+    /// the browser can reserve space for them.
+    ///
+    /// @default true
+    probe_dimensions: Option<bool>,
+    /// Whether images are given `loading="lazy"`
+    ///
+    /// @default true
+    lazy_loading: Option<bool>,
+    /// Whether images are given `decoding="async"`
+    ///
+    /// @default true
+    async_decoding: Option<bool>,
+    /// Whether a local image which can not be found produces a
+    /// diagnostic.
+    ///
+    /// @default true
+    report_missing: Option<bool>,
+    /// Whether resized variants of local raster images are created so
+    /// that the browser can choose the most appropriate size.
+    ///
+    /// @default false
+    responsive: Option<bool>,
+    /// The widths (in pixels) of the variants to create; widths larger
+    /// than the original image are skipped.
+    ///
+    /// @default [480, 960, 1440]
+    widths: Option<Vec<u32>>,
+    /// The formats which variants are created in _in addition_ to the
+    /// image's own format.
+    ///
+    /// @default ["webp"]
+    formats: Option<Vec<VariantFormat>>,
+    /// The value of the `sizes` attribute
+    ///
+    /// @default "100vw"
+    sizes: Option<String>,
+    /// Whether a `<picture>` element (with a `<source>` per format) is
+    /// used rather than just a `srcset` on the `<img>` tag.
+    ///
+    /// @default true
+    use_picture: Option<bool>,
+    /// The directory which variants are written to
+    ///
+    /// @default "public/_images"
+    output_dir: Option<String>,
+    /// The URL path which the `output_dir` is served on
+    ///
+    /// @default "/_images"
+    output_url: Option<String>,
+    /// The placeholder which is computed for responsive images
+    ///
+    /// @default "dataUri"
+    placeholder: Option<PlaceholderKind>,
+}
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
-pub struct ImageConfig {}
+pub struct ImageConfig {
+    /// The directory which images with an absolute path are served from
+    pub public_dir: String,
+    /// Whether local images have their dimensions read from the file
+    pub probe_dimensions: bool,
+    pub lazy_loading: bool,
+    pub async_decoding: bool,
+    /// Whether a missing local image produces a diagnostic
+    pub report_missing: bool,
+    /// Whether resized variants of local raster images are created
+    pub responsive: bool,
+    /// The widths (in pixels) of the variants to create
+    pub widths: Vec<u32>,
+    /// The formats which variants are created in (in addition to the
+    /// image's own format)
+    pub formats: Vec<VariantFormat>,
+    pub sizes: String,
+    /// Whether a `<picture>` element is used for responsive images
+    pub use_picture: bool,
+    /// The directory which variants are written to
+    pub output_dir: String,
+    /// The URL path which the `output_dir` is served on
+    pub output_url: String,
+    pub placeholder: PlaceholderKind,
+}
 
 impl Default for ImageConfig {
     fn default() -> Self {
-        ImageConfig {}
+        ImageConfig {
+            public_dir: String::from("public"),
+            probe_dimensions: true,
+            lazy_loading: true,
+            async_decoding: true,
+            report_missing: true,
+            responsive: false,
+            widths: vec![480, 960, 1440],
+            formats: vec![VariantFormat::Webp],
+            sizes: String::from("100vw"),
+            use_picture: true,
+            output_dir: String::from("public/_images"),
+            output_url: String::from("/_images"),
+            placeholder: PlaceholderKind::DataUri,
+        }
     }
 }
 
 impl ImageConfig {
     pub fn with_options(options: ImageOptions) -> Self {
-        ImageConfig::default()
+        let mut config = ImageConfig::default();
+
+        if let Some(public_dir) = options.public_dir {
+            config.public_dir = public_dir;
+        }
+        if let Some(probe_dimensions) = options.probe_dimensions {
+            config.probe_dimensions = probe_dimensions;
+        }
+        if let Some(lazy_loading) = options.lazy_loading {
+            config.lazy_loading = lazy_loading;
+        }
+        if let Some(async_decoding) = options.async_decoding {
+            config.async_decoding = async_decoding;
+        }
+        if let Some(report_missing) = options.report_missing {
+            config.report_missing = report_missing;
+        }
+        if let Some(responsive) = options.responsive {
+            config.responsive = responsive;
+        }
+        if let Some(widths) = options.widths {
+            config.widths = widths;
+        }
+        if let Some(formats) = options.formats {
+            config.formats = formats;
+        }
+        if let Some(sizes) = options.sizes {
+            config.sizes = sizes;
+        }
+        if let Some(use_picture) = options.use_picture {
+            config.use_picture = use_picture;
+        }
+        if let Some(output_dir) = options.output_dir {
+            config.output_dir = output_dir;
+        }
+        if let Some(output_url) = options.output_url {
+            config.output_url = output_url;
+        }
+        if let Some(placeholder) = options.placeholder {
+            config.placeholder = placeholder;
+        }
+
+        config
     }
 }
@@ -2,20 +2,57 @@ use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
-pub struct InlineOptions {}
+pub struct InlineOptions {
+    /// Whether code snippets can be imported from other files with
+    /// `<<< ./my-code.ts`
+    ///
+    /// @default true
+    code: Option<bool>,
+    /// Whether other markdown documents can be included in a page with
+    /// `<<< ./partial.md` or `::include ./partial.md`
+    ///
+    /// @default true
+    markdown: Option<bool>,
+    /// Whether the frontmatter of an included document is merged into
+    /// the page's frontmatter (the page's own values take precedence);
+    /// an include can choose for itself with the `frontmatter` option.
+    ///
+    /// @default false
+    frontmatter: Option<bool>,
+}
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
-pub struct InlineConfig {}
+pub struct InlineConfig {
+    pub code: bool,
+    pub markdown: bool,
+    pub frontmatter: bool,
+}
 
 impl Default for InlineConfig {
     fn default() -> Self {
-        InlineConfig {}
+        InlineConfig {
+            code: true,
+            markdown: true,
+            frontmatter: false,
+        }
     }
 }
 
 impl InlineConfig {
     pub fn with_options(options: InlineOptions) -> Self {
-        InlineConfig::default()
+        let mut config = InlineConfig::default();
+
+        if let Some(code) = options.code {
+            config.code = code;
+        }
+        if let Some(markdown) = options.markdown {
+            config.markdown = markdown;
+        }
+        if let Some(frontmatter) = options.frontmatter {
+            config.frontmatter = frontmatter;
+        }
+
+        config
     }
 }
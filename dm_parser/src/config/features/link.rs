@@ -24,6 +24,40 @@ pub struct LinkOptions {
     use_router_links: Option<bool>,
     clean_index_routes: Option<bool>,
     clean_all_routes: Option<bool>,
+    /// Whether links to other pages on the site (and their `#fragments`)
+    /// are validated against the corpus of pages.
+    ///
+    /// @default true
+    validate_internal_links: Option<bool>,
+    /// Whether external links are checked over HTTP. This is opt-in as it
+    /// requires network access and is considerably slower.
+    ///
+    /// @default false
+    validate_external_links: Option<bool>,
+    /// The maximum number of external links checked at the same time
+    ///
+    /// @default 8
+    external_concurrency: Option<usize>,
+    /// The minimum time (in milliseconds) between two requests to the
+    /// same host
+    ///
+    /// @default 250
+    external_host_interval_ms: Option<u64>,
+    /// The number of redirects which will be followed before giving up
+    ///
+    /// @default 5
+    external_max_redirects: Option<usize>,
+    /// The time (in milliseconds) to wait for a response
+    ///
+    /// @default 10000
+    external_timeout_ms: Option<u64>,
+    /// A file where the results of external link checks are persisted
+    /// between builds
+    external_cache_file: Option<String>,
+    /// How long (in seconds) a cached result is considered fresh
+    ///
+    /// @default 86400
+    external_cache_ttl_secs: Option<u64>,
     // post_processing: Option<>
 }
 
@@ -50,6 +84,14 @@ impl LinkOptions {
             use_router_links: None,
             clean_index_routes: None,
             clean_all_routes: None,
+            validate_internal_links: None,
+            validate_external_links: None,
+            external_concurrency: None,
+            external_host_interval_ms: None,
+            external_max_redirects: None,
+            external_timeout_ms: None,
+            external_cache_file: None,
+            external_cache_ttl_secs: None,
         }
     }
 }
@@ -57,27 +99,46 @@ impl LinkOptions {
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct LinkConfig {
-    root_dir: String,
-    external_link_class: String,
-    internal_link_class: String,
-    relative_link_class: String,
-    fully_qualified_link_class: String,
-    anchor_tag_class: String,
-    router_link_class: String,
-    insecure_class: String,
-    file_class: String,
-    mailto_class: String,
-    image_class: String,
-    document_class: String,
+    pub root_dir: String,
+    pub external_link_class: String,
+    pub internal_link_class: String,
+    pub relative_link_class: String,
+    pub fully_qualified_link_class: String,
+    pub anchor_tag_class: String,
+    pub router_link_class: String,
+    pub insecure_class: String,
+    pub file_class: String,
+    pub mailto_class: String,
+    pub image_class: String,
+    pub document_class: String,
     // TODO: fix this type
-    rule_based_classes: String,
-    external_target: String,
-    external_rel: String,
-    internal_target: Option<String>,
-    internal_rel: Option<String>,
-    use_router_links: bool,
-    clean_index_routes: bool,
-    clean_all_routes: bool,
+    pub rule_based_classes: String,
+    pub external_target: String,
+    pub external_rel: String,
+    pub internal_target: Option<String>,
+    pub internal_rel: Option<String>,
+    pub use_router_links: bool,
+    pub clean_index_routes: bool,
+    pub clean_all_routes: bool,
+    /// Whether links to other pages on the site (and their `#fragments`)
+    /// are validated against the corpus of pages.
+    pub validate_internal_links: bool,
+    /// Whether external links are checked over HTTP
+    pub validate_external_links: bool,
+    /// The maximum number of external links checked at the same time
+    pub external_concurrency: usize,
+    /// The minimum time (in milliseconds) between two requests to the
+    /// same host
+    pub external_host_interval_ms: u64,
+    /// The number of redirects which will be followed before giving up
+    pub external_max_redirects: usize,
+    /// The time (in milliseconds) to wait for a response
+    pub external_timeout_ms: u64,
+    /// A file where the results of external link checks are persisted
+    /// between builds
+    pub external_cache_file: Option<String>,
+    /// How long (in seconds) a cached result is considered fresh
+    pub external_cache_ttl_secs: u64,
     // post_processing: Option<>
 }
 impl Default for LinkConfig {
@@ -103,6 +164,14 @@ impl Default for LinkConfig {
             use_router_links: true,
             clean_index_routes: true,
             clean_all_routes: true,
+            validate_internal_links: true,
+            validate_external_links: false,
+            external_concurrency: 8,
+            external_host_interval_ms: 250,
+            external_max_redirects: 5,
+            external_timeout_ms: 10_000,
+            external_cache_file: None,
+            external_cache_ttl_secs: 86_400,
         }
     }
 }
@@ -168,6 +237,28 @@ impl LinkConfig {
         if let Some(clean_all_routes) = options.clean_all_routes {
             link.clean_all_routes = clean_all_routes;
         }
+        if let Some(validate_internal_links) = options.validate_internal_links {
+            link.validate_internal_links = validate_internal_links;
+        }
+        if let Some(validate_external_links) = options.validate_external_links {
+            link.validate_external_links = validate_external_links;
+        }
+        if let Some(external_concurrency) = options.external_concurrency {
+            link.external_concurrency = external_concurrency;
+        }
+        if let Some(external_host_interval_ms) = options.external_host_interval_ms {
+            link.external_host_interval_ms = external_host_interval_ms;
+        }
+        if let Some(external_max_redirects) = options.external_max_redirects {
+            link.external_max_redirects = external_max_redirects;
+        }
+        if let Some(external_timeout_ms) = options.external_timeout_ms {
+            link.external_timeout_ms = external_timeout_ms;
+        }
+        link.external_cache_file = options.external_cache_file;
+        if let Some(external_cache_ttl_secs) = options.external_cache_ttl_secs {
+            link.external_cache_ttl_secs = external_cache_ttl_secs;
+        }
 
         link
     }
@@ -3,6 +3,10 @@ use serde::{Deserialize, Serialize};
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ListOptions {
+    /// Whether items in a `-` (or ordered) list which have a sub-list are
+    /// collapsible too; they start expanded.
+    ///
+    /// @default false
     pub all_lists_are_collapsible: Option<bool>,
 }
 
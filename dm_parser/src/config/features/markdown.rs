@@ -13,6 +13,9 @@ pub struct MarkdownOptions {
     heading_attributes: Option<bool>,
     footnotes: Option<bool>,
     strikethrough: Option<bool>,
+    /// Whether the content of an HTML element with an `md` attribute
+    /// (e.g., `<td md>`) is parsed as markdown
+    md_attribute: Option<bool>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -24,6 +27,7 @@ pub struct MarkdownConfig {
     pub heading_attributes: bool,
     pub footnotes: bool,
     pub strikethrough: bool,
+    pub md_attribute: bool,
 }
 
 impl MarkdownConfig {
@@ -35,6 +39,7 @@ impl MarkdownConfig {
             heading_attributes: true,
             footnotes: true,
             strikethrough: true,
+            md_attribute: true,
         }
     }
 
@@ -59,6 +64,9 @@ impl MarkdownConfig {
         if let Some(strikethrough) = options.strikethrough {
             config.strikethrough = strikethrough;
         }
+        if let Some(md_attribute) = options.md_attribute {
+            config.md_attribute = md_attribute;
+        }
 
         config
     }
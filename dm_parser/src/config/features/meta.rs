@@ -24,6 +24,12 @@ pub struct MetaOptions {
     /// `route_name` hook instead.
     pub route_name_prop: Option<String>,
 
+    /// The frontmatter property which -- when set to `true` -- marks a
+    /// page as a draft.
+    ///
+    /// @default draft
+    pub draft_prop: Option<String>,
+
     /// Allows query parameters on the page to be passed into Frontmatter properties
     ///
     /// @default false
@@ -52,6 +58,10 @@ pub struct MetaConfig {
     /// `route_name` hook instead.
     pub route_name_prop: String,
 
+    /// The frontmatter property which -- when set to `true` -- marks a
+    /// page as a draft.
+    pub draft_prop: String,
+
     /// Allows query parameters on the page to be passed into Frontmatter properties
     ///
     /// @default false
@@ -72,6 +82,7 @@ impl MetaConfig {
             ],
             route_meta_props: vec!["layout".to_string()],
             route_name_prop: "routeName".to_string(),
+            draft_prop: "draft".to_string(),
             query_parameters: false,
         }
     }
@@ -88,6 +99,9 @@ impl MetaConfig {
         if let Some(route_name_prop) = options.route_name_prop {
             config.route_name_prop = route_name_prop;
         }
+        if let Some(draft_prop) = options.draft_prop {
+            config.draft_prop = draft_prop;
+        }
         if let Some(query_parameters) = options.query_parameters {
             config.query_parameters = query_parameters;
         }
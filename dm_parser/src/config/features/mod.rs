@@ -6,6 +6,7 @@ use self::{
     columns::{ColumnConfig, ColumnOptions},
     emoji::{EmojiConfig, EmojiOptions},
     frontmatter::{FrontmatterConfig, FrontmatterOptions},
+    icons::{IconConfig, IconOptions},
     image::{ImageConfig, ImageOptions},
     inline::{InlineConfig, InlineOptions},
     link::{LinkConfig, LinkOptions},
@@ -13,6 +14,10 @@ use self::{
     markdown::{MarkdownConfig, MarkdownOptions},
     meta::{MetaConfig, MetaOptions},
     nlp::{NlpConfig, NlpOptions},
+    shortcodes::{ShortcodeConfig, ShortcodeOptions},
+    slots::{SlotConfig, SlotOptions},
+    tabs::{TabConfig, TabOptions},
+    templates::{TemplateConfig, TemplateOptions},
     toc::{TocConfig, TocOptions},
 };
 
@@ -21,6 +26,7 @@ pub mod collapsible;
 pub mod columns;
 pub mod emoji;
 pub mod frontmatter;
+pub mod icons;
 pub mod image;
 pub mod inline;
 pub mod link;
@@ -28,6 +34,10 @@ pub mod list;
 pub mod markdown;
 pub mod meta;
 pub mod nlp;
+pub mod shortcodes;
+pub mod slots;
+pub mod tabs;
+pub mod templates;
 pub mod toc;
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -93,6 +103,31 @@ pub struct FeatureOptions {
     ///
     /// As this example illustrates, you can pass "slot props" as well.
     enable_slots: Option<bool>,
+    /// Configures the slots which pages are allowed to target
+    slots: Option<SlotOptions>,
+    /// Provides configuration for _tab menus_ in your markdown:
+    ///
+    /// ```md
+    /// ::tabs theme=light,dark
+    /// ::tab "One"
+    /// This is the first tab
+    /// ::tab name="Two", inline="./sub-page2.md"
+    /// ::tab "Three", route="./sub-page3.md"
+    /// ::end-tabs
+    /// ```
+    tabs: Option<TabOptions>,
+    /// Turns Iconify tags such as `<mdi:checkbox-marked-circle />` into
+    /// icons; an `unplugin-icons` component for SFC output or the inlined
+    /// SVG for HTML output.
+    icons: Option<IconOptions>,
+    /// Expands shortcodes -- written as `{{< name key="value" >}}` with an
+    /// optional `{{< /name >}}` closing tag -- from the HTML templates in
+    /// the `ShortCode` directories of `dir_mapping`.
+    shortcodes: Option<ShortcodeOptions>,
+    /// Replaces expressions such as `{{ frontmatter.title }}`,
+    /// `{{ dm.time_to_read }}` or `{{ route }}` in the page's text with
+    /// their values.
+    templates: Option<TemplateOptions>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -138,6 +173,11 @@ pub struct FeaturesConfig {
     ///
     /// As this example illustrates, you can pass "slot props" as well.
     pub enable_slots: bool,
+    pub slots: SlotConfig,
+    pub tabs: TabConfig,
+    pub icons: IconConfig,
+    pub shortcodes: ShortcodeConfig,
+    pub templates: TemplateConfig,
 }
 
 impl FeaturesConfig {
@@ -155,14 +195,44 @@ impl FeaturesConfig {
         if let Some(meta) = options.meta {
             config.meta = MetaConfig::with_options(meta);
         }
+        if let Some(enable_slots) = options.enable_slots {
+            config.enable_slots = enable_slots;
+        }
+        if let Some(slots) = options.slots {
+            config.slots = SlotConfig::with_options(slots);
+        }
+        if let Some(tabs) = options.tabs {
+            config.tabs = TabConfig::with_options(tabs);
+        }
+        if let Some(icons) = options.icons {
+            config.icons = IconConfig::with_options(icons);
+        }
+        if let Some(shortcodes) = options.shortcodes {
+            config.shortcodes = ShortcodeConfig::with_options(shortcodes);
+        }
+        if let Some(templates) = options.templates {
+            config.templates = TemplateConfig::with_options(templates);
+        }
+        if let Some(lists) = options.lists {
+            config.lists = ListConfig::with_options(lists);
+        }
+        if let Some(collapsible) = options.collapsible {
+            config.collapsable = CollapsibleConfig::with_options(collapsible);
+        }
+        if let Some(columns) = options.columns {
+            config.columns = ColumnConfig::with_options(columns);
+        }
+        if let Some(inline) = options.inline {
+            config.inline = InlineConfig::with_options(inline);
+        }
+        if let Some(emoji) = options.emoji {
+            config.emoji = EmojiConfig::with_options(emoji);
+        }
         if let Some(code) = options.code {
             config.code = CodeConfig::with_options(code);
         }
-        if let Some(markdown) = options.markdown {
-            config.markdown = MarkdownConfig::with_options(markdown);
-        }
-        if let Some(markdown) = options.markdown {
-            config.markdown = MarkdownConfig::with_options(markdown);
+        if let Some(images) = options.images {
+            config.images = ImageConfig::with_options(images);
         }
 
         config
@@ -186,6 +256,11 @@ impl Default for FeaturesConfig {
             columns: ColumnConfig::default(),
             collapsable: CollapsibleConfig::default(),
             enable_slots: true,
+            slots: SlotConfig::default(),
+            tabs: TabConfig::default(),
+            icons: IconConfig::default(),
+            shortcodes: ShortcodeConfig::default(),
+            templates: TemplateConfig::default(),
         }
     }
 }
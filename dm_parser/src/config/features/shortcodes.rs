@@ -0,0 +1,77 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ShortcodeOptions {
+    /// Whether shortcodes such as `{{< alert type="info" >}}` are expanded
+    ///
+    /// @default true
+    enabled: Option<bool>,
+    /// The characters which open a shortcode tag
+    ///
+    /// @default "{{<"
+    open: Option<String>,
+    /// The characters which close a shortcode tag
+    ///
+    /// @default ">}}"
+    close: Option<String>,
+    /// Shortcode templates defined in configuration rather than in an
+    /// HTML file; a template here takes precedence over a file of the
+    /// same name.
+    templates: Option<HashMap<String, String>>,
+    /// Static find/replace values which are substituted into the page
+    /// (outside of code) before shortcodes are expanded.
+    ///
+    /// ```json
+    /// { "%%company%%": "ACME Corp." }
+    /// ```
+    replacements: Option<HashMap<String, String>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ShortcodeConfig {
+    pub enabled: bool,
+    pub open: String,
+    pub close: String,
+    pub templates: HashMap<String, String>,
+    pub replacements: HashMap<String, String>,
+}
+
+impl Default for ShortcodeConfig {
+    fn default() -> Self {
+        ShortcodeConfig {
+            enabled: true,
+            open: String::from("{{<"),
+            close: String::from(">}}"),
+            templates: HashMap::new(),
+            replacements: HashMap::new(),
+        }
+    }
+}
+
+impl ShortcodeConfig {
+    pub fn with_options(options: ShortcodeOptions) -> Self {
+        let mut config = ShortcodeConfig::default();
+
+        if let Some(enabled) = options.enabled {
+            config.enabled = enabled;
+        }
+        if let Some(open) = options.open {
+            config.open = open;
+        }
+        if let Some(close) = options.close {
+            config.close = close;
+        }
+        if let Some(templates) = options.templates {
+            config.templates = templates;
+        }
+        if let Some(replacements) = options.replacements {
+            config.replacements = replacements;
+        }
+
+        config
+    }
+}
@@ -0,0 +1,51 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SlotOptions {
+    /// The names of the slots which the project's layouts provide; a
+    /// page which targets any other slot receives a diagnostic.
+    ///
+    /// @default ["header", "sidebar", "aside", "footer"]
+    known_slots: Option<Vec<String>>,
+    /// The class given to the element which wraps a slot's content
+    ///
+    /// @default "slot"
+    class: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SlotConfig {
+    pub known_slots: Vec<String>,
+    pub class: String,
+}
+
+impl Default for SlotConfig {
+    fn default() -> Self {
+        SlotConfig {
+            known_slots: vec![
+                String::from("header"),
+                String::from("sidebar"),
+                String::from("aside"),
+                String::from("footer"),
+            ],
+            class: String::from("slot"),
+        }
+    }
+}
+
+impl SlotConfig {
+    pub fn with_options(options: SlotOptions) -> Self {
+        let mut config = SlotConfig::default();
+
+        if let Some(known_slots) = options.known_slots {
+            config.known_slots = known_slots;
+        }
+        if let Some(class) = options.class {
+            config.class = class;
+        }
+
+        config
+    }
+}
@@ -0,0 +1,86 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TabOptions {
+    /// Whether the `::tabs` / `::tab` / `::end-tabs` syntax is recognized
+    ///
+    /// @default true
+    enabled: Option<bool>,
+    /// The class given to the element which wraps a tab menu
+    ///
+    /// @default "tabs"
+    class: Option<String>,
+    /// The class given to each tab in the menu
+    ///
+    /// @default "tab"
+    tab_class: Option<String>,
+    /// The class given to each tab's panel
+    ///
+    /// @default "tab-panel"
+    panel_class: Option<String>,
+    /// The themes used for light and dark mode when a tab menu does not
+    /// set its own `theme`. A single theme is used for both modes.
+    ///
+    /// @default ["light", "dark"]
+    theme: Option<Vec<String>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct TabConfig {
+    pub enabled: bool,
+    pub class: String,
+    pub tab_class: String,
+    pub panel_class: String,
+    /// the light and dark mode themes
+    pub theme: (String, String),
+}
+
+impl Default for TabConfig {
+    fn default() -> Self {
+        TabConfig {
+            enabled: true,
+            class: String::from("tabs"),
+            tab_class: String::from("tab"),
+            panel_class: String::from("tab-panel"),
+            theme: (String::from("light"), String::from("dark")),
+        }
+    }
+}
+
+impl TabConfig {
+    pub fn with_options(options: TabOptions) -> Self {
+        let mut config = TabConfig::default();
+
+        if let Some(enabled) = options.enabled {
+            config.enabled = enabled;
+        }
+        if let Some(class) = options.class {
+            config.class = class;
+        }
+        if let Some(tab_class) = options.tab_class {
+            config.tab_class = tab_class;
+        }
+        if let Some(panel_class) = options.panel_class {
+            config.panel_class = panel_class;
+        }
+        if let Some(theme) = options.theme {
+            if let Some(theme) = theme_pair(&theme) {
+                config.theme = theme;
+            }
+        }
+
+        config
+    }
+}
+
+/// The light and dark mode themes from a list where a single theme is
+/// used for both modes
+pub fn theme_pair<T: AsRef<str>>(themes: &[T]) -> Option<(String, String)> {
+    match themes {
+        [theme] => Some((theme.as_ref().to_string(), theme.as_ref().to_string())),
+        [light, dark, ..] => Some((light.as_ref().to_string(), dark.as_ref().to_string())),
+        [] => None,
+    }
+}
@@ -0,0 +1,47 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TemplateOptions {
+    /// Whether expressions such as `{{ frontmatter.title }}` are replaced
+    /// with their value in the page's text
+    ///
+    /// @default true
+    enabled: Option<bool>,
+    /// The format used by the `date` filter when it isn't given one;
+    /// it uses `strftime` style specifiers (`%Y`, `%m`, `%d`, `%B`, etc.)
+    ///
+    /// @default "%Y-%m-%d"
+    date_format: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct TemplateConfig {
+    pub enabled: bool,
+    pub date_format: String,
+}
+
+impl Default for TemplateConfig {
+    fn default() -> Self {
+        TemplateConfig {
+            enabled: true,
+            date_format: String::from("%Y-%m-%d"),
+        }
+    }
+}
+
+impl TemplateConfig {
+    pub fn with_options(options: TemplateOptions) -> Self {
+        let mut config = TemplateConfig::default();
+
+        if let Some(enabled) = options.enabled {
+            config.enabled = enabled;
+        }
+        if let Some(date_format) = options.date_format {
+            config.date_format = date_format;
+        }
+
+        config
+    }
+}
@@ -39,11 +39,13 @@ impl FrontmatterHooks {
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct CodeHooks {
-    line_content: Option<Handler<String>>,
-    block_content: Option<Handler<String>>,
-    block_content_formatted: Option<Handler<String>>,
-    inline_code: Option<Handler<String>>,
-    inline_code_formatted: Option<Handler<String>>,
+    pub line_content: Option<Handler<String>>,
+    pub block_content: Option<Handler<String>>,
+    /// Given the HTML of a fenced code block (including its heading and
+    /// footer wrapper)
+    pub block_content_formatted: Option<Handler<String>>,
+    pub inline_code: Option<Handler<String>>,
+    pub inline_code_formatted: Option<Handler<String>>,
 }
 
 impl Default for CodeHooks {
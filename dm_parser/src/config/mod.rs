@@ -1,3 +1,5 @@
+use std::path::PathBuf;
+
 use crate::models::content_type::ContentType;
 
 use self::{
@@ -32,6 +34,9 @@ pub struct Options {
     /// ]
     /// ```
     pub dir_mapping: Option<Vec<(String, ContentType)>>,
+    /// the project's root directory; paths starting with `@/` (e.g., in
+    /// includes and code imports) are resolved against it
+    pub project_root: Option<PathBuf>,
     /// specify the features you would like
     pub features: Option<FeatureOptions>,
     /// specify the hooks
@@ -46,6 +51,9 @@ pub struct Config {
     /// Allows specifying which directories will have various "content-types"
     /// where a "content-type" are things like `Page`, `ShortCode`, and `Post`.
     pub dir_mapping: Vec<(String, ContentType)>,
+    /// the project's root directory which `@/` paths are resolved against;
+    /// defaults to the directory the configuration was created in
+    pub project_root: PathBuf,
 
     /// configuration for NLP algorithms
     pub features: FeaturesConfig,
@@ -71,13 +79,14 @@ impl Default for Config {
             output,
             features,
             dir_mapping,
+            project_root: std::env::current_dir().unwrap_or_default(),
             hooks,
         }
     }
 }
 
 impl Config {
-    pub fn with_options(options: &Options) -> Self {
+    pub fn with_options(options: Options) -> Self {
         let mut config = Config::default();
         if let Some(output) = options.output {
             config.output = output;
@@ -88,6 +97,9 @@ impl Config {
         if let Some(dir_mapping) = options.dir_mapping {
             config.dir_mapping = dir_mapping;
         }
+        if let Some(project_root) = options.project_root {
+            config.project_root = project_root;
+        }
         if let Some(hooks) = options.hooks {
             config.hooks = HookConfig::with_options(&hooks);
         }
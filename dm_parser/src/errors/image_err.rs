@@ -0,0 +1,13 @@
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ImageError {
+    #[error("The image {0} could not be decoded or encoded")]
+    Codec(String, #[source] image::ImageError),
+
+    #[error("The image variant {0} could not be written")]
+    Write(String, #[source] std::io::Error),
+
+    #[error("A placeholder could not be computed for the image {0}: {1}")]
+    Placeholder(String, String),
+}
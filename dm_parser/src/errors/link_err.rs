@@ -0,0 +1,10 @@
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum LinkError {
+    #[error("The external link cache at {0} could not be read or written")]
+    CacheFile(String, #[source] std::io::Error),
+
+    #[error("The external link cache at {0} is not in a valid format")]
+    CacheFormat(String, #[source] serde_json::Error),
+}
@@ -1,4 +1,6 @@
 pub mod dm_err;
 pub mod fm_err;
+pub mod image_err;
+pub mod link_err;
 pub mod md_err;
 pub mod parser_err;
@@ -1,7 +1,7 @@
 use thiserror::Error;
 
 use crate::{
-    errors::{fm_err::FrontmatterError, md_err::MarkdownError},
+    errors::{fm_err::FrontmatterError, link_err::LinkError, md_err::MarkdownError},
     hooks::errors::HookError,
     models::sfc::SfcError,
 };
@@ -16,6 +16,8 @@ pub enum ParserError {
     Frontmatter(#[from] FrontmatterError),
     #[error("Issues encountered while processing userland hooks.")]
     Hooks(#[from] HookError),
+    #[error("Issues encountered while validating links.")]
+    Links(#[from] LinkError),
     #[error("Issues encountered while processing markdown")]
     Markdown(#[from] MarkdownError),
     // #[error("Issues encountered while converting Markdown to HTML.")]
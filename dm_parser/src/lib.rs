@@ -20,10 +20,12 @@ pub enum ParsedOutput {
 /// The key parsing/transform library which converts markdown into a
 /// target output that the user specifies as part of their configuration.
 #[instrument]
-pub fn parse(id: &str, content: &str, options: &Options) -> Result<ParsedOutput, ParserError> {
+pub fn parse(id: &str, content: &str, options: Options) -> Result<ParsedOutput, ParserError> {
+    let config = Config::with_options(options);
+    config.features.code.init();
     let pipeline = Pipeline::new(
         "foobar", //
-        Config::with_options(options),
+        config,
     )
     .load_content()?
     .h_raw_markdown()?;
@@ -48,6 +50,9 @@ pub fn parse(id: &str, content: &str, options: &Options) -> Result<ParsedOutput,
     // // ParseHtml
     // .h_initial_darkmatter()?
     // .parse_to_html()?
+    // .check_links(&routes)?
+    // .check_external_links(&checker)?
+    // .add_dependencies(&mut graph)?
     // .wrap_html_body()
     // .next_stage()?
     // // FinalizeDarkmatter
@@ -1,4 +1,13 @@
-use super::{content_type::ContentType, sentiment::Sentiment, toc::TocItem};
+use super::{
+    content_type::ContentType,
+    html::{
+        icons::IconRef,
+        image::ImageRef,
+        link::{LinkRef, LinkStatus},
+    },
+    sentiment::Sentiment,
+    toc::TocItem,
+};
 use core::fmt::Debug;
 use lingua::Language;
 use serde::{Deserialize, Serialize};
@@ -13,6 +22,8 @@ pub trait DmStage: std::fmt::Debug {
     type TTR;
     type SENT;
     type IMG;
+    /// The Iconify icons used on the page
+    type Icons;
     type Links;
 }
 
@@ -26,6 +37,7 @@ impl DmStage for DmInitial {
     type TTR = bool;
     type SENT = bool;
     type IMG = bool;
+    type Icons = bool;
     type Links = bool;
 }
 
@@ -37,8 +49,9 @@ impl DmStage for DmWhileParsing {
     type MaxNesting = Option<i32>;
     type TTR = bool;
     type SENT = bool;
-    type IMG = Vec<String>;
-    type Links = Vec<String>;
+    type IMG = Vec<ImageRef>;
+    type Icons = Vec<IconRef>;
+    type Links = Vec<LinkRef>;
 }
 
 /// Finalized Darkmatter types
@@ -50,8 +63,9 @@ impl DmStage for DmFinal {
     type MaxNesting = i32;
     type TTR = u8;
     type SENT = Option<Sentiment>;
-    type IMG = Vec<String>;
-    type Links = Vec<String>;
+    type IMG = Vec<ImageRef>;
+    type Icons = Vec<IconRef>;
+    type Links = Vec<LinkRef>;
 }
 
 #[derive(Serialize, Deserialize)]
@@ -94,6 +108,10 @@ where
     /// checking of the links (if configured to do so).
     images: T::IMG,
 
+    /// The Iconify icons used on the page; for SFC output each one is
+    /// imported as an `unplugin-icons` component.
+    icons: T::Icons,
+
     /// a list of all the link references on the page along with the validity
     /// of the link (if configured to do so).
     links: T::Links,
@@ -115,14 +133,117 @@ where
             .field("toc_hash", &"toc_hash")
             .field("toc", &"toc")
             .field("images", &"images")
+            .field("icons", &"icons")
             .field("links", &"links")
             .finish()
     }
 }
 
+impl<T> Darkmatter<T>
+where
+    T: DmStage<Links = Vec<LinkRef>>,
+{
+    /// The links found on the page
+    pub fn links(&self) -> &Vec<LinkRef> {
+        &self.links
+    }
+
+    /// Sets the links found on the page
+    pub fn set_links(&mut self, links: Vec<LinkRef>) {
+        self.links = links;
+    }
+
+    /// The links which were checked and found to be something
+    /// other than valid
+    pub fn invalid_links(&self) -> Vec<&LinkRef> {
+        self.links
+            .iter()
+            .filter(|l| matches!(&l.status, Some(s) if *s != LinkStatus::Valid))
+            .collect()
+    }
+}
+
+impl<T> Darkmatter<T>
+where
+    T: DmStage<IMG = Vec<ImageRef>>,
+{
+    /// The images found on the page
+    pub fn images(&self) -> &Vec<ImageRef> {
+        &self.images
+    }
+
+    /// Sets the images found on the page
+    pub fn set_images(&mut self, images: Vec<ImageRef>) {
+        self.images = images;
+    }
+
+    /// The local images whose file could not be found
+    pub fn missing_images(&self) -> Vec<&ImageRef> {
+        self.images.iter().filter(|i| i.local && !i.found).collect()
+    }
+}
+
+impl<T> Darkmatter<T>
+where
+    T: DmStage<Icons = Vec<IconRef>>,
+{
+    /// The icons used on the page
+    pub fn icons(&self) -> &Vec<IconRef> {
+        &self.icons
+    }
+
+    /// Sets the icons used on the page
+    pub fn set_icons(&mut self, icons: Vec<IconRef>) {
+        self.icons = icons;
+    }
+
+    /// The icons which could not be found in their local collection
+    pub fn missing_icons(&self) -> Vec<&IconRef> {
+        self.icons
+            .iter()
+            .filter(|i| i.found == Some(false))
+            .collect()
+    }
+}
+
 const DEFAULT_IMAGE_REFS: usize = 5;
 const DEFAULT_LINK_REFS: usize = 8;
 
+impl Default for Darkmatter<DmInitial> {
+    fn default() -> Self {
+        Darkmatter {
+            content_type: ContentType::default(),
+            word_tokens: vec![],
+            language: None,
+            time_to_read: 0,
+            sentiment: None,
+            complexity: None,
+            max_nesting: false,
+            toc_hash: false,
+            toc: false,
+            images: false,
+            icons: false,
+            links: false,
+        }
+    }
+}
+
+/// The darkmatter gathered before the HTML is parsed carries over
+/// into the parsing stage
+impl From<Darkmatter<DmInitial>> for Darkmatter<DmWhileParsing> {
+    fn from(initial: Darkmatter<DmInitial>) -> Self {
+        Darkmatter {
+            content_type: initial.content_type,
+            word_tokens: initial.word_tokens,
+            language: initial.language,
+            time_to_read: initial.time_to_read,
+            sentiment: initial.sentiment,
+            complexity: initial.complexity,
+            ..Darkmatter::default()
+        }
+    }
+}
+
 impl Default for Darkmatter<DmWhileParsing> {
     fn default() -> Self {
         Darkmatter {
@@ -136,6 +257,7 @@ impl Default for Darkmatter<DmWhileParsing> {
             toc_hash: None,
             toc: None,
             images: Vec::with_capacity(DEFAULT_IMAGE_REFS),
+            icons: vec![],
             links: Vec::with_capacity(DEFAULT_LINK_REFS),
         }
     }
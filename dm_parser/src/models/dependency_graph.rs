@@ -0,0 +1,137 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::html::{include::canonical, HtmlContent};
+
+/// Which pages were built from which files (included documents and
+/// imported code) so that a watcher knows what to rebuild when a file
+/// changes.
+///
+/// Pages and files are known by their canonical paths (where they exist)
+/// so that the paths a file watcher reports can be matched.
+#[derive(Debug, Serialize, Deserialize, Default, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct DependencyGraph {
+    /// the files each page depends on
+    dependencies: HashMap<String, HashSet<String>>,
+}
+
+impl DependencyGraph {
+    pub fn new() -> Self {
+        DependencyGraph::default()
+    }
+
+    /// Records the files a page was built from; this replaces anything
+    /// recorded for the page when it was last built
+    pub fn set_dependencies(&mut self, page: &str, dependencies: Vec<String>) {
+        self.dependencies
+            .insert(page.to_string(), dependencies.into_iter().collect());
+    }
+
+    /// Records the files which the page's HTML was built from
+    pub fn add_page(&mut self, page: &str, html: &HtmlContent) {
+        self.set_dependencies(&canonical_path(page), html.dependencies());
+    }
+
+    /// Forgets a page (such as one which has been deleted)
+    pub fn remove_page(&mut self, page: &str) {
+        self.dependencies.remove(page);
+    }
+
+    /// The files a page depends on
+    pub fn dependencies(&self, page: &str) -> Vec<String> {
+        let mut dependencies: Vec<String> = self
+            .dependencies
+            .get(page)
+            .map(|d| d.iter().cloned().collect())
+            .unwrap_or_default();
+        dependencies.sort();
+
+        dependencies
+    }
+
+    /// The pages which need to be rebuilt when `file` changes; a page
+    /// which depends on another page that changes is included too.
+    pub fn dependents(&self, file: &str) -> Vec<String> {
+        let mut dependents = HashSet::new();
+        let mut changed = vec![canonical_path(file)];
+        while let Some(file) = changed.pop() {
+            for (page, dependencies) in &self.dependencies {
+                if dependencies.contains(&file) && dependents.insert(page.clone()) {
+                    changed.push(page.clone());
+                }
+            }
+        }
+        let mut dependents: Vec<String> = dependents.into_iter().collect();
+        dependents.sort();
+
+        dependents
+    }
+}
+
+fn canonical_path(file: &str) -> String {
+    canonical(Path::new(file)).to_string_lossy().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::Config,
+        models::markdown::{MarkdownContent, MarkdownContentRaw},
+    };
+    use std::fs;
+
+    #[test]
+    fn dependents_are_found_transitively() {
+        let mut graph = DependencyGraph::new();
+        graph.set_dependencies("a.md", vec![String::from("partial.md")]);
+        graph.set_dependencies("b.md", vec![String::from("a.md"), String::from("code.ts")]);
+        graph.set_dependencies("c.md", vec![String::from("code.ts")]);
+
+        assert_eq!(graph.dependents("partial.md"), vec!["a.md", "b.md"]);
+        assert_eq!(graph.dependents("code.ts"), vec!["b.md", "c.md"]);
+
+        graph.set_dependencies("b.md", vec![]);
+        assert_eq!(graph.dependents("partial.md"), vec!["a.md"]);
+        graph.remove_page("c.md");
+        assert!(graph.dependents("code.ts").is_empty());
+    }
+    #[test]
+    fn dependents_are_found_by_canonical_path() {
+        let dir = std::env::temp_dir().join("dm-dependency-graph");
+        fs::create_dir_all(dir.join("parts")).unwrap();
+        fs::write(dir.join("parts/partial.md"), "<<< ../code.ts\n").unwrap();
+        fs::write(dir.join("code.ts"), "let a = 1;\n").unwrap();
+        fs::write(dir.join("page.md"), "<<< ./parts/partial.md\n").unwrap();
+        fs::write(dir.join("other.md"), "<<< ./page.md\n").unwrap();
+
+        let config = Config::default();
+        let mut graph = DependencyGraph::new();
+        for page in ["page.md", "other.md"] {
+            let file = dir
+                .join("parts/..")
+                .join(page)
+                .to_string_lossy()
+                .to_string();
+            let raw = MarkdownContentRaw::new(&fs::read_to_string(&file).unwrap());
+            let md = MarkdownContent::new(&raw, &config).unwrap();
+            graph.add_page(&file, &HtmlContent::for_page(&md, Some(&file), &config));
+        }
+
+        let page = canonical_path(&dir.join("page.md").to_string_lossy());
+        let other = canonical_path(&dir.join("other.md").to_string_lossy());
+        assert_eq!(
+            graph.dependents(&dir.join("code.ts").to_string_lossy()),
+            vec![other.clone(), page.clone()]
+        );
+        assert_eq!(
+            graph.dependents(&dir.join("parts/partial.md").to_string_lossy()),
+            vec![other, page]
+        );
+    }
+}
@@ -0,0 +1,63 @@
+use serde::{Deserialize, Serialize};
+
+use super::position::SourcePosition;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum Severity {
+    Error,
+    Warning,
+    Info,
+}
+
+/// A problem found on a page which does not prevent the page from
+/// being parsed but which the author will likely want to know about.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    /// where on the page the problem was found
+    pub position: SourcePosition,
+}
+
+impl Diagnostic {
+    pub fn error(message: &str, position: SourcePosition) -> Self {
+        Diagnostic {
+            severity: Severity::Error,
+            message: message.to_string(),
+            position,
+        }
+    }
+
+    pub fn warning(message: &str, position: SourcePosition) -> Self {
+        Diagnostic {
+            severity: Severity::Warning,
+            message: message.to_string(),
+            position,
+        }
+    }
+
+    pub fn info(message: &str, position: SourcePosition) -> Self {
+        Diagnostic {
+            severity: Severity::Info,
+            message: message.to_string(),
+            position,
+        }
+    }
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Info => "info",
+        };
+        write!(
+            f,
+            "{} [{}:{}]: {}",
+            severity, self.position.line, self.position.column, self.message
+        )
+    }
+}
@@ -0,0 +1,672 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::Path,
+    sync::Mutex,
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+use tracing::{debug, instrument};
+use url::Url;
+
+use crate::{
+    config::{features::link::LinkConfig, Config},
+    errors::link_err::LinkError,
+};
+
+use super::html::link::{LinkKind, LinkRef, LinkStatus};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HttpMethod {
+    Head,
+    Get,
+}
+
+/// The parts of an HTTP response which matter when validating a link
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HttpResponse {
+    pub status: u16,
+    /// the `Location` header of a redirect
+    pub location: Option<String>,
+}
+
+/// A client which can make HTTP requests on behalf of the link checker.
+///
+/// Implementations must **not** follow redirects themselves; the
+/// checker follows them so that they can be reported.
+pub trait HttpClient: Send + Sync {
+    /// Makes a request and returns the response or -- where the server
+    /// could not be reached at all -- a description of the problem.
+    fn request(&self, method: HttpMethod, url: &str) -> Result<HttpResponse, String>;
+}
+
+/// The default `HttpClient` which is backed by **ureq**
+pub struct UreqClient {
+    agent: ureq::Agent,
+}
+
+impl UreqClient {
+    pub fn new(config: &LinkConfig) -> Self {
+        let agent = ureq::AgentBuilder::new()
+            .redirects(0)
+            .timeout(Duration::from_millis(config.external_timeout_ms))
+            .user_agent("darkmatter-link-checker")
+            .build();
+
+        UreqClient { agent }
+    }
+}
+
+impl HttpClient for UreqClient {
+    fn request(&self, method: HttpMethod, url: &str) -> Result<HttpResponse, String> {
+        let request = match method {
+            HttpMethod::Head => self.agent.head(url),
+            HttpMethod::Get => self.agent.get(url),
+        };
+        let response = match request.call() {
+            Ok(response) => response,
+            Err(ureq::Error::Status(_, response)) => response,
+            Err(ureq::Error::Transport(e)) => return Err(e.to_string()),
+        };
+
+        Ok(HttpResponse {
+            status: response.status(),
+            location: response.header("location").map(|l| l.to_string()),
+        })
+    }
+}
+
+/// A previously determined result for an external URL
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct CachedCheck {
+    pub status: LinkStatus,
+    /// seconds since the UNIX epoch when the check was made
+    pub checked_at: u64,
+}
+
+/// The results of external link checks which can be persisted
+/// between builds so that third-party sites are not checked on
+/// every build.
+#[derive(Debug, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ExternalLinkCache {
+    entries: HashMap<String, CachedCheck>,
+}
+
+impl ExternalLinkCache {
+    pub fn new() -> Self {
+        ExternalLinkCache::default()
+    }
+
+    /// Loads the cache from a file; a missing file is an empty cache
+    pub fn load(file: &str) -> Result<Self, LinkError> {
+        if !Path::new(file).exists() {
+            return Ok(ExternalLinkCache::new());
+        }
+        let content =
+            fs::read_to_string(file).map_err(|e| LinkError::CacheFile(file.to_string(), e))?;
+
+        serde_json::from_str(&content).map_err(|e| LinkError::CacheFormat(file.to_string(), e))
+    }
+
+    /// Saves the cache to a file
+    pub fn save(&self, file: &str) -> Result<(), LinkError> {
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| LinkError::CacheFormat(file.to_string(), e))?;
+
+        fs::write(file, content).map_err(|e| LinkError::CacheFile(file.to_string(), e))
+    }
+
+    /// Gets the cached result for a URL if it is younger than `ttl` seconds
+    pub fn get(&self, url: &str, ttl: u64) -> Option<&LinkStatus> {
+        self.entries
+            .get(url)
+            .filter(|c| now().saturating_sub(c.checked_at) < ttl)
+            .map(|c| &c.status)
+    }
+
+    pub fn insert(&mut self, url: &str, status: LinkStatus) {
+        self.entries.insert(
+            url.to_string(),
+            CachedCheck {
+                status,
+                checked_at: now(),
+            },
+        );
+    }
+
+    /// Removes all entries which are older than `ttl` seconds
+    pub fn prune(&mut self, ttl: u64) {
+        let now = now();
+        self.entries
+            .retain(|_, c| now.saturating_sub(c.checked_at) < ttl);
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Ensures that requests to any one host are spaced out by at least
+/// the configured interval.
+#[derive(Debug)]
+struct HostThrottle {
+    interval: Duration,
+    next_slot: Mutex<HashMap<String, Instant>>,
+}
+
+impl HostThrottle {
+    fn new(interval: Duration) -> Self {
+        HostThrottle {
+            interval,
+            next_slot: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// blocks until a request to the host is allowed
+    fn wait(&self, host: &str) {
+        let slot = {
+            let mut next_slot = self.next_slot.lock().unwrap();
+            let now = Instant::now();
+            let slot = match next_slot.get(host) {
+                Some(slot) if *slot > now => *slot,
+                _ => now,
+            };
+            next_slot.insert(host.to_string(), slot + self.interval);
+            slot
+        };
+
+        let now = Instant::now();
+        if slot > now {
+            thread::sleep(slot - now);
+        }
+    }
+}
+
+/// Validates external links over HTTP using a `HEAD` request (falling
+/// back to `GET` for servers which do not support it) while limiting
+/// concurrency, rate limiting per host, following redirects and caching
+/// the results.
+///
+/// A checker is meant to be shared by all the pages of a build so that
+/// the per-host rate limit applies across the pages.
+pub struct ExternalLinkChecker {
+    config: LinkConfig,
+    client: Box<dyn HttpClient>,
+    cache: Mutex<ExternalLinkCache>,
+    throttle: HostThrottle,
+}
+
+impl ExternalLinkChecker {
+    /// Creates a checker which uses the default HTTP client and loads
+    /// the cache file (if one is configured)
+    pub fn new(config: &Config) -> Result<Self, LinkError> {
+        let client = Box::new(UreqClient::new(&config.features.links));
+        ExternalLinkChecker::with_client(config, client)
+    }
+
+    /// Creates a checker which makes requests with the given client
+    pub fn with_client(config: &Config, client: Box<dyn HttpClient>) -> Result<Self, LinkError> {
+        let config = config.features.links.clone();
+        let cache = match &config.external_cache_file {
+            Some(file) => ExternalLinkCache::load(file)?,
+            None => ExternalLinkCache::new(),
+        };
+
+        Ok(ExternalLinkChecker {
+            throttle: HostThrottle::new(Duration::from_millis(config.external_host_interval_ms)),
+            config,
+            client,
+            cache: Mutex::new(cache),
+        })
+    }
+
+    /// Checks the external links in the list (when `validate_external_links`
+    /// is configured) and sets their status; only `http` and `https` links
+    /// are checked (a protocol-relative `//` link is checked over `https`)
+    /// so links such as `tel:` or `ftp:` are left without a status.
+    #[instrument(skip_all)]
+    pub fn check_links(&self, links: &mut [LinkRef]) {
+        if !self.config.validate_external_links {
+            return;
+        }
+        let urls: Vec<String> = links
+            .iter()
+            .filter(|l| l.kind == LinkKind::External)
+            .filter_map(|l| http_url(&l.href))
+            .collect();
+        let results = self.check_urls(&urls);
+
+        for link in links.iter_mut() {
+            let status = http_url(&link.href).and_then(|url| results.get(&url));
+            if let Some(status) = status {
+                link.status = Some(status.clone());
+            }
+        }
+    }
+
+    /// Checks each unique URL -- using the cache where possible -- and
+    /// returns the status of each.
+    pub fn check_urls(&self, urls: &[String]) -> HashMap<String, LinkStatus> {
+        let ttl = self.config.external_cache_ttl_secs;
+        let mut results: HashMap<String, LinkStatus> = HashMap::new();
+        let mut queue: Vec<String> = vec![];
+        let mut seen: HashSet<&str> = HashSet::new();
+
+        {
+            let cache = self.cache.lock().unwrap();
+            for url in urls {
+                if !seen.insert(url) {
+                    continue;
+                }
+                match cache.get(url, ttl) {
+                    Some(status) => {
+                        results.insert(url.clone(), status.clone());
+                    }
+                    None => queue.push(url.clone()),
+                }
+            }
+        }
+        debug!(cached = results.len(), to_check = queue.len());
+
+        let queue = Mutex::new(queue);
+        let checked: Mutex<Vec<(String, Result<LinkStatus, String>)>> = Mutex::new(vec![]);
+        let workers = self.config.external_concurrency.max(1);
+
+        thread::scope(|scope| {
+            for _ in 0..workers {
+                scope.spawn(|| loop {
+                    let url = match queue.lock().unwrap().pop() {
+                        Some(url) => url,
+                        None => break,
+                    };
+                    let checked_url = self.check_url(&url);
+                    checked.lock().unwrap().push((url, checked_url));
+                });
+            }
+        });
+
+        // a server which could not be reached at all (a timeout, a DNS
+        // failure, etc.) may well be reachable next time so isn't cached
+        let mut cache = self.cache.lock().unwrap();
+        for (url, checked_url) in checked.into_inner().unwrap() {
+            let status = match checked_url {
+                Ok(status) => {
+                    cache.insert(&url, status.clone());
+                    status
+                }
+                Err(e) => LinkStatus::Unreachable(e),
+            };
+            results.insert(url, status);
+        }
+
+        results
+    }
+
+    /// Persists the cache to the configured cache file (if any)
+    pub fn save_cache(&self) -> Result<(), LinkError> {
+        match &self.config.external_cache_file {
+            Some(file) => {
+                let mut cache = self.cache.lock().unwrap();
+                cache.prune(self.config.external_cache_ttl_secs);
+                cache.save(file)
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Checks a single URL, following any redirects; an error is returned
+    /// when a server could not be reached at all
+    fn check_url(&self, url: &str) -> Result<LinkStatus, String> {
+        let mut current = match Url::parse(url) {
+            Ok(current) => current,
+            Err(e) => return Ok(LinkStatus::Unreachable(e.to_string())),
+        };
+        let mut redirects = 0;
+
+        loop {
+            let response = self.request(&current)?;
+
+            match (response.status, response.location) {
+                (300..=399, Some(location)) => {
+                    if redirects >= self.config.external_max_redirects {
+                        return Ok(LinkStatus::Unreachable(format!(
+                            "more than {} redirects",
+                            self.config.external_max_redirects
+                        )));
+                    }
+                    current = match current.join(&location) {
+                        Ok(next) => next,
+                        Err(e) => return Ok(LinkStatus::Unreachable(e.to_string())),
+                    };
+                    redirects += 1;
+                }
+                (200..=299, _) if redirects > 0 => {
+                    return Ok(LinkStatus::Redirected(current.to_string()));
+                }
+                (200..=299, _) => return Ok(LinkStatus::Valid),
+                (status, _) => return Ok(LinkStatus::HttpStatus(status)),
+            }
+        }
+    }
+
+    /// Makes a `HEAD` request and falls back to `GET` when the server
+    /// does not allow it
+    fn request(&self, url: &Url) -> Result<HttpResponse, String> {
+        let host = url.host_str().unwrap_or_default();
+        self.throttle.wait(host);
+        let response = self.client.request(HttpMethod::Head, url.as_str());
+
+        match &response {
+            Ok(r) if r.status == 405 || r.status == 501 => {
+                self.throttle.wait(host);
+                self.client.request(HttpMethod::Get, url.as_str())
+            }
+            Err(_) => {
+                self.throttle.wait(host);
+                self.client.request(HttpMethod::Get, url.as_str())
+            }
+            _ => response,
+        }
+    }
+}
+
+/// The URL an external link is checked with; `None` for links which
+/// aren't served over HTTP
+fn http_url(href: &str) -> Option<String> {
+    let href = href.trim();
+    if href.starts_with("//") {
+        return Some(format!("https:{}", href));
+    }
+    let lower = href.to_lowercase();
+
+    (lower.starts_with("http://") || lower.starts_with("https://")).then(|| href.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::position::SourcePosition;
+    use std::{
+        io::{BufRead, BufReader, Write},
+        net::TcpListener,
+        sync::atomic::{AtomicUsize, Ordering},
+        sync::Arc,
+    };
+
+    /// Starts a local server which answers requests based on their path
+    /// and returns the base URL it can be reached on.
+    fn mock_server() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = match stream {
+                    Ok(stream) => stream,
+                    Err(_) => continue,
+                };
+                let mut request_line = String::new();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                reader.read_line(&mut request_line).unwrap();
+                loop {
+                    let mut header = String::new();
+                    if reader.read_line(&mut header).unwrap() == 0 || header == "\r\n" {
+                        break;
+                    }
+                }
+                let mut parts = request_line.split_whitespace();
+                let method = parts.next().unwrap_or_default();
+                let path = parts.next().unwrap_or_default();
+
+                let (status, location) = match (method, path) {
+                    (_, "/ok") => ("200 OK", None),
+                    (_, "/moved") => ("301 Moved Permanently", Some("/ok")),
+                    (_, "/loop") => ("302 Found", Some("/loop")),
+                    ("HEAD", "/no-head") => ("405 Method Not Allowed", None),
+                    ("GET", "/no-head") => ("200 OK", None),
+                    _ => ("404 Not Found", None),
+                };
+                let location = location
+                    .map(|l| format!("Location: {}\r\n", l))
+                    .unwrap_or_default();
+                let response = format!(
+                    "HTTP/1.1 {}\r\n{}Content-Length: 0\r\nConnection: close\r\n\r\n",
+                    status, location
+                );
+                let _ = stream.write_all(response.as_bytes());
+            }
+        });
+
+        base
+    }
+
+    fn config() -> Config {
+        let mut config = Config::default();
+        config.features.links.validate_external_links = true;
+        config.features.links.external_host_interval_ms = 0;
+        config
+    }
+
+    fn link(href: &str) -> LinkRef {
+        LinkRef {
+            href: href.to_string(),
+            kind: LinkKind::from(href),
+            text: String::new(),
+            position: SourcePosition::from_offset("", 0),
+            status: None,
+        }
+    }
+
+    /// A client which always responds with a 200 and counts requests
+    struct CountingClient(Arc<AtomicUsize>);
+
+    impl HttpClient for CountingClient {
+        fn request(&self, _method: HttpMethod, _url: &str) -> Result<HttpResponse, String> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            Ok(HttpResponse {
+                status: 200,
+                location: None,
+            })
+        }
+    }
+
+    /// A client which records the URLs it is asked for and can't reach
+    /// any host named `down.test`
+    struct RecordingClient(Arc<Mutex<Vec<String>>>);
+
+    impl HttpClient for RecordingClient {
+        fn request(&self, _method: HttpMethod, url: &str) -> Result<HttpResponse, String> {
+            self.0.lock().unwrap().push(url.to_string());
+            match url.contains("down.test") {
+                true => Err(String::from("dns error")),
+                false => Ok(HttpResponse {
+                    status: 200,
+                    location: None,
+                }),
+            }
+        }
+    }
+
+    #[test]
+    fn external_links_checked_against_server() {
+        let base = mock_server();
+        let checker = ExternalLinkChecker::new(&config()).unwrap();
+        let mut links = vec![
+            link(&format!("{}/ok", base)),
+            link(&format!("{}/moved", base)),
+            link(&format!("{}/missing", base)),
+            link(&format!("{}/no-head", base)),
+            link(&format!("{}/loop", base)),
+            link("./internal.md"),
+        ];
+        checker.check_links(&mut links);
+
+        assert_eq!(links[0].status, Some(LinkStatus::Valid));
+        assert_eq!(
+            links[1].status,
+            Some(LinkStatus::Redirected(format!("{}/ok", base)))
+        );
+        assert_eq!(links[2].status, Some(LinkStatus::HttpStatus(404)));
+        assert_eq!(links[3].status, Some(LinkStatus::Valid));
+        assert!(matches!(links[4].status, Some(LinkStatus::Unreachable(_))));
+        assert_eq!(links[5].status, None);
+    }
+
+    #[test]
+    fn external_links_unchecked_unless_configured() {
+        let count = Arc::new(AtomicUsize::new(0));
+        let checker = ExternalLinkChecker::with_client(
+            &Config::default(),
+            Box::new(CountingClient(count.clone())),
+        )
+        .unwrap();
+        let mut links = vec![link("https://acme.com")];
+        checker.check_links(&mut links);
+
+        assert_eq!(links[0].status, None);
+        assert_eq!(count.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn external_links_only_checked_over_http() {
+        let requested = Arc::new(Mutex::new(vec![]));
+        let checker = ExternalLinkChecker::with_client(
+            &config(),
+            Box::new(RecordingClient(requested.clone())),
+        )
+        .unwrap();
+        let mut links = vec![
+            link("https://acme.com"),
+            link("//cdn.acme.com/lib.js"),
+            link("tel:+15555550100"),
+            link("ftp://files.acme.com"),
+            link("javascript:void(0)"),
+        ];
+        checker.check_links(&mut links);
+
+        assert_eq!(links[0].status, Some(LinkStatus::Valid));
+        assert_eq!(links[1].status, Some(LinkStatus::Valid));
+        assert!(links[2..].iter().all(|l| l.status.is_none()));
+        let mut requested = requested.lock().unwrap().clone();
+        requested.sort();
+        assert_eq!(
+            requested,
+            vec!["https://acme.com/", "https://cdn.acme.com/lib.js"]
+        );
+    }
+
+    #[test]
+    fn external_transport_errors_are_not_cached() {
+        let requested = Arc::new(Mutex::new(vec![]));
+        let checker = ExternalLinkChecker::with_client(
+            &config(),
+            Box::new(RecordingClient(requested.clone())),
+        )
+        .unwrap();
+        let urls = vec![String::from("https://down.test")];
+
+        let results = checker.check_urls(&urls);
+        assert_eq!(
+            results.get("https://down.test"),
+            Some(&LinkStatus::Unreachable(String::from("dns error")))
+        );
+        assert!(checker.cache.lock().unwrap().is_empty());
+
+        // HEAD then GET on each attempt
+        checker.check_urls(&urls);
+        assert_eq!(requested.lock().unwrap().len(), 4);
+    }
+
+    #[test]
+    fn external_links_are_deduplicated_and_cached() {
+        let count = Arc::new(AtomicUsize::new(0));
+        let checker =
+            ExternalLinkChecker::with_client(&config(), Box::new(CountingClient(count.clone())))
+                .unwrap();
+        let urls = vec![
+            String::from("https://acme.com/a"),
+            String::from("https://acme.com/a"),
+            String::from("https://acme.com/b"),
+        ];
+
+        let results = checker.check_urls(&urls);
+        assert_eq!(results.len(), 2);
+        assert_eq!(count.load(Ordering::SeqCst), 2);
+
+        checker.check_urls(&urls);
+        assert_eq!(count.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn external_link_cache_persists_and_expires() {
+        let file = std::env::temp_dir().join(format!("dm-link-cache-{}.json", std::process::id()));
+        let file = file.to_str().unwrap().to_string();
+        let mut config = config();
+        config.features.links.external_cache_file = Some(file.clone());
+
+        let count = Arc::new(AtomicUsize::new(0));
+        let checker =
+            ExternalLinkChecker::with_client(&config, Box::new(CountingClient(count.clone())))
+                .unwrap();
+        checker.check_urls(&[String::from("https://acme.com")]);
+        checker.save_cache().unwrap();
+
+        let cache = ExternalLinkCache::load(&file).unwrap();
+        assert_eq!(cache.get("https://acme.com", 60), Some(&LinkStatus::Valid));
+        assert_eq!(cache.get("https://acme.com", 0), None);
+
+        // a new checker uses the persisted results
+        let checker =
+            ExternalLinkChecker::with_client(&config, Box::new(CountingClient(count.clone())))
+                .unwrap();
+        checker.check_urls(&[String::from("https://acme.com")]);
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+
+        fs::remove_file(&file).unwrap();
+    }
+
+    #[test]
+    fn external_requests_to_same_host_are_throttled() {
+        let throttle = HostThrottle::new(Duration::from_millis(30));
+        let start = Instant::now();
+        throttle.wait("acme.com");
+        throttle.wait("other.com");
+        throttle.wait("acme.com");
+        throttle.wait("acme.com");
+
+        assert!(start.elapsed() >= Duration::from_millis(60));
+        assert!(start.elapsed() < Duration::from_millis(500));
+    }
+
+    #[test]
+    fn external_requests_are_throttled_across_pages() {
+        let mut config = config();
+        config.features.links.external_host_interval_ms = 40;
+        let count = Arc::new(AtomicUsize::new(0));
+        let checker =
+            ExternalLinkChecker::with_client(&config, Box::new(CountingClient(count.clone())))
+                .unwrap();
+        let start = Instant::now();
+        checker.check_links(&mut [link("https://acme.com/a")]);
+        checker.check_links(&mut [link("https://acme.com/b")]);
+        checker.check_links(&mut [link("https://acme.com/c")]);
+
+        assert_eq!(count.load(Ordering::SeqCst), 3);
+        assert!(start.elapsed() >= Duration::from_millis(80));
+    }
+}
@@ -316,8 +316,7 @@ impl Frontmatter {
 
         // Excerpt content extracted from the body parse
         let excerpt = fm.excerpt;
-        let raw = MarkdownContentRaw::new(&matter.content);
-        let markdown = MarkdownContent::new(&raw, config)?;
+        let markdown = MarkdownContent::extracted(raw_md, &matter.content);
         // Work with excerpt based on strategy
         let preferred = match config.features.frontmatter.excerpt_strategy {
             ExcerptStrategy::Auto => [&frontmatter.excerpt, &excerpt],
@@ -503,6 +502,22 @@ impl Frontmatter {
         Ok(())
     }
 
+    /// Adds the values of another document's frontmatter (such as one
+    /// included into the page) which this frontmatter does not set itself
+    pub fn merge(&mut self, other: &Frontmatter) -> Result<(), FrontmatterError> {
+        let selfy = self.clone();
+        let mut current = GenericFrontmatter::from(&selfy);
+        let other = GenericFrontmatter::from(other);
+
+        for (k, v) in other.0 {
+            if current.get(&k).is_none() {
+                current.insert(&k, &v);
+            }
+        }
+
+        self.set_values(current)
+    }
+
     pub fn apply_override_values(
         &mut self,
         overrides: &Frontmatter,
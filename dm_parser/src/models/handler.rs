@@ -1,5 +1,6 @@
 use std::collections::HashMap;
 
+use regex::Regex;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
@@ -14,6 +15,43 @@ pub enum Handler<O> {
     IfElse(String, (O, O)),
 }
 
+impl Handler<String> {
+    /// Applies the handler to some content:
+    ///
+    /// - `Static` replaces the content; `$content` in the value stands in
+    ///   for the original content
+    /// - `RegExp` replaces the matches of each pattern with its value
+    ///   (which can use `$1` style capture groups)
+    /// - `IfElse` is the first value when the pattern matches and the
+    ///   second one when it doesn't (`$content` can be used in both)
+    ///
+    /// A `Callback` is run by the client rather than here so leaves the
+    /// content as it is, as does a pattern which isn't a valid regex.
+    pub fn apply(&self, content: &str) -> String {
+        match self {
+            Handler::Callback => content.to_string(),
+            Handler::Static(value) => value.replace("$content", content),
+            Handler::RegExp(patterns) => {
+                let mut keys: Vec<&String> = patterns.keys().collect();
+                keys.sort();
+                keys.iter().fold(content.to_string(), |content, pattern| {
+                    match Regex::new(pattern) {
+                        Ok(re) => re
+                            .replace_all(&content, patterns[*pattern].as_str())
+                            .to_string(),
+                        Err(_) => content,
+                    }
+                })
+            }
+            Handler::IfElse(pattern, (then, otherwise)) => match Regex::new(pattern) {
+                Ok(re) if re.is_match(content) => then.replace("$content", content),
+                Ok(_) => otherwise.replace("$content", content),
+                Err(_) => content.to_string(),
+            },
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct FrontmatterHandler {
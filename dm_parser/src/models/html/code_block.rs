@@ -1,10 +1,657 @@
-/// Modifies the HTML based on the configuration of `options.
-/// escapeCodeTagInterpolation` and the fenced code blocks language
-/// (if language starts `!` then options configuration is reversed).
+use dm_utils::code_highlighting::{CodeBlock, Grammar, LineAnnotation, LineOptions};
+use pulldown_cmark::{escape::escape_html, CodeBlockKind, CowStr, Event, Tag};
+use regex::Regex;
+use tracing::warn;
+
+use crate::config::{features::code::CodeConfig, hooks::CodeHooks, Config};
+
+/// What the info string of a fenced code block asks for:
 ///
-/// Because we are looking at the fenced language, we'll also add that to
-/// the payload being passed through as this could be valuable for _search_
-/// or other meta features.
-pub fn escape_code_interpolation() -> Self {
-    todo!()
+/// ```md
+/// ```ts {1,3-5} # title="api.ts" footer="MIT"
+/// ```
+///
+/// is TypeScript with lines 1 and 3 through 5 highlighted, line numbers
+/// shown (`#` can also be put inside of the braces) and a heading and
+/// footer around the code.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct FenceInfo {
+    pub language: String,
+    /// the (1-based and inclusive) ranges of lines to highlight
+    pub highlight: Vec<(usize, usize)>,
+    /// whether line numbers were asked for with the `#` modifier
+    pub line_numbers: bool,
+    /// the `title` shown in the heading; this is also the code block's
+    /// filename
+    pub title: Option<String>,
+    /// the `footer` shown below the code
+    pub footer: Option<String>,
+    /// whatever else was in the info string
+    pub rest: String,
+}
+
+impl FenceInfo {
+    /// Parses a fenced code block's info string; `attribute` matches the
+    /// `title` and `footer` attributes (see `CodeBlocks::new`)
+    pub fn parse(info: &str, attribute: &Regex) -> Self {
+        let language = fence_language(info);
+        let mut fence = FenceInfo {
+            language: language.to_string(),
+            ..FenceInfo::default()
+        };
+        let mut rest = info[language.len()..].trim().to_string();
+
+        for captures in attribute.captures_iter(&rest.clone()) {
+            let value = (2..=4)
+                .find_map(|i| captures.get(i))
+                .map(|v| v.as_str().to_string());
+            match &captures[1] {
+                "title" => fence.title = value,
+                _ => fence.footer = value,
+            }
+        }
+        rest = attribute.replace_all(&rest, " ").to_string();
+
+        if let (Some(open), Some(close)) = (rest.find('{'), rest.find('}')) {
+            if open < close {
+                for token in rest[open + 1..close].split([',', ' ']) {
+                    let token = token.trim();
+                    if token == "#" {
+                        fence.line_numbers = true;
+                    } else if let Some(range) = line_range(token) {
+                        fence.highlight.push(range);
+                    }
+                }
+                rest.replace_range(open..=close, " ");
+            }
+        }
+        let mut others = vec![];
+        for token in rest.split_whitespace() {
+            match token {
+                "#" => fence.line_numbers = true,
+                token => others.push(token),
+            }
+        }
+        fence.rest = others.join(" ");
+
+        fence
+    }
+}
+
+/// A line number (`3`) or a range of lines (`3-5`)
+fn line_range(token: &str) -> Option<(usize, usize)> {
+    match token.split_once('-') {
+        Some((from, to)) => {
+            let (from, to) = (from.trim().parse().ok()?, to.trim().parse().ok()?);
+            (from <= to).then_some((from, to))
+        }
+        None => token.parse().ok().map(|line| (line, line)),
+    }
+}
+
+/// A fenced code block whose code is being gathered
+#[derive(Debug)]
+struct FencedCode {
+    fence: FenceInfo,
+    escape: bool,
+    code: String,
+}
+
+/// Highlights the fenced code blocks on a page -- wrapping each line
+/// according to the `line_class_strategy`, highlighted lines and line
+/// numbers -- along with inline code which has a language hint, and
+/// handles the escaping of VueJS interpolation in code
+#[derive(Debug)]
+pub struct CodeBlocks {
+    config: CodeConfig,
+    hooks: CodeHooks,
+    /// whether the `{{ }}` of the code block being handled are escaped;
+    /// `None` when not inside of a code block
+    escape: Option<bool>,
+    /// the fenced code block being handled
+    fenced: Option<FencedCode>,
+    /// the languages of the fenced code blocks in the order first used
+    languages: Vec<String>,
+    /// matches the `title` and `footer` attributes of an info string
+    attribute: Regex,
+}
+
+impl CodeBlocks {
+    pub fn new(config: &Config) -> Self {
+        CodeBlocks {
+            config: config.features.code.clone(),
+            hooks: config.hooks.code.clone(),
+            escape: None,
+            fenced: None,
+            languages: vec![],
+            attribute: Regex::new(r#"\b(title|footer)=(?:"([^"]*)"|'([^']*)'|([^\s{}]+))"#)
+                .unwrap(),
+        }
+    }
+
+    pub fn handle<'a>(&mut self, event: Event<'a>) -> Option<Event<'a>> {
+        let event = self.escape_code_interpolation(event);
+        match event {
+            Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(info))) => {
+                self.fenced = Some(FencedCode {
+                    fence: FenceInfo::parse(&info, &self.attribute),
+                    escape: self.escape == Some(true),
+                    code: String::new(),
+                });
+                None
+            }
+            Event::Text(text) if self.fenced.is_some() => {
+                if let Some(fenced) = self.fenced.as_mut() {
+                    fenced.code.push_str(&text);
+                }
+                None
+            }
+            Event::End(Tag::CodeBlock(CodeBlockKind::Fenced(_))) => {
+                let fenced = self.fenced.take()?;
+                Some(Event::Html(self.highlight(fenced).into()))
+            }
+            Event::Code(code) => Some(self.inline(code)),
+            event => Some(event),
+        }
+    }
+
+    /// The languages of the page's fenced code blocks (each listed once)
+    pub fn finish(self) -> Vec<String> {
+        self.languages
+    }
+
+    /// The HTML for a fenced code block; when the language is missing or
+    /// can't be highlighted the detected language (recorded with its
+    /// confidence in `data-` attributes) or else the configured default
+    /// language is used and the `block_content_formatted` hook is given
+    /// the final HTML
+    fn highlight(&self, fenced: FencedCode) -> String {
+        let fence = &fenced.fence;
+        // a `diff-{lang}` block is highlighted as `lang`
+        let (diff, language) = match fence.language.strip_prefix("diff-") {
+            Some(language) if self.config.code_annotations && !language.is_empty() => {
+                (true, language)
+            }
+            _ => (false, fence.language.as_str()),
+        };
+        let block = CodeBlock::new(&fenced.code);
+        let block = match block.with_themes(&self.config.theme_light, &self.config.theme_dark) {
+            Ok(block) => block,
+            Err(e) => {
+                warn!("{}", e);
+                CodeBlock::new(&fenced.code)
+            }
+        };
+        let mut block = block.with_lines(LineOptions {
+            strategy: self.config.line_class_strategy,
+            line_numbers: fence.line_numbers || self.config.line_numbers,
+            highlight: match self.config.highlight_lines {
+                true => fence.highlight.clone(),
+                false => vec![],
+            },
+            ..LineOptions::default()
+        });
+        block.filename = fence.title.clone();
+        if self.config.code_annotations {
+            block.annotate(diff);
+        }
+        // without a known language it's worked out from the title, first
+        // line or content of the code before falling back to the defaults
+        let known = !language.is_empty() && Grammar::get(language).is_some();
+        let detected = match known {
+            true => None,
+            false => block.detect_language(),
+        };
+        block.language = Some(match (&detected, language) {
+            (_, language) if known => language.to_string(),
+            (Some(detected), _) => detected.grammar.clone(),
+            (None, "") => self.config.default_language_for_unspecified.clone(),
+            (None, _) => self.config.default_language_for_unknown.clone(),
+        });
+
+        let code = match block.as_html(&false) {
+            Ok(html) => html,
+            Err(_) => {
+                let mut escaped = String::new();
+                escape_html(&mut escaped, &fenced.code).unwrap();
+                escaped
+            }
+        };
+        let code = match fenced.escape {
+            true => code
+                .replace("{{", "&#123;&#123;")
+                .replace("}}", "&#125;&#125;"),
+            false => code,
+        };
+        let mut classes = vec![];
+        if block.lines.line_numbers {
+            classes.push("line-numbers");
+        }
+        let annotated = |kinds: &[LineAnnotation]| {
+            block.lines.annotations.iter().any(|(_, a)| kinds.contains(a))
+        };
+        if annotated(&[LineAnnotation::Added, LineAnnotation::Removed]) {
+            classes.push("has-diff");
+        }
+        if annotated(&[LineAnnotation::Focus]) {
+            classes.push("has-focused-lines");
+        }
+        if annotated(&[LineAnnotation::Error, LineAnnotation::Warning]) {
+            classes.push("has-highlighted");
+        }
+        let pre = match classes.is_empty() {
+            true => String::from("<pre>"),
+            false => format!("<pre class=\"{}\">", classes.join(" ")),
+        };
+        let mut class = String::new();
+        if !fence.language.is_empty() {
+            class.push_str(" class=\"language-");
+            escape_html(&mut class, &fence.language).unwrap();
+            class.push('"');
+        }
+        if let Some(detected) = &detected {
+            class.push_str(" data-language=\"");
+            escape_html(&mut class, &detected.grammar).unwrap();
+            class.push_str(&format!("\" data-confidence=\"{}\"", detected.confidence));
+        }
+
+        let html = format!("{}<code{}>{}</code></pre>\n", pre, class, code);
+        let html = self.wrap(fence, html);
+
+        match &self.hooks.block_content_formatted {
+            Some(hook) => hook.apply(&html),
+            None => html,
+        }
+    }
+
+    /// Inline code with a language hint at its end (`` `let x = 1;{rust}` ``)
+    /// is highlighted as that language -- or as the
+    /// `default_language_for_inline` when the language isn't known -- while
+    /// the `inline_code` hook is given the code before it's formatted and
+    /// the `inline_code_formatted` hook the final HTML
+    fn inline<'a>(&self, code: CowStr<'a>) -> Event<'a> {
+        let (text, hint) = match self.config.highlight_inline {
+            true => self.inline_hint(&code),
+            false => (&*code, None),
+        };
+        let language = match hint {
+            Some(language) => Some(language),
+            None if self.config.highlight_unhinted_inline => {
+                Some(self.config.default_language_for_inline.as_str())
+            }
+            None => None,
+        };
+        let escape = self.config.escape_code_interpolation && text.contains("{{");
+        if language.is_none()
+            && !escape
+            && self.hooks.inline_code.is_none()
+            && self.hooks.inline_code_formatted.is_none()
+        {
+            return Event::Code(code);
+        }
+
+        let text = match &self.hooks.inline_code {
+            Some(hook) => hook.apply(text),
+            None => text.to_string(),
+        };
+        let highlighted = language.and_then(|language| {
+            let block = CodeBlock::new_with_lang(&text, language).ok()?;
+            block.as_inline_html().ok().map(|html| (language, html))
+        });
+        let mut html = String::from("<code");
+        let content = match highlighted {
+            Some((language, spans)) => {
+                html.push_str(" class=\"language-");
+                escape_html(&mut html, language).unwrap();
+                html.push('"');
+                spans
+            }
+            None => {
+                let mut escaped = String::new();
+                escape_html(&mut escaped, &text).unwrap();
+                escaped
+            }
+        };
+        let content = match self.config.escape_code_interpolation {
+            true => content
+                .replace("{{", "&#123;&#123;")
+                .replace("}}", "&#125;&#125;"),
+            false => content,
+        };
+        html.push('>');
+        html.push_str(&content);
+        html.push_str("</code>");
+
+        let html = match &self.hooks.inline_code_formatted {
+            Some(hook) => hook.apply(&html),
+            None => html,
+        };
+        Event::Html(html.into())
+    }
+
+    /// Splits inline code into the code and the language hint at its end
+    /// (e.g. `rust` for `let x = 1;{rust}`); what looks like a hint is only
+    /// taken off when it names a known language so `${name}` or `obj{a}`
+    /// are left whole
+    fn inline_hint<'c>(&self, code: &'c str) -> (&'c str, Option<&'c str>) {
+        let open = self.config.inline_language_open.as_str();
+        let close = self.config.inline_language_close.as_str();
+        let hint = code.strip_suffix(close).and_then(|rest| {
+            let at = rest.rfind(open)?;
+            let (text, language) = (&rest[..at], &rest[at + open.len()..]);
+            let valid = !language.is_empty()
+                && language
+                    .chars()
+                    .all(|c| c.is_alphanumeric() || "+#-_.".contains(c));
+            let known = valid && Grammar::get(language).is_some();
+            (known && !text.trim().is_empty()).then_some((text, language))
+        });
+
+        match hint {
+            Some((text, language)) => (text, Some(language)),
+            None => (code, None),
+        }
+    }
+
+    /// Puts the code block inside of a wrapper with a heading bar (the
+    /// title, language badge and clipboard button) and a footer when
+    /// there is something to show in either
+    fn wrap(&self, fence: &FenceInfo, html: String) -> String {
+        let mut heading = String::new();
+        if self.config.allow_code_block_heading {
+            if let Some(title) = &fence.title {
+                heading.push_str("<span class=\"code-title\">");
+                escape_html(&mut heading, title).unwrap();
+                heading.push_str("</span>");
+            }
+            if self.config.show_language && !fence.language.is_empty() {
+                heading.push_str("<span class=\"code-language\">");
+                escape_html(&mut heading, &fence.language).unwrap();
+                heading.push_str("</span>");
+            }
+            if self.config.show_clipboard {
+                heading.push_str(
+                    "<button class=\"code-clipboard\" type=\"button\" aria-label=\"Copy code\"></button>",
+                );
+            }
+        }
+        let mut footer = String::new();
+        if self.config.allow_code_block_footer {
+            if let Some(text) = &fence.footer {
+                footer.push_str("<div class=\"code-footer\">");
+                escape_html(&mut footer, text).unwrap();
+                footer.push_str("</div>\n");
+            }
+        }
+        if heading.is_empty() && footer.is_empty() {
+            return html;
+        }
+
+        let mut wrapper = String::from("<div class=\"code-block");
+        if !fence.language.is_empty() {
+            wrapper.push_str(" language-");
+            escape_html(&mut wrapper, &fence.language).unwrap();
+        }
+        wrapper.push_str("\">\n");
+        if !heading.is_empty() {
+            wrapper.push_str(&format!("<div class=\"code-heading\">{}</div>\n", heading));
+        }
+        wrapper.push_str(&html);
+        wrapper.push_str(&footer);
+        wrapper.push_str("</div>\n");
+
+        wrapper
+    }
+
+    /// Modifies the HTML based on the configuration of `options.
+    /// escapeCodeTagInterpolation` and the fenced code blocks language
+    /// (if language starts `!` then options configuration is reversed).
+    ///
+    /// Because we are looking at the fenced language, we'll also add that to
+    /// the payload being passed through as this could be valuable for _search_
+    /// or other meta features.
+    fn escape_code_interpolation<'a>(&mut self, event: Event<'a>) -> Event<'a> {
+        match event {
+            Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(info))) => {
+                let (reversed, info) = match info.strip_prefix('!') {
+                    Some(info) => (true, CowStr::from(info.to_string())),
+                    None => (false, info),
+                };
+                self.escape = Some(self.config.escape_code_interpolation != reversed);
+                let language = fence_language(&info);
+                if !language.is_empty() && !self.languages.iter().any(|l| l == language) {
+                    self.languages.push(language.to_string());
+                }
+
+                Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(info)))
+            }
+            Event::Start(Tag::CodeBlock(kind)) => {
+                self.escape = Some(self.config.escape_code_interpolation);
+                Event::Start(Tag::CodeBlock(kind))
+            }
+            Event::End(Tag::CodeBlock(kind)) => {
+                self.escape = None;
+                Event::End(Tag::CodeBlock(kind))
+            }
+            Event::Text(text)
+                if self.fenced.is_none() && self.escape == Some(true) && text.contains("{{") =>
+            {
+                Event::Html(escape_mustache(&text).into())
+            }
+            event => event,
+        }
+    }
+}
+
+/// The language of a fenced code block's info string (e.g. `ts` for
+/// `ts {1,3}`)
+pub(crate) fn fence_language(info: &str) -> &str {
+    let end = info
+        .find(|c: char| c.is_whitespace() || c == '{')
+        .unwrap_or(info.len());
+
+    &info[..end]
+}
+
+/// HTML escapes the code and then replaces the `{{` and `}}` characters
+/// with entities so that VueJS doesn't treat them as interpolation
+fn escape_mustache(code: &str) -> String {
+    let mut escaped = String::with_capacity(code.len() + 16);
+    escape_html(&mut escaped, code).unwrap();
+
+    escaped
+        .replace("{{", "&#123;&#123;")
+        .replace("}}", "&#125;&#125;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::features::code::LineClassStrategy,
+        models::{handler::Handler, html::HtmlContent, markdown::MarkdownContentRaw},
+    };
+
+    fn html(md: &str, config: &Config) -> HtmlContent {
+        let (md, _) = MarkdownContentRaw::new(md).parse(config).unwrap();
+        HtmlContent::new(&md, config)
+    }
+
+    #[test]
+    fn interpolation_in_code_is_escaped() {
+        let html = html(
+            "Use `{{ msg }}` in a template:\n\n```vue\n<p>{{ msg }}</p>\n```\n\n```!ts\nconst a = `{{ b }}`\n```\n",
+            &Config::default(),
+        );
+
+        assert!(html
+            .content()
+            .starts_with("<p>Use <code>&#123;&#123; msg &#125;&#125;</code> in a template:</p>\n"));
+        assert!(html
+            .content()
+            .contains("<pre><code class=\"language-vue\">"));
+        // `vue` is highlighted as HTML so the tags are split across spans
+        assert!(html.content().contains("&#123;&#123; msg &#125;&#125;"));
+        assert!(!html.content().contains("{{ msg"));
+        assert!(html.content().contains("</span>{{ b }}<span"));
+        assert_eq!(html.code_languages, vec!["vue", "ts"]);
+    }
+
+    #[test]
+    fn escaping_can_be_opted_into() {
+        let mut config = Config::default();
+        config.features.code.escape_code_interpolation = false;
+        let html = html("`{{ a }}`\n\n```!vue\n{{ b }}\n```\n", &config);
+
+        assert!(html.content().starts_with("<p><code>{{ a }}</code></p>\n"));
+        assert!(html.content().contains("&#123;&#123; b &#125;&#125;"));
+    }
+
+    #[test]
+    fn fence_info_is_parsed() {
+        let attribute = CodeBlocks::new(&Config::default()).attribute;
+        let fence = FenceInfo::parse("ts {1,3-5} # title=\"x\"", &attribute);
+        assert_eq!(fence.language, "ts");
+        assert_eq!(fence.highlight, vec![(1, 1), (3, 5)]);
+        assert!(fence.line_numbers);
+        assert_eq!(fence.title, Some(String::from("x")));
+        assert_eq!(fence.rest, "");
+
+        let fence = FenceInfo::parse("rust{# 2}", &attribute);
+        assert_eq!(fence.language, "rust");
+        assert_eq!(fence.highlight, vec![(2, 2)]);
+        assert!(fence.line_numbers);
+        assert_eq!(FenceInfo::parse("c#", &attribute).language, "c#");
+    }
+
+    #[test]
+    fn lines_are_wrapped_and_highlighted() {
+        let content = html(
+            "```js {2} #\nlet a = 1;\nlet b = 2;\n```\n",
+            &Config::default(),
+        )
+        .content();
+
+        assert!(content.contains("<pre class=\"line-numbers\"><code class=\"language-js\"><span class=\"line line-1 odd\"><span class=\"line-number\">1</span>"));
+        assert!(content.contains(
+            "\n<span class=\"line line-2 even highlight\"><span class=\"line-number\">2</span>"
+        ));
+        assert!(content.contains("</span>\n</code></pre>\n"));
+
+        let mut config = Config::default();
+        config.features.code.line_class_strategy = LineClassStrategy::None;
+        config.features.code.highlight_lines = false;
+        let content = html("```js {2}\nlet a = 1;\nlet b = 2;\n```\n", &config).content();
+        assert!(!content.contains("line"));
+    }
+
+    #[test]
+    fn code_blocks_have_headings_and_footers() {
+        let mut config = Config::default();
+        config.features.code.show_clipboard = true;
+        let content = html(
+            "```ts title=\"api & more.ts\" footer='MIT licensed'\nlet a = 1;\n```\n",
+            &config,
+        )
+        .content();
+
+        assert!(content.starts_with("<div class=\"code-block language-ts\">\n<div class=\"code-heading\"><span class=\"code-title\">api &amp; more.ts</span><span class=\"code-language\">ts</span><button class=\"code-clipboard\" type=\"button\" aria-label=\"Copy code\"></button></div>\n<pre><code class=\"language-ts\">"));
+        assert!(content
+            .ends_with("</code></pre>\n<div class=\"code-footer\">MIT licensed</div>\n</div>\n"));
+
+        // nothing to show so there's no wrapper
+        config.features.code.show_clipboard = false;
+        config.features.code.show_language = false;
+        let content = html("```ts\nlet a = 1;\n```\n", &config).content();
+        assert!(content.starts_with("<pre><code"));
+    }
+
+    #[test]
+    fn diffs_and_annotations_become_line_classes() {
+        let content = html(
+            "```diff-rust\n fn main() {\n-    old();\n+    new();\n }\n```\n\n```ts\nlet a = 1; // [!code focus]\nlet b = 2;\n```\n",
+            &Config::default(),
+        )
+        .content();
+
+        assert!(content.contains("<pre class=\"has-diff\"><code class=\"language-diff-rust\">"));
+        assert!(content.contains("<span class=\"line line-2 even diff remove\"><span class=\"source rust\">"));
+        assert!(content.contains("<span class=\"line line-3 odd diff add\"><span class=\"source rust\">"));
+        assert!(content.contains("<pre class=\"has-focused-lines\"><code class=\"language-ts\"><span class=\"line line-1 odd focused\">"));
+        assert!(!content.contains("[!code"));
+    }
+
+    #[test]
+    fn inline_code_with_a_hint_is_highlighted() {
+        let content = html(
+            "Use `let x = 1;{rust}`, `a{nope}`, `x{y` and `{{ a }}`.\n",
+            &Config::default(),
+        )
+        .content();
+
+        assert!(content.contains("<code class=\"language-rust\"><span class=\"source rust\"><span class=\"storage type rust\">let</span>"));
+        // a hint which isn't a known language is left in the code
+        assert!(content.contains("<code>a{nope}</code>"));
+        assert!(content.contains("<code>x{y</code>"));
+        assert!(content.contains("<code>&#123;&#123; a &#125;&#125;</code>"));
+
+        let content = html("`${name}`, `obj{a}` and `fn f() {x}`\n", &Config::default()).content();
+        assert_eq!(
+            content,
+            "<p><code>${name}</code>, <code>obj{a}</code> and <code>fn f() {x}</code></p>\n"
+        );
+
+        // without a hint the whole of the code gets the default language
+        let mut config = Config::default();
+        config.features.code.highlight_unhinted_inline = true;
+        let content = html("`obj{a}`\n", &config).content();
+        assert!(content.contains("<code class=\"language-markdown\">"));
+        assert!(content.contains("obj{a}"));
+
+        let mut config = Config::default();
+        config.features.code.highlight_inline = false;
+        let content = html("`let x = 1;{rust}`\n", &config).content();
+        assert_eq!(content, "<p><code>let x = 1;{rust}</code></p>\n");
+    }
+
+    #[test]
+    fn inline_code_hooks_are_applied() {
+        let mut config = Config::default();
+        config.features.code.inline_language_open = String::from("[");
+        config.features.code.inline_language_close = String::from("]");
+        config.hooks.code.inline_code =
+            Some(Handler::Static(String::from("$content;")));
+        config.hooks.code.inline_code_formatted =
+            Some(Handler::Static(String::from("<kbd>$content</kbd>")));
+        let content = html("`let x = 1[rust]` and `plain`\n", &config).content();
+
+        assert!(content.starts_with("<p><kbd><code class=\"language-rust\">"));
+        assert!(content.contains("<span class=\"punctuation terminator rust\">;</span>"));
+        assert!(content.ends_with(" and <kbd><code>plain;</code></kbd></p>\n"));
+    }
+
+    #[test]
+    fn unlabeled_code_languages_are_detected() {
+        let content = html(
+            "```\n#!/bin/bash\necho hi\n```\n\n```nope\nSELECT id FROM users WHERE a = 1;\n```\n\n```\nhello\n```\n",
+            &Config::default(),
+        )
+        .content();
+
+        assert!(content.contains("<pre><code data-language=\"Bourne Again Shell (bash)\" data-confidence=\"0.95\"><span class=\"line line-1 odd\"><span class=\"source shell bash\">"));
+        assert!(content.contains("<code class=\"language-nope\" data-language=\"SQL\" data-confidence=\"0.58\"><span class=\"line line-1 odd\"><span class=\"source sql\">"));
+        // nothing to go on so the default is used
+        assert!(content.contains("<pre><code><span class=\"line line-1 odd\"><span class=\"text plain\">hello"));
+    }
+
+    #[test]
+    fn formatted_block_hook_is_applied() {
+        let mut config = Config::default();
+        config.hooks.code.block_content_formatted =
+            Some(Handler::Static(String::from("<figure>$content</figure>")));
+        let content = html("```\nx\n```\n", &config).content();
+
+        assert!(content.starts_with("<figure><pre><code>"));
+        assert!(content.ends_with("</code></pre>\n</figure>"));
+    }
 }
@@ -0,0 +1,448 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use dm_utils::code_highlighting::{CodeBlock, Grammar};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::Config,
+    models::{
+        diagnostic::Diagnostic,
+        position::{SourceMap, SourcePosition},
+    },
+};
+
+use super::{
+    include::{canonical, is_markdown},
+    md_attribute::fenced_ranges,
+};
+
+/// A code snippet imported into the page from another file
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct CodeImport {
+    /// the path to the snippet as written on the page
+    pub src: String,
+    /// the file which the path resolved to (in its canonical form when
+    /// it exists); the page depends on it
+    pub file: String,
+    /// whether the file could be read
+    pub found: bool,
+    /// the `#region` of the file which was imported
+    pub region: Option<String>,
+    /// the (1-based, inclusive) line ranges which were imported
+    pub lines: Vec<(usize, Option<usize>)>,
+    /// the language given to the code block
+    pub language: Option<String>,
+    /// the grammar used to highlight the snippet
+    pub grammar: Option<String>,
+    pub position: SourcePosition,
+}
+
+/// Replaces `<<<` imports with a fenced code block holding the code
+/// from another file:
+///
+/// ```md
+/// <<< ./my-code.ts
+/// <<< ./my-code.ts{3-10}
+/// <<< ./my-code.ts#setup{1,4-6 js}
+/// <<< @/snippets/build.rs
+/// ```
+///
+/// Paths are resolved against the page (or the project root when they
+/// start with `@/`). `#name` imports just the lines between the
+/// `#region name` and `#endregion` markers. The braces hold the line
+/// ranges to import and -- optionally -- the language to use in place
+/// of the one detected from the file.
+#[derive(Debug)]
+pub struct CodeImports {
+    enabled: bool,
+    /// the directory which relative imports are resolved against
+    page_dir: PathBuf,
+    /// the directory which `@/` imports are resolved against
+    project_root: PathBuf,
+    import: Regex,
+}
+
+impl CodeImports {
+    pub fn new(config: &Config, file: Option<&str>) -> Self {
+        let page_dir = file
+            .and_then(|f| Path::new(f).parent())
+            .map(|p| p.to_path_buf())
+            .unwrap_or_default();
+
+        CodeImports {
+            enabled: config.features.inline.code,
+            page_dir,
+            project_root: config.project_root.clone(),
+            import: Regex::new(r"^(\s*)<<<\s+([^\s#{]+)(?:#([\w-]+))?(?:\{([^}]*)\})?\s*$")
+                .unwrap(),
+        }
+    }
+
+    /// Replaces the imports in `md`; where each import's code block was
+    /// spliced in is recorded in `source_map`
+    pub fn expand(
+        &self,
+        md: &str,
+        source_map: &mut SourceMap,
+    ) -> (String, Vec<CodeImport>, Vec<Diagnostic>) {
+        let mut imports = vec![];
+        let mut diagnostics = vec![];
+        if !self.enabled || !md.contains("<<<") {
+            return (md.to_string(), imports, diagnostics);
+        }
+
+        let fenced = fenced_ranges(md);
+        let mut output = String::with_capacity(md.len());
+        let mut offset = 0;
+        for line in md.split_inclusive('\n') {
+            let start = offset;
+            offset += line.len();
+            let captures = match self.import.captures(line.trim_end_matches(['\n', '\r'])) {
+                Some(captures)
+                    if !fenced.iter().any(|r| r.contains(&start)) && !is_markdown(&captures[2]) =>
+                {
+                    captures
+                }
+                _ => {
+                    output.push_str(line);
+                    continue;
+                }
+            };
+
+            let position = SourcePosition::from_offset(md, start);
+            let (lines, language) = self.options(captures.get(4).map(|o| o.as_str()));
+            let mut import = CodeImport {
+                src: captures[2].to_string(),
+                file: canonical(&self.resolve(&captures[2]))
+                    .to_string_lossy()
+                    .to_string(),
+                found: false,
+                region: captures.get(3).map(|r| r.as_str().to_string()),
+                lines: lines.clone(),
+                language,
+                grammar: None,
+                position,
+            };
+            match self.snippet(&mut import, &lines, &mut diagnostics) {
+                Some(code) => {
+                    let spliced = output.len();
+                    output.push_str(&fence(&captures[1], &import, &code, line));
+                    source_map.splice(start..offset, spliced..output.len());
+                }
+                None => output.push_str(line),
+            }
+            imports.push(import);
+        }
+
+        (output, imports, diagnostics)
+    }
+
+    fn resolve(&self, src: &str) -> PathBuf {
+        match src.strip_prefix("@/") {
+            Some(path) => self.project_root.join(path),
+            None => self.page_dir.join(src),
+        }
+    }
+
+    /// The line ranges and language override found between the braces
+    fn options(&self, options: Option<&str>) -> (Vec<(usize, Option<usize>)>, Option<String>) {
+        let mut lines = vec![];
+        let mut language = None;
+        let tokens = options
+            .unwrap_or("")
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|t| !t.is_empty());
+        for token in tokens {
+            let range = match token.split_once('-') {
+                Some((from, to)) => from.parse::<usize>().ok().zip(match to {
+                    "" => Some(None),
+                    to => to.parse::<usize>().ok().map(Some),
+                }),
+                None => token.parse::<usize>().ok().map(|l| (l, Some(l))),
+            };
+            match range {
+                Some(range) => lines.push(range),
+                None => language = Some(token.to_string()),
+            }
+        }
+
+        (lines, language)
+    }
+
+    /// Reads the code to import; the grammar is detected from the file's
+    /// name unless the language was given
+    fn snippet(
+        &self,
+        import: &mut CodeImport,
+        lines: &[(usize, Option<usize>)],
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> Option<String> {
+        let code = match fs::read_to_string(&import.file) {
+            Ok(code) => code,
+            Err(e) => {
+                diagnostics.push(Diagnostic::error(
+                    &format!(
+                        "unable to import the code \"{}\" from {}: {}",
+                        import.src, import.file, e
+                    ),
+                    import.position,
+                ));
+                return None;
+            }
+        };
+        import.found = true;
+
+        let code = match &import.region {
+            Some(name) => match region(&code, name) {
+                Some(code) => code,
+                None => {
+                    diagnostics.push(Diagnostic::warning(
+                        &format!("the region \"{}\" was not found in {}", name, import.file),
+                        import.position,
+                    ));
+                    return None;
+                }
+            },
+            None => code,
+        };
+        let all: Vec<&str> = code.lines().collect();
+        let code = match lines.is_empty() {
+            true => all.join("\n"),
+            false => {
+                let mut selected = vec![];
+                for (from, to) in lines {
+                    let to = to.unwrap_or(all.len());
+                    if *from == 0 || *from > to || to > all.len() {
+                        diagnostics.push(Diagnostic::warning(
+                            &format!(
+                                "the lines {}-{} are not within the {} lines of {}",
+                                from,
+                                to,
+                                all.len(),
+                                import.src
+                            ),
+                            import.position,
+                        ));
+                    }
+                    let from = std::cmp::max(*from, 1);
+                    let to = std::cmp::min(to, all.len());
+                    if from <= to {
+                        selected.extend_from_slice(&all[from - 1..to]);
+                    }
+                }
+                selected.join("\n")
+            }
+        };
+
+        let filename = Path::new(&import.file)
+            .file_name()
+            .map(|f| f.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let block = CodeBlock::new_with_filename(&code, &filename);
+        import.grammar = match &import.language {
+            Some(language) => Grammar::get(language).map(|g| g.to_string()),
+            // syntect doesn't know every extension (e.g. `ts`) so the
+            // abbreviations which `Grammar` maps are the fallback
+            None => block.determine_grammar().map(|g| g.name).or_else(|| {
+                block
+                    .file_extension()
+                    .and_then(Grammar::get)
+                    .map(|g| g.to_string())
+            }),
+        };
+        if import.language.is_none() {
+            import.language = block.file_extension().map(|e| e.to_string());
+        }
+
+        Some(block.unformatted_code())
+    }
+}
+
+/// The lines between the `#region name` and `#endregion` markers (with
+/// any nested markers removed and the common indentation taken out)
+fn region(code: &str, name: &str) -> Option<String> {
+    let marker = |line: &str, kind: &str| {
+        line.split_whitespace()
+            .position(|w| w.trim_start_matches(['/', '*', '<', '!', '-']) == kind)
+    };
+    let lines: Vec<&str> = code.lines().collect();
+    let start = lines.iter().position(|l| {
+        marker(l, "#region")
+            .and_then(|idx| l.split_whitespace().nth(idx + 1))
+            .is_some_and(|n| n == name)
+    })?;
+
+    let mut depth = 0;
+    let mut region = vec![];
+    for line in &lines[start + 1..] {
+        if marker(line, "#region").is_some() {
+            depth += 1;
+        } else if marker(line, "#endregion").is_some() {
+            if depth == 0 {
+                return Some(dedent(&region));
+            }
+            depth -= 1;
+        } else {
+            region.push(*line);
+        }
+    }
+
+    None
+}
+
+fn dedent(lines: &[&str]) -> String {
+    let indent = lines
+        .iter()
+        .filter(|l| !l.trim().is_empty())
+        .map(|l| l.len() - l.trim_start().len())
+        .min()
+        .unwrap_or(0);
+
+    lines
+        .iter()
+        .map(|l| l.get(indent..).unwrap_or(""))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// A fenced code block for the snippet; the fence is longer than any
+/// run of backticks in the code
+fn fence(indent: &str, import: &CodeImport, code: &str, line: &str) -> String {
+    let longest = code
+        .lines()
+        .map(|l| l.trim_start().chars().take_while(|c| *c == '`').count())
+        .max()
+        .unwrap_or(0);
+    let fence = "`".repeat(std::cmp::max(3, longest + 1));
+
+    let mut block = format!(
+        "{}{}{}\n",
+        indent,
+        fence,
+        import.language.as_deref().unwrap_or("")
+    );
+    for l in code.lines() {
+        block.push_str(indent);
+        block.push_str(l);
+        block.push('\n');
+    }
+    block.push_str(indent);
+    block.push_str(&fence);
+    if line.ends_with('\n') {
+        block.push('\n');
+    }
+
+    block
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{html::HtmlContent, markdown::MarkdownContentRaw};
+
+    /// writes the snippets into a directory and returns the path for a
+    /// page which sits alongside them
+    fn page() -> String {
+        let dir = std::env::temp_dir().join("dm-code-imports");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("example.ts"),
+            "import { a } from 'a'\n\n// #region setup\nfunction setup() {\n  // #region inner\n  a()\n  // #endregion inner\n}\n// #endregion setup\nexport default setup\n",
+        )
+        .unwrap();
+
+        dir.join("page.md").to_string_lossy().to_string()
+    }
+
+    fn expand(md: &str) -> (String, Vec<CodeImport>, Vec<Diagnostic>) {
+        CodeImports::new(&Config::default(), Some(&page())).expand(md, &mut SourceMap::new())
+    }
+
+    #[test]
+    fn code_import_whole_file() {
+        let (md, imports, diagnostics) = expand("Here is some code I wrote:\n\n<<< ./example.ts\n");
+
+        assert!(md.starts_with("Here is some code I wrote:\n\n```ts\nimport { a } from 'a'\n"));
+        assert!(md.ends_with("export default setup\n```\n"));
+        assert_eq!(imports[0].grammar.as_deref(), Some("JavaScript"));
+        assert!(imports[0].file.ends_with("example.ts"));
+        assert!(imports[0].found);
+        assert!(diagnostics.is_empty());
+    }
+
+    #[test]
+    fn code_import_regions_and_lines() {
+        let (md, imports, _) =
+            expand("<<< ./example.ts#setup\n\n- list\n  <<< ./example.ts{1,9-10 js}");
+
+        assert_eq!(
+            md,
+            "```ts\nfunction setup() {\n  a()\n}\n```\n\n- list\n  ```js\n  import { a } from 'a'\n  // #endregion setup\n  export default setup\n  ```"
+        );
+        assert_eq!(imports[0].region.as_deref(), Some("setup"));
+        assert_eq!(imports[1].lines, vec![(1, Some(1)), (9, Some(10))]);
+        assert_eq!(imports[1].language.as_deref(), Some("js"));
+    }
+
+    #[test]
+    fn code_import_problems_are_reported() {
+        let md = "<<< ./missing.rs\n<<< ./example.ts#nope\n<<< ./example.ts{8-20}\n```\n<<< ./missing.rs\n```";
+        let (output, imports, diagnostics) = expand(md);
+
+        assert!(output.starts_with("<<< ./missing.rs\n<<< ./example.ts#nope\n```ts\n}\n"));
+        assert!(output.ends_with("```\n<<< ./missing.rs\n```"));
+        assert_eq!(imports.len(), 3);
+        assert!(!imports[0].found);
+        assert_eq!(diagnostics.len(), 3);
+        assert_eq!(diagnostics[1].position.line, 2);
+        assert!(diagnostics[2].message.contains("8-20"));
+    }
+
+    #[test]
+    fn code_import_project_root_paths_use_the_configured_root() {
+        let page = page();
+        let config = Config {
+            project_root: std::env::temp_dir().join("dm-code-imports"),
+            ..Config::default()
+        };
+        let (md, imports, diagnostics) =
+            CodeImports::new(&config, None).expand("<<< @/example.ts{10}", &mut SourceMap::new());
+
+        assert_eq!(md, "```ts\nexport default setup\n```");
+        assert_eq!(
+            imports[0].file,
+            canonical(&Path::new(&page).with_file_name("example.ts")).to_string_lossy()
+        );
+        assert!(diagnostics.is_empty());
+    }
+
+    #[test]
+    fn code_import_files_are_page_dependencies() {
+        let config = Config::default();
+        let (md, _) = MarkdownContentRaw::new("<<< ./example.ts{10}")
+            .parse(&config)
+            .unwrap();
+        let html = HtmlContent::for_page(&md, Some(&page()), &config);
+
+        assert_eq!(
+            html.content(),
+            [
+                "<div class=\"code-block language-ts\">\n",
+                "<div class=\"code-heading\"><span class=\"code-language\">ts</span></div>\n",
+                "<pre><code class=\"language-ts\"><span class=\"line line-1 odd\"><span class=\"source js\"><span class=\"meta export js\">",
+                "<span class=\"keyword control import-export js\">export</span> ",
+                "<span class=\"keyword control import-export js\">default</span> ",
+                "<span class=\"variable other readwrite js\">setup</span>",
+                "</span></span></span>\n</code></pre>\n</div>\n",
+            ]
+            .concat()
+        );
+        assert_eq!(html.dependencies().len(), 1);
+        assert!(html.dependencies()[0].ends_with("example.ts"));
+    }
+}
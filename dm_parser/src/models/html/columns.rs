@@ -0,0 +1,381 @@
+use pulldown_cmark::escape::escape_html;
+use regex::Regex;
+
+use crate::{
+    config::{
+        features::columns::{ColumnConfig, ColumnOutput},
+        Config,
+    },
+    models::diagnostic::Diagnostic,
+};
+
+use super::directive::{
+    Directive, DirectiveContext, DirectiveKind, DirectiveNode, Directives, Node,
+};
+
+/// How much the widths of a container may differ from 100% before
+/// they are considered invalid
+const WIDTH_TOLERANCE: f64 = 0.01;
+
+/// A column container which is being rendered
+#[derive(Debug)]
+struct OpenContainer {
+    count: usize,
+    widths: Vec<f64>,
+    /// the index of the column currently being filled
+    column: usize,
+    indent: String,
+}
+
+/// Converts the column container syntax into HTML blocks which wrap
+/// each column's markdown (separated by blank lines so that the content
+/// is still parsed as markdown):
+///
+/// ```md
+/// ::: 2 columns [40%, 60%]
+/// foo
+/// ::: next
+/// bar
+/// :::
+/// ```
+///
+/// Widths are optional but -- when provided -- there must be one per
+/// column and they must add up to 100%; otherwise a diagnostic is raised
+/// and the columns are given equal widths.
+#[derive(Debug, Clone)]
+pub struct Columns {
+    config: ColumnConfig,
+    /// the name and arguments of the opening line (e.g., `2 columns [40%, 60%]`)
+    opener: Regex,
+}
+
+impl Columns {
+    pub fn new(config: &Config) -> Self {
+        Columns {
+            config: config.features.columns.clone(),
+            opener: Regex::new(r"^(\d+)\s+columns?\s*(?:\[(.*)\])?\s*$").unwrap(),
+        }
+    }
+
+    fn open_container(&self, container: &OpenContainer) -> String {
+        let mut html = format!("\n{}<div class=\"", container.indent);
+        escape_html(&mut html, &self.config.class).unwrap();
+        html.push(' ');
+        escape_html(
+            &mut html,
+            &format!("{}-{}", self.config.class, container.count),
+        )
+        .unwrap();
+        html.push('"');
+
+        let mut style = String::new();
+        if self.config.output == ColumnOutput::Inline {
+            style.push_str(&format!(
+                "display:flex;flex-wrap:wrap;gap:{};",
+                self.config.gap
+            ));
+        }
+        if let Some(stack_below) = &self.config.stack_below {
+            style.push_str(&format!("--columns-stack-below:{};", stack_below));
+        }
+        if !style.is_empty() {
+            html.push_str(" style=\"");
+            escape_html(&mut html, &style).unwrap();
+            html.push('"');
+        }
+        html.push_str(">\n");
+        html.push_str(&self.open_column(container));
+
+        html
+    }
+
+    fn open_column(&self, container: &OpenContainer) -> String {
+        let width = container.widths.get(container.column).copied();
+        let mut html = format!("{}<div class=\"", container.indent);
+        escape_html(&mut html, &self.config.column_class).unwrap();
+        if let (Some(width), ColumnOutput::Classes) = (width, self.config.output) {
+            html.push(' ');
+            escape_html(
+                &mut html,
+                &format!(
+                    "{}-{}",
+                    self.config.column_class,
+                    format_width(width).replace('.', "_")
+                ),
+            )
+            .unwrap();
+        }
+        html.push('"');
+
+        if self.config.output == ColumnOutput::Inline {
+            let grow = format_width(width.unwrap_or(100.0 / container.count as f64));
+            // when a stacking width is given the "flex-basis" is either
+            // negative (columns sit side by side in proportion to their
+            // width) or huge (each column takes a full row)
+            let basis = match &self.config.stack_below {
+                Some(stack_below) => format!("calc(({} - 100%) * 999)", stack_below),
+                None => String::from("0%"),
+            };
+            html.push_str(" style=\"");
+            escape_html(
+                &mut html,
+                &format!("flex:{} 1 {};min-width:0;", grow, basis),
+            )
+            .unwrap();
+            html.push('"');
+        }
+        html.push_str(">\n\n");
+
+        html
+    }
+
+    fn close_container(&self, container: &OpenContainer) -> String {
+        format!(
+            "\n{indent}</div>\n{indent}</div>\n\n",
+            indent = container.indent
+        )
+    }
+}
+
+impl Directive for Columns {
+    fn name(&self) -> &str {
+        "columns"
+    }
+
+    fn kind(&self) -> DirectiveKind {
+        DirectiveKind::Container
+    }
+
+    fn matches(&self, name: &str, args: &str) -> bool {
+        self.opener.is_match(&format!("{} {}", name, args))
+    }
+
+    fn separators(&self) -> &[&str] {
+        &["next"]
+    }
+
+    fn render(
+        &self,
+        node: &DirectiveNode,
+        directives: &Directives,
+        ctx: &mut DirectiveContext,
+    ) -> String {
+        if ctx.nesting(self.name()) > 1 && !self.config.allow_nesting {
+            ctx.diagnostics.push(Diagnostic::error(
+                "columns can not be nested inside of other columns",
+                node.position,
+            ));
+            return directives.passthrough(node, ctx);
+        }
+
+        let definition = format!("{} {}", node.name, node.args);
+        let captures = self.opener.captures(&definition).unwrap();
+        let count: usize = captures[1].parse().unwrap_or(1).max(1);
+        let widths = match captures.get(2) {
+            Some(widths) => match parse_widths(widths.as_str(), count) {
+                Ok(widths) => widths,
+                Err(e) => {
+                    ctx.diagnostics.push(Diagnostic::error(&e, node.position));
+                    equal_widths(count)
+                }
+            },
+            None => equal_widths(count),
+        };
+        let mut container = OpenContainer {
+            count,
+            widths,
+            column: 0,
+            indent: node.indent.clone(),
+        };
+
+        let mut html = self.open_container(&container);
+        for child in &node.children {
+            match child {
+                Node::Separator { position, .. } => {
+                    container.column += 1;
+                    if container.column == container.count {
+                        ctx.diagnostics.push(Diagnostic::warning(
+                            &format!(
+                                "expected {} columns but more were provided",
+                                container.count
+                            ),
+                            *position,
+                        ));
+                    }
+                    html.push_str(&container.indent);
+                    html.push_str("</div>\n");
+                    html.push_str(&self.open_column(&container));
+                }
+                _ => html.push_str(&directives.render_node(child, ctx)),
+            }
+        }
+
+        if !node.closed() {
+            ctx.diagnostics.push(Diagnostic::error(
+                "columns were opened but never closed with `:::`",
+                node.position,
+            ));
+            if !html.ends_with('\n') {
+                html.push('\n');
+            }
+        }
+        html.push_str(&self.close_container(&container));
+
+        html
+    }
+}
+
+/// Parses a list of widths such as `40%, 60%` and validates that there
+/// is one for each column and that they add up to 100%
+fn parse_widths(widths: &str, count: usize) -> Result<Vec<f64>, String> {
+    let widths: Vec<f64> = widths
+        .split(',')
+        .map(|w| {
+            let w = w.trim();
+            w.strip_suffix('%')
+                .and_then(|n| n.trim().parse::<f64>().ok())
+                .filter(|n| *n > 0.0)
+                .ok_or_else(|| format!("the column width \"{}\" is not a percentage", w))
+        })
+        .collect::<Result<Vec<f64>, String>>()?;
+
+    if widths.len() != count {
+        return Err(format!(
+            "{} columns were defined but {} widths were provided",
+            count,
+            widths.len()
+        ));
+    }
+    let total: f64 = widths.iter().sum();
+    if (total - 100.0).abs() > WIDTH_TOLERANCE {
+        return Err(format!(
+            "column widths must add up to 100% but add up to {}%",
+            format_width(total)
+        ));
+    }
+
+    Ok(widths)
+}
+
+fn equal_widths(count: usize) -> Vec<f64> {
+    vec![100.0 / count as f64; count]
+}
+
+/// widths are written without a fractional part where possible
+fn format_width(width: f64) -> String {
+    let rounded = (width * 100.0).round() / 100.0;
+    match rounded.fract() == 0.0 {
+        true => format!("{}", rounded as i64),
+        false => format!("{}", rounded),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{html::HtmlContent, markdown::MarkdownContentRaw};
+
+    fn expand(md: &str, config: &Config) -> (String, Vec<Diagnostic>) {
+        let mut ctx = DirectiveContext::default();
+        let expanded = Directives::new(config, None).expand(md, &mut ctx);
+
+        (expanded, ctx.diagnostics)
+    }
+
+    fn html(md: &str, config: &Config) -> HtmlContent {
+        let (md, _) = MarkdownContentRaw::new(md).parse(config).unwrap();
+        HtmlContent::new(&md, config)
+    }
+
+    #[test]
+    fn columns_content_is_parsed_as_markdown() {
+        let html = html(
+            "::: 2 columns [40%, 60%]\n**foo**\n::: next\n- bar\n:::\n\nafter",
+            &Config::default(),
+        );
+
+        assert_eq!(
+            html.content(),
+            [
+                "<div class=\"columns columns-2\" style=\"--columns-stack-below:640px;\">\n",
+                "<div class=\"column column-40\">\n",
+                "<p><strong>foo</strong></p>\n",
+                "</div>\n",
+                "<div class=\"column column-60\">\n",
+                "<ul>\n<li>bar</li>\n</ul>\n",
+                "</div>\n</div>\n",
+                "<p>after</p>\n",
+            ]
+            .concat()
+        );
+        assert!(html.diagnostics.is_empty());
+    }
+
+    #[test]
+    fn columns_inline_styles() {
+        let mut config = Config::default();
+        config.features.columns.output = ColumnOutput::Inline;
+        config.features.columns.stack_below = None;
+        let html = html("::: 3 columns\na\n::: next\nb\n::: next\nc\n:::", &config);
+
+        assert!(html.content().starts_with(
+            "<div class=\"columns columns-3\" style=\"display:flex;flex-wrap:wrap;gap:1rem;\">\n<div class=\"column\" style=\"flex:33.33 1 0%;min-width:0;\">"
+        ));
+    }
+
+    #[test]
+    fn columns_widths_are_validated() {
+        assert_eq!(parse_widths("25%, 75%", 2), Ok(vec![25.0, 75.0]));
+        assert!(parse_widths("40%, 40%", 2).unwrap_err().contains("80%"));
+        assert!(parse_widths("40%, 60%", 3)
+            .unwrap_err()
+            .contains("3 columns were defined but 2 widths"));
+        assert!(parse_widths("40px, 60%", 2)
+            .unwrap_err()
+            .contains("not a percentage"));
+
+        let html = html(
+            "# Title\n::: 2 columns [10%, 10%]\na\n::: next\nb\n:::",
+            &Config::default(),
+        );
+        assert_eq!(html.diagnostics.len(), 1);
+        assert_eq!(html.diagnostics[0].position.line, 2);
+        assert!(html.content().contains("column column-50"));
+    }
+
+    #[test]
+    fn columns_can_be_nested() {
+        let md = "::: 2 columns\na\n::: next\n::: 2 columns\nb\n::: next\nc\n:::\n:::";
+        let html = html(md, &Config::default());
+        assert_eq!(
+            html.content()
+                .matches("class=\"columns columns-2\"")
+                .count(),
+            2
+        );
+        assert_eq!(html.content().matches("</div>").count(), 6);
+
+        let mut config = Config::default();
+        config.features.columns.allow_nesting = false;
+        let (_, diagnostics) = expand(md, &config);
+        assert!(diagnostics[0].message.contains("nested"));
+        assert_eq!(diagnostics[0].position.line, 4);
+    }
+
+    #[test]
+    fn columns_ignored_in_code_blocks() {
+        let md = "```md\n::: 2 columns\n:::\n```";
+        let (expanded, diagnostics) = expand(md, &Config::default());
+
+        assert_eq!(expanded, md);
+        assert!(diagnostics.is_empty());
+    }
+
+    #[test]
+    fn columns_unclosed_are_reported() {
+        let (expanded, diagnostics) = expand("::: 2 columns\na", &Config::default());
+
+        assert!(expanded.ends_with("</div>\n</div>\n\n"));
+        assert_eq!(diagnostics.len(), 1);
+    }
+}
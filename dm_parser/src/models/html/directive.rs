@@ -0,0 +1,677 @@
+use std::{collections::HashMap, path::PathBuf};
+
+use pulldown_cmark::escape::escape_html;
+use regex::Regex;
+
+use crate::{
+    config::{Config, OutputFormat},
+    models::{diagnostic::Diagnostic, position::SourcePosition},
+};
+
+use super::{
+    columns::Columns,
+    slots::{SlotContent, Slots},
+    tabs::{TabItem, TabRoute, Tabs},
+};
+
+/// The syntax a directive is written with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DirectiveKind {
+    /// `:::name args` followed by content and closed with `:::`
+    Container,
+    /// `::name args` on a line of its own
+    Leaf,
+    /// `::name args` followed by content and closed with `::end-name`
+    Section,
+}
+
+/// A node in the tree of directives found on a page
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Node {
+    /// a line of markdown (including its line ending)
+    Line(String, SourcePosition),
+    /// a `::: word` line which divides the content of the container it is
+    /// found in (e.g., `::: next` in columns)
+    Separator {
+        name: String,
+        line: String,
+        position: SourcePosition,
+    },
+    Directive(DirectiveNode),
+}
+
+/// A directive along with the content it wraps
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirectiveNode {
+    pub name: String,
+    /// everything which follows the name on the opening line
+    pub args: String,
+    pub kind: DirectiveKind,
+    /// the leading whitespace of the opening line
+    pub indent: String,
+    pub children: Vec<Node>,
+    /// the markdown between the opening and closing lines as written
+    pub inner: String,
+    /// the opening line as written
+    pub open: String,
+    /// the closing line as written; `None` when a container or section
+    /// was never explicitly closed
+    pub close: Option<String>,
+    pub position: SourcePosition,
+}
+
+impl DirectiveNode {
+    pub fn closed(&self) -> bool {
+        self.close.is_some()
+    }
+
+    pub fn attributes(&self) -> Attributes {
+        Attributes::parse(&self.args)
+    }
+}
+
+/// The attributes of a directive: an optional label (e.g., `"One"`)
+/// followed by `key=value` pairs which are separated by commas and/or
+/// whitespace. Values may be quoted and a key without a value is `true`.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Attributes {
+    pub label: Option<String>,
+    pub values: Vec<(String, String)>,
+}
+
+impl Attributes {
+    pub fn parse(input: &str) -> Self {
+        let mut chars = input.trim().chars().peekable();
+        let mut attributes = Attributes::default();
+        let mut first = true;
+
+        while let Some(c) = chars.peek().copied() {
+            if c.is_whitespace() || c == ',' {
+                chars.next();
+                continue;
+            }
+            if c == '"' || c == '\'' {
+                chars.next();
+                let value: String = chars.by_ref().take_while(|ch| *ch != c).collect();
+                if first {
+                    attributes.label = Some(value);
+                }
+                first = false;
+                continue;
+            }
+
+            let key: String = std::iter::from_fn(|| {
+                chars.next_if(|ch| !ch.is_whitespace() && *ch != '=' && *ch != ',')
+            })
+            .collect();
+            let value = match chars.next_if_eq(&'=') {
+                Some(_) => match chars.peek().copied() {
+                    Some(q) if q == '"' || q == '\'' => {
+                        chars.next();
+                        chars.by_ref().take_while(|ch| *ch != q).collect()
+                    }
+                    _ => std::iter::from_fn(|| chars.next_if(|ch| !ch.is_whitespace()))
+                        .collect::<String>()
+                        .trim_end_matches(',')
+                        .to_string(),
+                },
+                None if first => {
+                    // an unquoted label runs until the next comma
+                    let rest: String =
+                        std::iter::from_fn(|| chars.next_if(|ch| *ch != ',')).collect();
+                    attributes.label = Some(format!("{}{}", key, rest).trim().to_string());
+                    first = false;
+                    continue;
+                }
+                None => String::from("true"),
+            };
+            attributes.values.push((key, value));
+            first = false;
+        }
+
+        attributes
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.values
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+/// What the directives on a page produced beyond their HTML
+#[derive(Debug, Default)]
+pub struct DirectiveContext {
+    pub diagnostics: Vec<Diagnostic>,
+    /// the sections of the page which target a slot in the layout
+    pub slots: Vec<SlotContent>,
+    /// the tabs which are lazily loaded sub-routes of the page
+    pub tab_routes: Vec<TabRoute>,
+    /// the names of the directives currently being rendered
+    ancestors: Vec<String>,
+    /// the (canonical) files whose content is currently being expanded
+    /// in place of a directive -- such as an inline tab -- innermost last
+    pub(crate) files: Vec<PathBuf>,
+    ids: HashMap<String, usize>,
+}
+
+impl DirectiveContext {
+    /// How many of the directives currently being rendered -- including
+    /// the one asking -- have the given name
+    pub fn nesting(&self, name: &str) -> usize {
+        self.ancestors.iter().filter(|a| *a == name).count()
+    }
+
+    /// An id which is unique on the page (e.g., `tabs-1`, `tabs-2`)
+    pub fn next_id(&mut self, prefix: &str) -> String {
+        let count = self.ids.entry(prefix.to_string()).or_insert(0);
+        *count += 1;
+
+        format!("{}-{}", prefix, count)
+    }
+}
+
+/// A directive which can be registered with `Directives`
+pub trait Directive {
+    /// The name the directive is known by (e.g., `slot` for `:::slot`)
+    fn name(&self) -> &str;
+
+    fn kind(&self) -> DirectiveKind;
+
+    /// Whether the name and arguments on an opening line invoke this
+    /// directive; by default only the name is compared.
+    fn matches(&self, name: &str, _args: &str) -> bool {
+        name == self.name()
+    }
+
+    /// Words which -- on a `::: word` line of their own -- divide the
+    /// content of this directive rather than opening a new container
+    fn separators(&self) -> &[&str] {
+        &[]
+    }
+
+    /// Whether the directive can be placed inside of itself; when it
+    /// can't, opening it again closes the one which is already open.
+    fn nests(&self) -> bool {
+        true
+    }
+
+    /// Renders the directive -- and the content it wraps -- into the
+    /// markdown/HTML which replaces it on the page
+    fn render(
+        &self,
+        node: &DirectiveNode,
+        directives: &Directives,
+        ctx: &mut DirectiveContext,
+    ) -> String;
+}
+
+/// A directive which is still open while scanning the page
+struct Frame {
+    node: DirectiveNode,
+    /// the offset where the directive's content starts
+    inner_start: usize,
+}
+
+/// The registry of directives along with the pre-pass which expands them
+/// before the page is handed to the markdown parser. Directives which
+/// are not registered are passed through unchanged.
+pub struct Directives<'a> {
+    directives: Vec<Box<dyn Directive + 'a>>,
+    container: Regex,
+    leaf: Regex,
+}
+
+impl<'a> Directives<'a> {
+    /// A registry with no directives
+    pub fn empty() -> Self {
+        Directives {
+            directives: vec![],
+            container: Regex::new(r"^(\s*):::\s*(\S*)\s*(.*?)\s*$").unwrap(),
+            leaf: Regex::new(r"^(\s*)::([A-Za-z][\w-]*)\s*(.*?)\s*$").unwrap(),
+        }
+    }
+
+    /// A registry with the built-in directives which are enabled for the
+    /// page found in `file`
+    pub fn new(config: &'a Config, file: Option<&str>) -> Self {
+        let mut directives = Directives::empty();
+        if config.features.columns.enabled {
+            directives.register(Columns::new(config));
+        }
+        if config.features.enable_slots {
+            directives.register(Slots::new(config));
+        }
+        if config.features.tabs.enabled {
+            directives.register(Tabs::new(config, file));
+            directives.register(TabItem);
+        }
+
+        directives
+    }
+
+    /// Adds a directive; a directive registered later takes precedence
+    /// over an earlier one which matches the same syntax.
+    pub fn register(&mut self, directive: impl Directive + 'a) -> &mut Self {
+        self.directives.push(Box::new(directive));
+        self
+    }
+
+    /// The directive (if any) invoked by a name and its arguments
+    pub fn find(&self, name: &str, args: &str) -> Option<&dyn Directive> {
+        self.directives
+            .iter()
+            .rev()
+            .find(|d| d.matches(name, args))
+            .map(|d| d.as_ref())
+    }
+
+    /// Expands all the directives found in the markdown content
+    pub fn expand(&self, md: &str, ctx: &mut DirectiveContext) -> String {
+        self.render_nodes(&self.parse(md), ctx)
+    }
+
+    pub fn render_nodes(&self, nodes: &[Node], ctx: &mut DirectiveContext) -> String {
+        nodes
+            .iter()
+            .map(|node| self.render_node(node, ctx))
+            .collect()
+    }
+
+    pub fn render_node(&self, node: &Node, ctx: &mut DirectiveContext) -> String {
+        match node {
+            Node::Line(line, _) => line.clone(),
+            Node::Separator { line, .. } => line.clone(),
+            Node::Directive(node) => match self.find(&node.name, &node.args) {
+                Some(directive) => {
+                    ctx.ancestors.push(directive.name().to_string());
+                    let html = directive.render(node, self, ctx);
+                    ctx.ancestors.pop();
+                    html
+                }
+                None => self.passthrough(node, ctx),
+            },
+        }
+    }
+
+    /// Writes a directive back out as it was written (although any
+    /// directives inside of it are still expanded)
+    pub fn passthrough(&self, node: &DirectiveNode, ctx: &mut DirectiveContext) -> String {
+        let mut md = node.open.clone();
+        md.push_str(&self.render_nodes(&node.children, ctx));
+        if let Some(close) = &node.close {
+            md.push_str(close);
+        }
+
+        md
+    }
+
+    /// Tokenizes the markdown content into a tree of directives; fenced
+    /// code blocks are never searched for directives.
+    pub fn parse(&self, md: &str) -> Vec<Node> {
+        let mut root: Vec<Node> = vec![];
+        let mut stack: Vec<Frame> = vec![];
+        let mut fence: Option<(char, usize)> = None;
+        let mut offset = 0;
+
+        for line in md.split_inclusive('\n') {
+            let start = offset;
+            let position = SourcePosition::from_offset(md, offset);
+            offset += line.len();
+            let content = line.trim_end_matches(['\n', '\r']);
+
+            if let Some(f) = code_fence(content) {
+                fence = match fence {
+                    None => Some(f),
+                    Some((c, len)) if c == f.0 && f.1 >= len => None,
+                    open => open,
+                };
+            }
+            if fence.is_some() || !content.trim_start().starts_with("::") {
+                push(
+                    &mut stack,
+                    &mut root,
+                    Node::Line(line.to_string(), position),
+                );
+                continue;
+            }
+
+            if let Some(captures) = self.container.captures(content) {
+                let (name, args) = (&captures[2], &captures[3]);
+                if name.is_empty() {
+                    let top = stack.len().checked_sub(1);
+                    match top.filter(|idx| stack[*idx].node.kind == DirectiveKind::Container) {
+                        Some(idx) => close(&mut stack, &mut root, idx, Some(line), md, start),
+                        _ => push(
+                            &mut stack,
+                            &mut root,
+                            Node::Line(line.to_string(), position),
+                        ),
+                    }
+                    continue;
+                }
+                if args.is_empty() && self.is_separator(stack.last(), name) {
+                    let separator = Node::Separator {
+                        name: name.to_string(),
+                        line: line.to_string(),
+                        position,
+                    };
+                    push(&mut stack, &mut root, separator);
+                    continue;
+                }
+                if let Some(directive) = self.find(name, args).filter(|d| !d.nests()) {
+                    let open = stack.iter().rposition(|f| {
+                        self.find(&f.node.name, &f.node.args)
+                            .is_some_and(|d| d.name() == directive.name())
+                    });
+                    if let Some(idx) = open {
+                        close(&mut stack, &mut root, idx, None, md, start);
+                    }
+                }
+                stack.push(Frame {
+                    node: node(&captures, DirectiveKind::Container, line, position),
+                    inner_start: offset,
+                });
+                continue;
+            }
+
+            if let Some(captures) = self.leaf.captures(content) {
+                let (name, args) = (&captures[2], &captures[3]);
+                if let Some(section) = name.strip_prefix("end-") {
+                    let open = stack.iter().rposition(|f| {
+                        f.node.kind == DirectiveKind::Section && f.node.name == section
+                    });
+                    if let Some(idx) = open {
+                        close(&mut stack, &mut root, idx, Some(line), md, start);
+                        continue;
+                    }
+                }
+                match self.find(name, args).map(|d| d.kind()) {
+                    Some(DirectiveKind::Section) => stack.push(Frame {
+                        node: node(&captures, DirectiveKind::Section, line, position),
+                        inner_start: offset,
+                    }),
+                    _ => push(
+                        &mut stack,
+                        &mut root,
+                        Node::Directive(node(&captures, DirectiveKind::Leaf, line, position)),
+                    ),
+                }
+                continue;
+            }
+
+            push(
+                &mut stack,
+                &mut root,
+                Node::Line(line.to_string(), position),
+            );
+        }
+
+        if !stack.is_empty() {
+            close(&mut stack, &mut root, 0, None, md, md.len());
+        }
+
+        root
+    }
+
+    fn is_separator(&self, frame: Option<&Frame>, name: &str) -> bool {
+        match frame {
+            Some(frame) if frame.node.kind == DirectiveKind::Container => self
+                .find(&frame.node.name, &frame.node.args)
+                .is_some_and(|d| d.separators().contains(&name)),
+            _ => false,
+        }
+    }
+}
+
+fn node(
+    captures: &regex::Captures,
+    kind: DirectiveKind,
+    line: &str,
+    position: SourcePosition,
+) -> DirectiveNode {
+    DirectiveNode {
+        name: captures[2].to_string(),
+        args: captures[3].to_string(),
+        kind,
+        indent: captures[1].to_string(),
+        children: vec![],
+        inner: String::new(),
+        open: line.to_string(),
+        close: None,
+        position,
+    }
+}
+
+/// Adds a node to the directive currently open or -- when there is
+/// none -- to the root of the tree
+fn push(stack: &mut [Frame], root: &mut Vec<Node>, node: Node) {
+    match stack.last_mut() {
+        Some(frame) => frame.node.children.push(node),
+        None => root.push(node),
+    }
+}
+
+/// Closes the directive at `idx` in the stack (along with any opened
+/// inside of it which were never closed themselves); `end` is where
+/// the content of the directives ends.
+fn close(
+    stack: &mut Vec<Frame>,
+    root: &mut Vec<Node>,
+    idx: usize,
+    line: Option<&str>,
+    md: &str,
+    end: usize,
+) {
+    while stack.len() > idx {
+        let mut frame = stack.pop().unwrap();
+        frame.node.inner = md[frame.inner_start.min(end)..end].to_string();
+        if stack.len() == idx {
+            frame.node.close = line.map(|l| l.to_string());
+        }
+        push(stack, root, Node::Directive(frame.node));
+    }
+}
+
+/// The fence character and its length when the line opens or closes
+/// a fenced code block
+pub(crate) fn code_fence(line: &str) -> Option<(char, usize)> {
+    let trimmed = line.trim_start();
+    let c = trimmed.chars().next()?;
+    if c != '`' && c != '~' {
+        return None;
+    }
+    let len = trimmed.chars().take_while(|ch| *ch == c).count();
+
+    (len >= 3).then_some((c, len))
+}
+
+/// A container directive which wraps its content in a component when
+/// the output is an SFC (e.g., `:::visualize type=bar` becomes
+/// `<DataViz type="bar">`) and in a `<div>` classed with the directive's
+/// name otherwise. Attributes become props or `data-*` attributes.
+#[derive(Debug, Clone)]
+pub struct Component {
+    name: String,
+    component: String,
+    output: OutputFormat,
+}
+
+impl Component {
+    pub fn new(name: &str, component: &str, config: &Config) -> Self {
+        Component {
+            name: name.to_string(),
+            component: component.to_string(),
+            output: config.output.clone(),
+        }
+    }
+}
+
+impl Directive for Component {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn kind(&self) -> DirectiveKind {
+        DirectiveKind::Container
+    }
+
+    fn render(
+        &self,
+        node: &DirectiveNode,
+        directives: &Directives,
+        ctx: &mut DirectiveContext,
+    ) -> String {
+        let sfc = self.output == OutputFormat::SFC;
+        let tag = match sfc {
+            true => self.component.as_str(),
+            false => "div",
+        };
+        let mut html = format!("\n{}<{}", node.indent, tag);
+        if !sfc {
+            html.push_str(" class=\"");
+            escape_html(&mut html, &self.name).unwrap();
+            html.push('"');
+        }
+        let attributes = node.attributes();
+        let values = attributes
+            .label
+            .iter()
+            .map(|l| ("label", l.as_str()))
+            .chain(
+                attributes
+                    .values
+                    .iter()
+                    .map(|(k, v)| (k.as_str(), v.as_str())),
+            );
+        for (key, value) in values {
+            if !key
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+            {
+                ctx.diagnostics.push(Diagnostic::warning(
+                    &format!("\"{}\" is not a valid attribute name", key),
+                    node.position,
+                ));
+                continue;
+            }
+            html.push(' ');
+            if !sfc {
+                html.push_str("data-");
+            }
+            html.push_str(key);
+            html.push_str("=\"");
+            escape_html(&mut html, value).unwrap();
+            html.push('"');
+        }
+        html.push_str(">\n\n");
+        html.push_str(&directives.render_nodes(&node.children, ctx));
+        html.push_str(&format!("\n{}</{}>\n\n", node.indent, tag));
+
+        html
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{html::HtmlContent, markdown::MarkdownContentRaw};
+
+    fn names(nodes: &[Node]) -> Vec<String> {
+        nodes
+            .iter()
+            .map(|n| match n {
+                Node::Line(line, _) => line.trim().to_string(),
+                Node::Separator { name, .. } => format!("--{}", name),
+                Node::Directive(d) => format!("{}({})", d.name, names(&d.children).join(",")),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn directive_attributes_are_parsed() {
+        let attributes = Attributes::parse("theme=light,dark");
+        assert_eq!(attributes.label, None);
+        assert_eq!(attributes.get("theme"), Some("light,dark"));
+
+        let attributes = Attributes::parse("\"Three\", route=\"./sub-page3.md\" open");
+        assert_eq!(attributes.label.as_deref(), Some("Three"));
+        assert_eq!(attributes.get("route"), Some("./sub-page3.md"));
+        assert_eq!(attributes.get("open"), Some("true"));
+
+        let attributes = Attributes::parse("name='Two', inline=./two.md");
+        assert_eq!(
+            attributes.values,
+            vec![
+                ("name".to_string(), "Two".to_string()),
+                ("inline".to_string(), "./two.md".to_string())
+            ]
+        );
+        assert_eq!(Attributes::parse("My Tab").label.as_deref(), Some("My Tab"));
+    }
+
+    #[test]
+    fn directive_tree_is_nested() {
+        let config = Config::default();
+        let directives = Directives::new(&config, None);
+        let md = "a\n:::slot aside\n::: 2 columns\nb\n::: next\n:::unknown\n:::\n:::\n::tabs\n::tab One\n::end-tabs\n:::\n```\n:::slot footer\n```\n";
+        let nodes = directives.parse(md);
+
+        assert_eq!(
+            names(&nodes),
+            vec![
+                "a",
+                "slot(2(b,--next,unknown()),tabs(tab()))",
+                "```",
+                ":::slot footer",
+                "```"
+            ]
+        );
+        match &nodes[1] {
+            Node::Directive(slot) => {
+                assert_eq!(slot.args, "aside");
+                assert!(slot.closed());
+                assert!(slot.inner.starts_with("::: 2 columns\n"));
+                assert!(slot.inner.ends_with("::end-tabs\n"));
+            }
+            _ => panic!("expected a slot"),
+        }
+    }
+
+    #[test]
+    fn directive_unknown_passes_through() {
+        let config = Config::default();
+        let md = ":::warning\nCareful\n:::\n::toc depth=2\n:::\n";
+        let mut ctx = DirectiveContext::default();
+
+        assert_eq!(Directives::new(&config, None).expand(md, &mut ctx), md);
+        assert!(ctx.diagnostics.is_empty());
+    }
+
+    #[test]
+    fn directive_userland_components() {
+        let config = Config::default();
+        let md = ":::visualize \"Sales\" type=bar\n- red: 15\n:::\n";
+
+        let mut directives = Directives::new(&config, None);
+        directives.register(Component::new("visualize", "DataViz", &config));
+        let (md_content, _) = MarkdownContentRaw::new(md).parse(&config).unwrap();
+        let html = HtmlContent::with_directives(&md_content, None, &config, &directives);
+        assert_eq!(
+            html.content(),
+            "<DataViz label=\"Sales\" type=\"bar\">\n<ul>\n<li>red: 15</li>\n</ul>\n</DataViz>\n"
+        );
+
+        let config = Config {
+            output: OutputFormat::HTML,
+            ..Config::default()
+        };
+        let mut ctx = DirectiveContext::default();
+        let mut directives = Directives::empty();
+        directives.register(Component::new("visualize", "DataViz", &config));
+        assert!(directives
+            .expand(md, &mut ctx)
+            .starts_with("\n<div class=\"visualize\" data-label=\"Sales\" data-type=\"bar\">"));
+    }
+}
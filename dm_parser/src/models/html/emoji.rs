@@ -0,0 +1,255 @@
+use std::{iter::Peekable, ops::Range};
+
+use pulldown_cmark::{
+    escape::{escape_href, escape_html},
+    CowStr, Event, Tag,
+};
+use regex::{Captures, Regex};
+
+use crate::config::{
+    features::emoji::{CustomEmoji, EmojiConfig, EmojiOutput},
+    Config,
+};
+
+/// The markdown parser will break up text into multiple `Text` events
+/// (e.g., around an unmatched `_`) so adjacent text is merged back
+/// together -- along with its source range -- before it is handled.
+pub struct MergeText<'a, I: Iterator<Item = (Event<'a>, Range<usize>)>> {
+    iter: Peekable<I>,
+}
+
+impl<'a, I: Iterator<Item = (Event<'a>, Range<usize>)>> MergeText<'a, I> {
+    pub fn new(iter: I) -> Self {
+        MergeText {
+            iter: iter.peekable(),
+        }
+    }
+}
+
+impl<'a, I: Iterator<Item = (Event<'a>, Range<usize>)>> Iterator for MergeText<'a, I> {
+    type Item = (Event<'a>, Range<usize>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (event, range) = self.iter.next()?;
+        let (mut text, mut range) = match event {
+            Event::Text(text) => (text, range),
+            _ => return Some((event, range)),
+        };
+
+        while let Some((Event::Text(next), next_range)) = self.iter.peek() {
+            text = CowStr::from([text.as_ref(), next.as_ref()].concat());
+            range = range.start..next_range.end;
+            self.iter.next();
+        }
+
+        Some((Event::Text(text), range))
+    }
+}
+
+/// What a shortcode expands to
+enum Expansion {
+    /// text along with the accessible label for the emoji
+    Unicode(String, String),
+    /// the source of an image based emoji
+    Image(String),
+}
+
+/// Expands emoji shortcodes such as `:smile:` found in the text of
+/// a page. Code blocks (and inline code which is never a `Text` event)
+/// are left untouched.
+#[derive(Debug)]
+pub struct Emojis {
+    config: EmojiConfig,
+    shortcode: Regex,
+    in_code_block: bool,
+    /// how deeply nested we are inside an image's alt text where only
+    /// plain text can be used
+    in_image: usize,
+}
+
+impl Emojis {
+    pub fn new(config: &Config) -> Self {
+        Emojis {
+            config: config.features.emoji.clone(),
+            shortcode: Regex::new(r":([a-zA-Z0-9_+\-]+):").unwrap(),
+            in_code_block: false,
+            in_image: 0,
+        }
+    }
+
+    pub fn handle<'a>(&mut self, event: Event<'a>) -> Event<'a> {
+        if !self.config.enabled {
+            return event;
+        }
+
+        match event {
+            Event::Start(Tag::CodeBlock(_)) => self.in_code_block = true,
+            Event::End(Tag::CodeBlock(_)) => self.in_code_block = false,
+            Event::Start(Tag::Image(..)) => self.in_image += 1,
+            Event::End(Tag::Image(..)) => self.in_image -= 1,
+            Event::Text(text) if !self.in_code_block => return self.expand(text),
+            _ => (),
+        }
+
+        event
+    }
+
+    /// Looks up a shortcode (without the surrounding `:`); project
+    /// defined shortcodes take precedence over the bundled ones.
+    fn lookup(&self, name: &str) -> Option<Expansion> {
+        match self.config.custom.get(name) {
+            Some(CustomEmoji::Unicode(text)) => Some(Expansion::Unicode(
+                text.clone(),
+                name.replace(['_', '-'], " "),
+            )),
+            Some(CustomEmoji::Image { image }) => Some(Expansion::Image(image.clone())),
+            None => emojis::get_by_shortcode(name)
+                .map(|e| Expansion::Unicode(e.as_str().to_string(), e.name().to_string())),
+        }
+    }
+
+    fn expand<'a>(&self, text: CowStr<'a>) -> Event<'a> {
+        if !text.contains(':') {
+            return Event::Text(text);
+        }
+        let allow_html = self.in_image == 0;
+        let needs_html = allow_html
+            && self.shortcode.captures_iter(&text).any(|c| {
+                matches!(
+                    (self.lookup(&c[1]), self.config.output),
+                    (Some(Expansion::Image(_)), _) | (Some(_), EmojiOutput::Span)
+                )
+            });
+
+        if !needs_html {
+            let expanded =
+                self.shortcode
+                    .replace_all(&text, |c: &Captures| match self.lookup(&c[1]) {
+                        Some(Expansion::Unicode(emoji, _)) => emoji,
+                        _ => c[0].to_string(),
+                    });
+            return match expanded == text.as_ref() {
+                true => Event::Text(text),
+                false => Event::Text(CowStr::from(expanded.to_string())),
+            };
+        }
+
+        let mut html = String::new();
+        let mut last = 0;
+        for c in self.shortcode.captures_iter(&text) {
+            let all = c.get(0).unwrap();
+            escape_html(&mut html, &text[last..all.start()]).unwrap();
+            last = all.end();
+            match self.lookup(&c[1]) {
+                Some(Expansion::Unicode(emoji, label)) => match self.config.output {
+                    EmojiOutput::Span => {
+                        html.push_str("<span class=\"");
+                        escape_html(&mut html, &self.config.class).unwrap();
+                        html.push_str("\" role=\"img\" aria-label=\"");
+                        escape_html(&mut html, &label).unwrap();
+                        html.push_str("\">");
+                        escape_html(&mut html, &emoji).unwrap();
+                        html.push_str("</span>");
+                    }
+                    EmojiOutput::Unicode => escape_html(&mut html, &emoji).unwrap(),
+                },
+                Some(Expansion::Image(src)) => {
+                    html.push_str("<img class=\"");
+                    escape_html(&mut html, &self.config.class).unwrap();
+                    html.push_str("\" src=\"");
+                    escape_href(&mut html, &src).unwrap();
+                    html.push_str("\" alt=\"");
+                    escape_html(&mut html, all.as_str()).unwrap();
+                    html.push_str("\" />");
+                }
+                None => escape_html(&mut html, all.as_str()).unwrap(),
+            }
+        }
+        escape_html(&mut html, &text[last..]).unwrap();
+
+        Event::Html(CowStr::from(html))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::features::emoji::EmojiConfig,
+        models::{html::HtmlContent, markdown::MarkdownContentRaw},
+    };
+
+    fn html_with(md: &str, emoji: EmojiConfig) -> String {
+        let mut config = Config::default();
+        config.features.emoji = emoji;
+        let (md, _) = MarkdownContentRaw::new(md).parse(&config).unwrap();
+
+        HtmlContent::new(&md, &config).content()
+    }
+
+    #[test]
+    fn emoji_shortcodes_expand_to_unicode() {
+        assert_eq!(
+            html_with(
+                "Ship it :rocket: :heavy_check_mark: but not :not_an_emoji: at 10:30:00",
+                EmojiConfig::default()
+            ),
+            "<p>Ship it 🚀 ✔️ but not :not_an_emoji: at 10:30:00</p>\n"
+        );
+    }
+
+    #[test]
+    fn emoji_skips_code() {
+        assert_eq!(
+            html_with(
+                "`:smile:` :smile:\n\n```\n:smile:\n```",
+                EmojiConfig::default()
+            ),
+            "<p><code>:smile:</code> 😄</p>\n<pre><code><span class=\"line line-1 odd\"><span class=\"text plain\">:smile:</span></span>\n</code></pre>\n"
+        );
+    }
+
+    #[test]
+    fn emoji_span_output_and_custom_shortcodes() {
+        let mut emoji = EmojiConfig {
+            output: EmojiOutput::Span,
+            ..EmojiConfig::default()
+        };
+        emoji.custom.insert(
+            String::from("parrot"),
+            CustomEmoji::Image {
+                image: String::from("/emoji/parrot.gif"),
+            },
+        );
+        emoji.custom.insert(
+            String::from("smile"),
+            CustomEmoji::Unicode(String::from(":-)")),
+        );
+
+        assert_eq!(
+            html_with("a :tada: b :parrot: <c> :smile:", emoji),
+            "<p>a <span class=\"emoji\" role=\"img\" aria-label=\"party popper\">🎉</span> b <img class=\"emoji\" src=\"/emoji/parrot.gif\" alt=\":parrot:\" /> <c> <span class=\"emoji\" role=\"img\" aria-label=\"smile\">:-)</span></p>\n"
+        );
+    }
+
+    #[test]
+    fn emoji_plain_text_inside_image_alt() {
+        let emoji = EmojiConfig {
+            output: EmojiOutput::Span,
+            ..EmojiConfig::default()
+        };
+        assert_eq!(
+            html_with("![a :cat: photo](https://acme.com/cat.png)", emoji),
+            "<p><img src=\"https://acme.com/cat.png\" alt=\"a 🐱 photo\" loading=\"lazy\" decoding=\"async\" /></p>\n"
+        );
+    }
+
+    #[test]
+    fn emoji_can_be_turned_off() {
+        let emoji = EmojiConfig {
+            enabled: false,
+            ..EmojiConfig::default()
+        };
+        assert_eq!(html_with(":smile:", emoji), "<p>:smile:</p>\n");
+    }
+}
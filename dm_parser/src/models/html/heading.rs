@@ -0,0 +1,123 @@
+use std::collections::HashMap;
+
+use pulldown_cmark::{Event, Options as ParserOptions, Parser, Tag};
+
+/// Converts the text of a heading into the `id` it will be known by
+/// in the same manner GitHub does: lowercased, punctuation removed
+/// and whitespace converted to `-`.
+pub fn slugify_heading(text: &str) -> String {
+    text.trim()
+        .to_lowercase()
+        .chars()
+        .filter_map(|c| {
+            if c.is_alphanumeric() || c == '-' || c == '_' {
+                Some(c)
+            } else if c.is_whitespace() {
+                Some('-')
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// Hands out heading ids for a page, ensuring that duplicate
+/// headings receive a `-1`, `-2`, etc. suffix.
+#[derive(Debug, Default)]
+pub struct HeadingIds {
+    seen: HashMap<String, usize>,
+}
+
+impl HeadingIds {
+    pub fn new() -> Self {
+        HeadingIds::default()
+    }
+
+    /// provides a unique id for the heading text
+    pub fn id_for(&mut self, text: &str) -> String {
+        let slug = slugify_heading(text);
+        self.reserve(&slug)
+    }
+
+    /// registers an id which was explicitly set on the page and
+    /// returns the id which should be used
+    pub fn reserve(&mut self, id: &str) -> String {
+        match self.seen.get_mut(id) {
+            Some(count) => {
+                *count += 1;
+                let id = format!("{}-{}", id, count);
+                self.seen.insert(id.clone(), 0);
+                id
+            }
+            None => {
+                self.seen.insert(id.to_string(), 0);
+                id.to_string()
+            }
+        }
+    }
+
+    /// The ids of the headings in `md` -- in document order -- which are
+    /// unique among all of the ids handed out so far; this lets sections
+    /// of a page which are rendered separately share one set of ids.
+    pub fn ids_for(&mut self, md: &str, options: ParserOptions) -> Vec<String> {
+        let mut found = vec![];
+        let mut text: Option<(Option<String>, String)> = None;
+
+        for event in Parser::new_ext(md, options) {
+            match event {
+                Event::Start(Tag::Heading(_, id, _)) => {
+                    text = Some((id.map(|i| i.to_string()), String::new()));
+                }
+                Event::Text(t) | Event::Code(t) => {
+                    if let Some((_, content)) = &mut text {
+                        content.push_str(&t);
+                    }
+                }
+                Event::End(Tag::Heading(..)) => {
+                    if let Some((explicit, content)) = text.take() {
+                        found.push(match explicit {
+                            Some(id) => self.reserve(&id),
+                            None => self.id_for(&content),
+                        });
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        found
+    }
+}
+
+/// The ids of all headings on a page -- in document order -- where
+/// an explicit `{#id}` attribute takes precedence over the id derived
+/// from the heading's text.
+pub fn heading_ids(md: &str, options: ParserOptions) -> Vec<String> {
+    HeadingIds::new().ids_for(md, options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn heading_slugs_follow_github_style() {
+        assert_eq!(slugify_heading("Getting Started"), "getting-started");
+        assert_eq!(slugify_heading("What's `new` in 2.0?"), "whats-new-in-20");
+        assert_eq!(
+            slugify_heading("snake_case & kebab-case"),
+            "snake_case--kebab-case"
+        );
+    }
+
+    #[test]
+    fn heading_ids_are_unique_and_respect_explicit_ids() {
+        let md = "# Intro\n## Setup\n## Setup\n## Custom {#my-id}\n### `code` Setup";
+        let ids = heading_ids(md, ParserOptions::ENABLE_HEADING_ATTRIBUTES);
+
+        assert_eq!(
+            ids,
+            vec!["intro", "setup", "setup-1", "my-id", "code-setup"]
+        );
+    }
+}
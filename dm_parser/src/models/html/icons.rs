@@ -0,0 +1,416 @@
+use std::{collections::HashMap, fs, path::Path};
+
+use pulldown_cmark::escape::escape_html;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    config::{features::icons::IconConfig, Config, OutputFormat},
+    models::{diagnostic::Diagnostic, position::SourcePosition},
+};
+
+use super::md_attribute::code_ranges;
+
+/// The size (in both directions) of an icon's view box when neither the
+/// icon nor its collection say otherwise
+const DEFAULT_ICON_SIZE: f64 = 16.0;
+/// How many aliases are followed to find the icon which one refers to
+const MAX_ALIAS_DEPTH: usize = 8;
+
+/// An Iconify icon used on a page
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct IconRef {
+    /// the Iconify collection (e.g. `mdi`)
+    pub collection: String,
+    /// the icon's name within the collection
+    pub name: String,
+    /// the name of the `unplugin-icons` component for the icon
+    pub component: String,
+    /// the module which `unplugin-icons` resolves the component from
+    pub module: String,
+    pub position: SourcePosition,
+    /// whether the icon was found in its local collection; this is only
+    /// checked when the SVG is inlined
+    pub found: Option<bool>,
+}
+
+impl IconRef {
+    fn new(collection: &str, name: &str, position: SourcePosition) -> Self {
+        IconRef {
+            collection: collection.to_string(),
+            name: name.to_string(),
+            component: format!("I{}{}", pascal_case(collection), pascal_case(name)),
+            module: format!("~icons/{}/{}", collection, name),
+            position,
+            found: None,
+        }
+    }
+
+    /// The statement which imports the icon's component into an SFC
+    pub fn import(&self) -> String {
+        format!("import {} from '{}'", self.component, self.module)
+    }
+}
+
+/// Finds Iconify tags -- `<collection:icon />` -- on a page:
+///
+/// ```md
+/// <mdi:account-edit class="font-xl text-blue-500" />
+/// ```
+///
+/// For SFC output the tag becomes the `unplugin-icons` component (whose
+/// import the SFC's script block picks up from the icons recorded); for
+/// HTML output the icon's SVG is inlined from the local Iconify JSON
+/// collection. Tags inside of code are left alone.
+#[derive(Debug)]
+pub struct Icons {
+    config: IconConfig,
+    output: OutputFormat,
+    tag: Regex,
+    attribute: Regex,
+    /// the collections read so far (`None` when it could not be read)
+    collections: HashMap<String, Option<Value>>,
+}
+
+impl Icons {
+    pub fn new(config: &Config) -> Self {
+        Icons {
+            config: config.features.icons.clone(),
+            output: config.output.clone(),
+            tag: Regex::new(
+                r#"<([a-z0-9]+(?:-[a-z0-9]+)*):([a-z0-9]+(?:-[a-z0-9]+)*)((?:\s+[^\s=/>]+(?:\s*=\s*(?:"[^"]*"|'[^']*'|[^\s"'>]+))?)*)\s*(/?)>"#,
+            )
+            .unwrap(),
+            attribute: Regex::new(
+                r#"\s+([^\s=/>]+)(?:\s*=\s*(?:"([^"]*)"|'([^']*)'|([^\s"'>]+)))?"#,
+            )
+            .unwrap(),
+            collections: HashMap::new(),
+        }
+    }
+
+    /// Replaces every icon tag on the page with its component or SVG
+    pub fn expand(&mut self, md: &str) -> (String, Vec<IconRef>, Vec<Diagnostic>) {
+        let mut icons = vec![];
+        let mut diagnostics = vec![];
+        if !self.config.enabled {
+            return (md.to_string(), icons, diagnostics);
+        }
+
+        let code = code_ranges(md);
+        let mut output = String::with_capacity(md.len());
+        let mut last = 0;
+        let mut search = 0;
+
+        while let Some(captures) = self.tag.captures(&md[search..]) {
+            let all = captures.get(0).unwrap();
+            let (start, mut end) = (search + all.start(), search + all.end());
+            search = end;
+            if code.iter().any(|r| r.contains(&start)) {
+                continue;
+            }
+            let (collection, name) = (&captures[1], &captures[2]);
+            // an icon may be written with an (empty) closing tag
+            if captures[4].is_empty() {
+                let close = format!("</{}:{}>", collection, name);
+                let rest = &md[end..];
+                if rest.trim_start().starts_with(&close) {
+                    end += rest.len() - rest.trim_start().len() + close.len();
+                    search = end;
+                }
+            }
+
+            let mut icon = IconRef::new(collection, name, SourcePosition::from_offset(md, start));
+            let attributes = self.attributes(&captures[3]);
+            let html = match self.output {
+                OutputFormat::SFC => Some(self.component(&icon, &attributes)),
+                OutputFormat::HTML => {
+                    let svg = self.svg(&icon, &attributes, &mut diagnostics);
+                    icon.found = Some(svg.is_some());
+                    svg
+                }
+            };
+            if let Some(html) = html {
+                output.push_str(&md[last..start]);
+                output.push_str(&html);
+                last = end;
+            }
+            icons.push(icon);
+        }
+        output.push_str(&md[last..]);
+
+        (output, icons, diagnostics)
+    }
+
+    /// The tag's attributes with the configured class added to any class
+    /// the tag was given
+    fn attributes(&self, attributes: &str) -> Vec<(String, Option<String>)> {
+        let mut list: Vec<(String, Option<String>)> = self
+            .attribute
+            .captures_iter(attributes)
+            .map(|a| {
+                let value = a.get(2).or_else(|| a.get(3)).or_else(|| a.get(4));
+                (a[1].to_string(), value.map(|v| v.as_str().to_string()))
+            })
+            .collect();
+
+        if !self.config.class.is_empty() {
+            match list.iter_mut().find(|(name, _)| name == "class") {
+                Some((_, value)) => {
+                    let class = value.as_deref().unwrap_or("").trim();
+                    *value = Some(
+                        format!("{} {}", self.config.class, class)
+                            .trim()
+                            .to_string(),
+                    );
+                }
+                None => list.insert(0, (String::from("class"), Some(self.config.class.clone()))),
+            }
+        }
+
+        list
+    }
+
+    fn component(&self, icon: &IconRef, attributes: &[(String, Option<String>)]) -> String {
+        let mut html = format!("<{}", icon.component);
+        push_attributes(&mut html, attributes);
+        html.push_str(" />");
+
+        html
+    }
+
+    /// The icon's SVG built from its local collection
+    fn svg(
+        &mut self,
+        icon: &IconRef,
+        attributes: &[(String, Option<String>)],
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> Option<String> {
+        let dir = self.config.collections.clone();
+        let collection = self
+            .collections
+            .entry(icon.collection.clone())
+            .or_insert_with(|| {
+                let file = Path::new(&dir).join(format!("{}.json", icon.collection));
+                fs::read_to_string(file)
+                    .ok()
+                    .and_then(|json| serde_json::from_str(&json).ok())
+            });
+        let collection = match collection {
+            Some(collection) => collection,
+            None => {
+                diagnostics.push(Diagnostic::warning(
+                    &format!(
+                        "the icon collection \"{}\" could not be read from {}",
+                        icon.collection, dir
+                    ),
+                    icon.position,
+                ));
+                return None;
+            }
+        };
+        let data = match icon_data(collection, &icon.name) {
+            Some(data) => data,
+            None => {
+                diagnostics.push(Diagnostic::warning(
+                    &format!(
+                        "the icon \"{}\" is not in the \"{}\" collection",
+                        icon.name, icon.collection
+                    ),
+                    icon.position,
+                ));
+                return None;
+            }
+        };
+
+        let size = format!("{}em", self.config.scale);
+        let mut svg = String::from("<svg xmlns=\"http://www.w3.org/2000/svg\"");
+        for dimension in ["width", "height"] {
+            if !attributes.iter().any(|(name, _)| name == dimension) {
+                svg.push_str(&format!(" {}=\"{}\"", dimension, size));
+            }
+        }
+        svg.push_str(&format!(
+            " viewBox=\"{} {} {} {}\"",
+            data.left, data.top, data.width, data.height
+        ));
+        push_attributes(&mut svg, attributes);
+        svg.push('>');
+        svg.push_str(&data.body);
+        svg.push_str("</svg>");
+
+        Some(svg)
+    }
+}
+
+/// The parts of an icon needed to build its SVG
+struct IconData {
+    body: String,
+    left: f64,
+    top: f64,
+    width: f64,
+    height: f64,
+}
+
+/// Looks up an icon in an Iconify JSON collection, following aliases to
+/// the icon they refer to. The view box of an alias (or the icon) takes
+/// precedence over the collection's default.
+fn icon_data(collection: &Value, name: &str) -> Option<IconData> {
+    let mut name = name;
+    let mut chain = vec![];
+    for _ in 0..MAX_ALIAS_DEPTH {
+        if let Some(icon) = collection["icons"].get(name) {
+            chain.push(icon);
+            let number = |key: &str, default: f64| {
+                chain
+                    .iter()
+                    .find_map(|i| i[key].as_f64())
+                    .or_else(|| collection[key].as_f64())
+                    .unwrap_or(default)
+            };
+            return Some(IconData {
+                body: icon["body"].as_str()?.to_string(),
+                left: number("left", 0.0),
+                top: number("top", 0.0),
+                width: number("width", DEFAULT_ICON_SIZE),
+                height: number("height", DEFAULT_ICON_SIZE),
+            });
+        }
+        let alias = collection["aliases"].get(name)?;
+        chain.push(alias);
+        name = alias["parent"].as_str()?;
+    }
+
+    None
+}
+
+fn push_attributes(html: &mut String, attributes: &[(String, Option<String>)]) {
+    for (name, value) in attributes {
+        html.push(' ');
+        html.push_str(name);
+        if let Some(value) = value {
+            html.push_str("=\"");
+            escape_html(&mut *html, value).unwrap();
+            html.push('"');
+        }
+    }
+}
+
+/// `checkbox-marked-circle` → `CheckboxMarkedCircle`
+fn pascal_case(name: &str) -> String {
+    name.split('-')
+        .map(|part| {
+            let mut chars = part.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect::<String>(),
+                None => String::new(),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{html::HtmlContent, markdown::MarkdownContentRaw, sfc::Sfc};
+
+    fn html(md: &str, config: &Config) -> HtmlContent {
+        let (md, _) = MarkdownContentRaw::new(md).parse(config).unwrap();
+        HtmlContent::new(&md, config)
+    }
+
+    fn collections() -> String {
+        let dir = std::env::temp_dir().join("dm-icon-collections");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("mdi.json"),
+            r#"{
+                "prefix": "mdi",
+                "width": 24,
+                "height": 24,
+                "icons": { "home": { "body": "<path d=\"M10 20v-6h4v6\"/>" } },
+                "aliases": { "house": { "parent": "home", "width": 20 } }
+            }"#,
+        )
+        .unwrap();
+
+        dir.to_string_lossy().to_string()
+    }
+
+    #[test]
+    fn icons_become_components_for_sfc() {
+        let html = html(
+            "Done <mdi:checkbox-marked-circle class=\"text-blue-500\" />\n\n<mdi:home></mdi:home>",
+            &Config::default(),
+        );
+
+        assert_eq!(
+            html.content(),
+            "<p>Done <IMdiCheckboxMarkedCircle class=\"icon text-blue-500\" /></p>\n<IMdiHome class=\"icon\" />"
+        );
+        assert_eq!(html.icons.len(), 2);
+        assert_eq!(
+            html.icons[0].import(),
+            "import IMdiCheckboxMarkedCircle from '~icons/mdi/checkbox-marked-circle'"
+        );
+        assert_eq!(html.icons[1].found, None);
+
+        let mut sfc = Sfc::new(&Config::default());
+        sfc.add_icon_imports(&html.icons);
+        sfc.add_icon_imports(&html.icons);
+        assert_eq!(
+            sfc.setup_script,
+            "import IMdiCheckboxMarkedCircle from '~icons/mdi/checkbox-marked-circle'\nimport IMdiHome from '~icons/mdi/home'\n"
+        );
+    }
+
+    #[test]
+    fn icons_in_code_are_left_alone() {
+        let html = html(
+            "`<mdi:home />` and ``<mdi:home/>``\n\n```html\n<mdi:home />\n```\n<https://example.com>",
+            &Config::default(),
+        );
+
+        assert!(html.icons.is_empty());
+        assert!(html.content().contains("<code>&lt;mdi:home /&gt;</code>"));
+        assert!(html.content().contains("<a href=\"https://example.com\">"));
+    }
+
+    #[test]
+    fn icons_inline_svg_for_html() {
+        let mut config = Config {
+            output: OutputFormat::HTML,
+            ..Config::default()
+        };
+        config.features.icons.collections = collections();
+        let html = html(
+            "<mdi:home style='color: red' /> <mdi:house width=\"2em\" /> <mdi:nope />",
+            &config,
+        );
+
+        assert_eq!(
+            html.content(),
+            [
+                "<p><svg xmlns=\"http://www.w3.org/2000/svg\" width=\"1.2em\" height=\"1.2em\" viewBox=\"0 0 24 24\" class=\"icon\" style=\"color: red\"><path d=\"M10 20v-6h4v6\"/></svg> ",
+                "<svg xmlns=\"http://www.w3.org/2000/svg\" height=\"1.2em\" viewBox=\"0 0 20 24\" class=\"icon\" width=\"2em\"><path d=\"M10 20v-6h4v6\"/></svg> ",
+                "&lt;mdi:nope /&gt;</p>\n",
+            ]
+            .concat()
+        );
+        assert_eq!(html.icons[2].found, Some(false));
+        assert_eq!(html.diagnostics.len(), 1);
+        assert!(html.diagnostics[0].message.contains("\"nope\""));
+    }
+
+    #[test]
+    fn icons_can_be_turned_off() {
+        let mut config = Config::default();
+        config.features.icons.enabled = false;
+        let (output, icons, _) = Icons::new(&config).expand("<mdi:home />");
+
+        assert_eq!(output, "<mdi:home />");
+        assert!(icons.is_empty());
+    }
+}
@@ -0,0 +1,550 @@
+use std::{
+    fs,
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use pulldown_cmark::{
+    escape::{escape_href, escape_html},
+    CowStr, Event, Tag,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::{
+        features::image::{ImageConfig, PlaceholderKind},
+        Config,
+    },
+    models::{diagnostic::Diagnostic, position::SourcePosition},
+};
+
+use super::{
+    link::{split_suffix, LinkKind},
+    responsive_image::{create_responsive_image, srcset, variant_formats, ImageVariant},
+};
+
+/// The image formats whose dimensions can be read from the file
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum ImageFormat {
+    Png,
+    Jpeg,
+    Webp,
+    Gif,
+    Svg,
+}
+
+/// An image found on a page
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ImageRef {
+    /// the image's source as written on the page
+    pub src: String,
+    pub alt: String,
+    pub title: String,
+    pub position: SourcePosition,
+    /// whether the image is served by this site rather than from
+    /// an external URL
+    pub local: bool,
+    /// the file on disk which a local image resolved to
+    pub file: Option<String>,
+    /// whether the file for a local image was found
+    pub found: bool,
+    pub format: Option<ImageFormat>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    /// the resized variants created when `responsive` is configured
+    pub variants: Vec<ImageVariant>,
+    /// a data URI or blurhash which can be shown while the image loads
+    pub placeholder: Option<String>,
+}
+
+/// Reads the format and intrinsic `(width, height)` of an image from
+/// the bytes of its file header.
+pub fn probe_dimensions(bytes: &[u8]) -> Option<(ImageFormat, u32, u32)> {
+    if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        let width = be_u32(bytes, 16)?;
+        let height = be_u32(bytes, 20)?;
+        Some((ImageFormat::Png, width, height))
+    } else if bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a") {
+        let width = le_u16(bytes, 6)? as u32;
+        let height = le_u16(bytes, 8)? as u32;
+        Some((ImageFormat::Gif, width, height))
+    } else if bytes.starts_with(&[0xFF, 0xD8]) {
+        probe_jpeg(bytes)
+    } else if bytes.starts_with(b"RIFF") && bytes.get(8..12) == Some(b"WEBP") {
+        probe_webp(bytes)
+    } else {
+        probe_svg(bytes)
+    }
+}
+
+/// walks the JPEG segments until a _start of frame_ marker is found
+fn probe_jpeg(bytes: &[u8]) -> Option<(ImageFormat, u32, u32)> {
+    let mut idx = 2;
+    while idx + 4 <= bytes.len() {
+        if bytes[idx] != 0xFF {
+            return None;
+        }
+        let marker = bytes[idx + 1];
+        // fill bytes
+        if marker == 0xFF {
+            idx += 1;
+            continue;
+        }
+        let is_sof = (0xC0..=0xCF).contains(&marker) && ![0xC4, 0xC8, 0xCC].contains(&marker);
+        if is_sof {
+            let height = be_u16(bytes, idx + 5)? as u32;
+            let width = be_u16(bytes, idx + 7)? as u32;
+            return Some((ImageFormat::Jpeg, width, height));
+        }
+        let length = be_u16(bytes, idx + 2)? as usize;
+        idx += 2 + length;
+    }
+
+    None
+}
+
+fn probe_webp(bytes: &[u8]) -> Option<(ImageFormat, u32, u32)> {
+    let (width, height) = match bytes.get(12..16)? {
+        b"VP8 " => (
+            (le_u16(bytes, 26)? & 0x3FFF) as u32,
+            (le_u16(bytes, 28)? & 0x3FFF) as u32,
+        ),
+        b"VP8L" => {
+            let b = bytes.get(21..25)?;
+            let (b0, b1, b2, b3) = (b[0] as u32, b[1] as u32, b[2] as u32, b[3] as u32);
+            (
+                1 + (((b1 & 0x3F) << 8) | b0),
+                1 + (((b3 & 0x0F) << 10) | (b2 << 2) | ((b1 & 0xC0) >> 6)),
+            )
+        }
+        b"VP8X" => (1 + le_u24(bytes, 24)?, 1 + le_u24(bytes, 27)?),
+        _ => return None,
+    };
+
+    Some((ImageFormat::Webp, width, height))
+}
+
+/// Uses the `width`/`height` attributes of the `<svg>` element and
+/// falls back to its `viewBox`
+fn probe_svg(bytes: &[u8]) -> Option<(ImageFormat, u32, u32)> {
+    let text = std::str::from_utf8(bytes).ok()?;
+    let start = text.find("<svg")?;
+    let end = start + text[start..].find('>')?;
+    let tag = &text[start..end];
+
+    let size = |name: &str| {
+        svg_attr(tag, name).and_then(|v| {
+            v.trim_end_matches("px")
+                .parse::<f64>()
+                .ok()
+                .map(|n| n.round() as u32)
+        })
+    };
+    let (width, height) = match (size("width"), size("height")) {
+        (Some(width), Some(height)) => (width, height),
+        _ => {
+            let view_box: Vec<f64> = svg_attr(tag, "viewBox")?
+                .split(|c: char| c.is_whitespace() || c == ',')
+                .filter_map(|n| n.parse().ok())
+                .collect();
+            match view_box[..] {
+                [_, _, width, height] => (width.round() as u32, height.round() as u32),
+                _ => return None,
+            }
+        }
+    };
+
+    Some((ImageFormat::Svg, width, height))
+}
+
+/// The value of an attribute on an element's opening tag
+fn svg_attr<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    let mut rest = tag;
+    while let Some(idx) = rest.find(name) {
+        let preceded_by_space = rest[..idx]
+            .chars()
+            .last()
+            .map(|c| c.is_whitespace())
+            .unwrap_or(false);
+        let after = rest[idx + name.len()..].trim_start();
+        if preceded_by_space {
+            if let Some(value) = after.strip_prefix('=') {
+                let value = value.trim_start();
+                let quote = value.chars().next()?;
+                if quote == '"' || quote == '\'' {
+                    let value = &value[1..];
+                    return value.find(quote).map(|end| &value[..end]);
+                }
+            }
+        }
+        rest = &rest[idx + name.len()..];
+    }
+
+    None
+}
+
+fn be_u16(bytes: &[u8], idx: usize) -> Option<u16> {
+    bytes
+        .get(idx..idx + 2)
+        .map(|b| u16::from_be_bytes([b[0], b[1]]))
+}
+
+fn le_u16(bytes: &[u8], idx: usize) -> Option<u16> {
+    bytes
+        .get(idx..idx + 2)
+        .map(|b| u16::from_le_bytes([b[0], b[1]]))
+}
+
+fn le_u24(bytes: &[u8], idx: usize) -> Option<u32> {
+    bytes
+        .get(idx..idx + 3)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], 0]))
+}
+
+fn be_u32(bytes: &[u8], idx: usize) -> Option<u32> {
+    bytes
+        .get(idx..idx + 4)
+        .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+/// Replaces the image events produced by the markdown parser with an
+/// `<img>` tag which has its dimensions, `loading` and `decoding`
+/// attributes set; gathering an `ImageRef` for each image along the way.
+#[derive(Debug)]
+pub struct Images {
+    config: ImageConfig,
+    /// the directory which relative images are resolved against
+    page_dir: PathBuf,
+    images: Vec<ImageRef>,
+    diagnostics: Vec<Diagnostic>,
+    /// how deeply nested we are inside of an image's alt text
+    depth: usize,
+}
+
+impl Images {
+    /// Creates the handler for the page found in `file`; without a file
+    /// relative images are resolved against the current directory
+    pub fn new(config: &Config, file: Option<&str>) -> Self {
+        let page_dir = file
+            .and_then(|f| Path::new(f).parent())
+            .map(|p| p.to_path_buf())
+            .unwrap_or_default();
+
+        Images {
+            config: config.features.images.clone(),
+            page_dir,
+            images: vec![],
+            diagnostics: vec![],
+            depth: 0,
+        }
+    }
+
+    /// Handles an event (and the range of the markdown content it was
+    /// found in); the events which make up an image are swallowed and
+    /// the `<img>` tag is emitted in their place at the end.
+    pub fn handle<'a>(
+        &mut self,
+        event: Event<'a>,
+        range: Range<usize>,
+        md: &str,
+    ) -> Option<Event<'a>> {
+        match event {
+            Event::Start(Tag::Image(_, src, title)) => {
+                self.depth += 1;
+                if self.depth == 1 {
+                    let image =
+                        self.resolve(&src, &title, SourcePosition::from_offset(md, range.start));
+                    self.images.push(image);
+                }
+                None
+            }
+            Event::End(Tag::Image(..)) => {
+                self.depth -= 1;
+                match self.depth {
+                    0 => self
+                        .images
+                        .last()
+                        .map(|image| Event::Html(CowStr::from(self.img_tag(image)))),
+                    _ => None,
+                }
+            }
+            Event::Text(text) | Event::Code(text) if self.depth > 0 => {
+                if let Some(image) = self.images.last_mut() {
+                    image.alt.push_str(&text);
+                }
+                None
+            }
+            _ if self.depth > 0 => None,
+            _ => Some(event),
+        }
+    }
+
+    /// The images and diagnostics which were gathered
+    pub fn finish(self) -> (Vec<ImageRef>, Vec<Diagnostic>) {
+        (self.images, self.diagnostics)
+    }
+
+    fn resolve(&mut self, src: &str, title: &str, position: SourcePosition) -> ImageRef {
+        let mut image = ImageRef {
+            src: src.to_string(),
+            alt: String::new(),
+            title: title.to_string(),
+            position,
+            local: false,
+            file: None,
+            found: false,
+            format: None,
+            width: None,
+            height: None,
+            variants: vec![],
+            placeholder: None,
+        };
+        let (path, _) = split_suffix(src);
+        let file = match LinkKind::from(src) {
+            LinkKind::Internal => {
+                Path::new(&self.config.public_dir).join(path.trim_start_matches('/'))
+            }
+            LinkKind::Relative => self.page_dir.join(path),
+            _ => return image,
+        };
+
+        image.local = true;
+        image.file = Some(file.to_string_lossy().replace('\\', "/"));
+        image.found = file.is_file();
+
+        if !image.found {
+            if self.config.report_missing {
+                self.diagnostics.push(Diagnostic::warning(
+                    &format!("the image \"{}\" was not found at {}", src, file.display()),
+                    position,
+                ));
+            }
+        } else if self.config.probe_dimensions || self.config.responsive {
+            let bytes = fs::read(&file).unwrap_or_default();
+            if let Some((format, width, height)) = probe_dimensions(&bytes) {
+                image.format = Some(format);
+                if self.config.probe_dimensions {
+                    image.width = Some(width);
+                    image.height = Some(height);
+                }
+                if self.config.responsive {
+                    match create_responsive_image(&file, &bytes, format, &self.config) {
+                        Ok(responsive) => {
+                            image.variants = responsive.variants;
+                            image.placeholder = responsive.placeholder;
+                        }
+                        Err(e) => self
+                            .diagnostics
+                            .push(Diagnostic::warning(&e.to_string(), position)),
+                    }
+                }
+            }
+        }
+
+        image
+    }
+
+    fn img_tag(&self, image: &ImageRef) -> String {
+        let original = match (image.variants.first(), self.config.use_picture) {
+            (Some(first), true) => first.format,
+            _ => return self.img_only_tag(image),
+        };
+
+        let mut tag = String::from("<picture>");
+        for format in variant_formats(original, &self.config).into_iter().skip(1) {
+            tag.push_str("<source type=\"");
+            tag.push_str(format.mime_type());
+            tag.push_str("\" srcset=\"");
+            escape_html(&mut tag, &srcset(&image.variants, format)).unwrap();
+            tag.push_str("\" sizes=\"");
+            escape_html(&mut tag, &self.config.sizes).unwrap();
+            tag.push_str("\" />");
+        }
+        tag.push_str(&self.img_only_tag(image));
+        tag.push_str("</picture>");
+
+        tag
+    }
+
+    fn img_only_tag(&self, image: &ImageRef) -> String {
+        let mut tag = String::from("<img src=\"");
+        escape_href(&mut tag, &image.src).unwrap();
+        tag.push_str("\" alt=\"");
+        escape_html(&mut tag, &image.alt).unwrap();
+        tag.push('"');
+        if !image.title.is_empty() {
+            tag.push_str(" title=\"");
+            escape_html(&mut tag, &image.title).unwrap();
+            tag.push('"');
+        }
+        if let Some(first) = image.variants.first() {
+            tag.push_str(" srcset=\"");
+            escape_html(&mut tag, &srcset(&image.variants, first.format)).unwrap();
+            tag.push_str("\" sizes=\"");
+            escape_html(&mut tag, &self.config.sizes).unwrap();
+            tag.push('"');
+        }
+        if let (Some(width), Some(height)) = (image.width, image.height) {
+            tag.push_str(&format!(" width=\"{}\" height=\"{}\"", width, height));
+        }
+        match (&image.placeholder, self.config.placeholder) {
+            (Some(placeholder), PlaceholderKind::DataUri) => {
+                tag.push_str(" style=\"background-size:cover;background-image:url(");
+                escape_html(&mut tag, placeholder).unwrap();
+                tag.push_str(")\"");
+            }
+            (Some(placeholder), PlaceholderKind::Blurhash) => {
+                tag.push_str(" data-blurhash=\"");
+                escape_html(&mut tag, placeholder).unwrap();
+                tag.push('"');
+            }
+            _ => (),
+        }
+        if self.config.lazy_loading {
+            tag.push_str(" loading=\"lazy\"");
+        }
+        if self.config.async_decoding {
+            tag.push_str(" decoding=\"async\"");
+        }
+        tag.push_str(" />");
+
+        tag
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{html::HtmlContent, markdown::MarkdownContentRaw};
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let mut bytes = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR".to_vec();
+        bytes.extend(width.to_be_bytes());
+        bytes.extend(height.to_be_bytes());
+        bytes.extend([8, 6, 0, 0, 0]);
+        bytes
+    }
+
+    #[test]
+    fn image_dimensions_probed_from_headers() {
+        assert_eq!(
+            probe_dimensions(&png(640, 480)),
+            Some((ImageFormat::Png, 640, 480))
+        );
+
+        let gif = b"GIF89a\x20\x03\x58\x02\0\0\0";
+        assert_eq!(probe_dimensions(gif), Some((ImageFormat::Gif, 800, 600)));
+
+        let jpeg = [
+            0xFF, 0xD8, // SOI
+            0xFF, 0xE0, 0x00, 0x04, 0x00, 0x00, // APP0 w/ two bytes of payload
+            0xFF, 0xC0, 0x00, 0x11, 0x08, 0x01, 0x2C, 0x01, 0x90, // SOF0 400x300
+        ];
+        assert_eq!(probe_dimensions(&jpeg), Some((ImageFormat::Jpeg, 400, 300)));
+
+        let mut webp = b"RIFF\0\0\0\0WEBPVP8X\x0a\0\0\0\0\0\0\0".to_vec();
+        webp.extend([0x1F, 0x01, 0x00, 0x0F, 0x01, 0x00]); // 288 x 272
+        assert_eq!(probe_dimensions(&webp), Some((ImageFormat::Webp, 288, 272)));
+
+        let svg = br#"<?xml version="1.0"?><svg xmlns="http://www.w3.org/2000/svg" stroke-width="2" width="24px" height="32">"#;
+        assert_eq!(probe_dimensions(svg), Some((ImageFormat::Svg, 24, 32)));
+
+        let svg = br#"<svg viewBox="0 0 100 50"><path /></svg>"#;
+        assert_eq!(probe_dimensions(svg), Some((ImageFormat::Svg, 100, 50)));
+
+        assert_eq!(probe_dimensions(b"not an image"), None);
+    }
+
+    #[test]
+    fn image_local_files_resolved_with_dimensions() {
+        let dir = std::env::temp_dir().join(format!("dm-images-{}", std::process::id()));
+        fs::create_dir_all(dir.join("pages")).unwrap();
+        fs::create_dir_all(dir.join("public")).unwrap();
+        fs::write(dir.join("pages/diagram.png"), png(200, 100)).unwrap();
+        fs::write(dir.join("public/logo.png"), png(32, 32)).unwrap();
+
+        let mut config = Config::default();
+        config.features.images.public_dir = dir.join("public").to_string_lossy().to_string();
+        let page = dir.join("pages/index.md").to_string_lossy().to_string();
+        let md = "![The *diagram*](./diagram.png \"Diagram\")\n\n![logo](/logo.png)\n\n![gone](./missing.png)\n\n![remote](https://acme.com/a.png)";
+        let (md, _) = MarkdownContentRaw::new(md).parse(&config).unwrap();
+        let html = HtmlContent::for_page(&md, Some(&page), &config);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            html.content(),
+            [
+                "<p><img src=\"./diagram.png\" alt=\"The diagram\" title=\"Diagram\" width=\"200\" height=\"100\" loading=\"lazy\" decoding=\"async\" /></p>\n",
+                "<p><img src=\"/logo.png\" alt=\"logo\" width=\"32\" height=\"32\" loading=\"lazy\" decoding=\"async\" /></p>\n",
+                "<p><img src=\"./missing.png\" alt=\"gone\" loading=\"lazy\" decoding=\"async\" /></p>\n",
+                "<p><img src=\"https://acme.com/a.png\" alt=\"remote\" loading=\"lazy\" decoding=\"async\" /></p>\n",
+            ]
+            .concat()
+        );
+
+        assert_eq!(html.images.len(), 4);
+        assert_eq!(html.images[0].format, Some(ImageFormat::Png));
+        assert_eq!(html.images[0].position.line, 1);
+        assert!(html.images[1].found);
+        assert!(html.images[2].local && !html.images[2].found);
+        assert!(!html.images[3].local);
+
+        assert_eq!(html.diagnostics.len(), 1);
+        assert_eq!(html.diagnostics[0].position.line, 5);
+        assert!(html.diagnostics[0].message.contains("./missing.png"));
+    }
+
+    #[test]
+    fn image_attributes_can_be_turned_off() {
+        let mut config = Config::default();
+        config.features.images.lazy_loading = false;
+        config.features.images.async_decoding = false;
+        config.features.images.report_missing = false;
+        let (md, _) = MarkdownContentRaw::new("![gone](./missing.png)")
+            .parse(&config)
+            .unwrap();
+        let html = HtmlContent::new(&md, &config);
+
+        assert_eq!(
+            html.content(),
+            "<p><img src=\"./missing.png\" alt=\"gone\" /></p>\n"
+        );
+        assert!(html.diagnostics.is_empty());
+    }
+
+    #[test]
+    fn image_responsive_output_uses_picture() {
+        let dir = std::env::temp_dir().join(format!("dm-picture-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        image::RgbaImage::from_pixel(20, 10, image::Rgba([10, 120, 10, 255]))
+            .save(dir.join("photo.png"))
+            .unwrap();
+
+        let mut config = Config::default();
+        config.features.images.responsive = true;
+        config.features.images.widths = vec![10];
+        config.features.images.output_dir = dir.join("out").to_string_lossy().to_string();
+        let page = dir.join("index.md").to_string_lossy().to_string();
+        let (md, _) = MarkdownContentRaw::new("![photo](./photo.png)")
+            .parse(&config)
+            .unwrap();
+        let html = HtmlContent::for_page(&md, Some(&page), &config);
+        let out_files = fs::read_dir(dir.join("out")).unwrap().count();
+        fs::remove_dir_all(&dir).unwrap();
+        let content = html.content();
+
+        assert_eq!(out_files, 4);
+        assert!(
+            content.starts_with("<p><picture><source type=\"image/webp\" srcset=\"/_images/photo-")
+        );
+        assert!(content.contains("-10.webp 10w, /_images/photo-"));
+        assert!(content.contains("<img src=\"./photo.png\" alt=\"photo\" srcset=\"/_images/photo-"));
+        assert!(content.contains("sizes=\"100vw\" width=\"20\" height=\"10\""));
+        assert!(content
+            .contains("style=\"background-size:cover;background-image:url(data:image/png;base64,"));
+        assert!(content.ends_with("</picture></p>\n"));
+        assert_eq!(html.images[0].variants.len(), 4);
+    }
+}
@@ -0,0 +1,495 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::Config,
+    models::{
+        diagnostic::Diagnostic,
+        frontmatter::Frontmatter,
+        markdown::MarkdownContentRaw,
+        position::{SourceMap, SourcePosition},
+    },
+};
+
+use super::{
+    code_import::{CodeImport, CodeImports},
+    directive::{code_fence, Attributes},
+    md_attribute::fenced_ranges,
+};
+
+/// Another markdown document included in the page
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Include {
+    /// the path to the document as written
+    pub src: String,
+    /// the file which the path resolved to (in its canonical form when
+    /// it exists); the page depends on it
+    pub file: String,
+    /// the file which included this one (`None` when it is the page)
+    pub parent: Option<String>,
+    /// whether the file could be read
+    pub found: bool,
+    /// how many levels the document's headings were moved by
+    pub shift: i8,
+    /// the document's frontmatter when it is to be merged into the page
+    pub frontmatter: Option<Frontmatter>,
+    /// where the document was included on the page
+    pub position: SourcePosition,
+}
+
+/// What was found while splicing included documents into a page
+#[derive(Debug, Default)]
+pub struct Transcluded {
+    pub content: String,
+    /// where the included documents were spliced into the page
+    pub source_map: SourceMap,
+    pub includes: Vec<Include>,
+    /// code imported by the included documents (which is resolved
+    /// against the document rather than the page)
+    pub code_imports: Vec<CodeImport>,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/// Splices the body of other markdown documents into the page before
+/// it is parsed:
+///
+/// ```md
+/// <<< ./partial.md
+/// <<< ./partial.md{+1 frontmatter}
+/// ::include ./partial.md, shift=1, frontmatter
+/// ```
+///
+/// Paths are resolved against the including document (or the project
+/// root when they start with `@/`). Included documents can include
+/// others; a document which would include itself is reported rather
+/// than followed. Headings can be moved down (or up) a number of levels
+/// and the document's frontmatter can be kept to be merged into the
+/// page's.
+///
+/// This runs ahead of the directives -- rather than being one -- so that
+/// the included content takes part in every later step just as though
+/// it was written on the page.
+#[derive(Debug)]
+pub struct Includes<'a> {
+    config: &'a Config,
+    import: Regex,
+    directive: Regex,
+    heading: Regex,
+}
+
+impl<'a> Includes<'a> {
+    pub fn new(config: &'a Config) -> Self {
+        Includes {
+            config,
+            import: Regex::new(r"^(\s*)<<<\s+(\S+\.(?:md|dm))(?:\{([^}]*)\})?\s*$").unwrap(),
+            directive: Regex::new(r"^(\s*)::include\s+(.+?)\s*$").unwrap(),
+            heading: Regex::new(r"^( {0,3})(#{1,6})(\s|$)").unwrap(),
+        }
+    }
+
+    /// Splices the included documents into the markdown of the page
+    /// found in `file`
+    pub fn expand(&self, md: &str, file: Option<&str>) -> Transcluded {
+        let mut transcluded = Transcluded::default();
+        if !self.config.features.inline.markdown
+            || !(md.contains("<<<") || md.contains("::include"))
+        {
+            transcluded.content = md.to_string();
+            return transcluded;
+        }
+
+        let mut stack: Vec<PathBuf> = file.map(|f| canonical(Path::new(f))).into_iter().collect();
+        let mut source_map = SourceMap::new();
+        transcluded.content =
+            self.expand_in(md, file, &mut stack, &mut source_map, &mut transcluded);
+        transcluded.source_map = source_map;
+
+        transcluded
+    }
+
+    fn expand_in(
+        &self,
+        md: &str,
+        file: Option<&str>,
+        stack: &mut Vec<PathBuf>,
+        source_map: &mut SourceMap,
+        transcluded: &mut Transcluded,
+    ) -> String {
+        let fenced = fenced_ranges(md);
+        let dir = file
+            .and_then(|f| Path::new(f).parent())
+            .map(|p| p.to_path_buf())
+            .unwrap_or_default();
+        let mut output = String::with_capacity(md.len());
+        let mut offset = 0;
+
+        for line in md.split_inclusive('\n') {
+            let start = offset;
+            offset += line.len();
+            let request = match self.request(line.trim_end_matches(['\n', '\r'])) {
+                Some(request) if !fenced.iter().any(|r| r.contains(&start)) => request,
+                _ => {
+                    output.push_str(line);
+                    continue;
+                }
+            };
+
+            let position = SourcePosition::from_offset(md, start);
+            let path = match request.src.strip_prefix("@/") {
+                Some(path) => self.config.project_root.join(path),
+                None => dir.join(&request.src),
+            };
+            let canonical = canonical(&path);
+            let mut include = Include {
+                src: request.src.clone(),
+                file: canonical.to_string_lossy().to_string(),
+                parent: file.map(|f| f.to_string()),
+                found: false,
+                shift: request.shift,
+                frontmatter: None,
+                position,
+            };
+
+            if stack.contains(&canonical) {
+                let mut chain: Vec<String> = stack
+                    .iter()
+                    .map(|f| f.to_string_lossy().to_string())
+                    .collect();
+                chain.push(include.file.clone());
+                transcluded.diagnostics.push(Diagnostic::error(
+                    &format!(
+                        "including \"{}\" would create a cycle: {}",
+                        include.src,
+                        chain.join(" → ")
+                    ),
+                    position,
+                ));
+                output.push_str(line);
+                transcluded.includes.push(include);
+                continue;
+            }
+
+            let raw = match fs::read_to_string(&path) {
+                Ok(raw) => raw,
+                Err(e) => {
+                    transcluded.diagnostics.push(Diagnostic::error(
+                        &format!(
+                            "unable to include \"{}\" from {}: {}",
+                            include.src, include.file, e
+                        ),
+                        position,
+                    ));
+                    output.push_str(line);
+                    transcluded.includes.push(include);
+                    continue;
+                }
+            };
+            include.found = true;
+            let (body, frontmatter) = match MarkdownContentRaw::new(&raw).parse(self.config) {
+                Ok((body, frontmatter)) => (body.content(), Some(frontmatter)),
+                Err(_) => (raw, None),
+            };
+            if request
+                .frontmatter
+                .unwrap_or(self.config.features.inline.frontmatter)
+            {
+                include.frontmatter = frontmatter;
+            }
+            transcluded.includes.push(include);
+
+            // the included document's own includes and code imports are
+            // resolved against the document
+            let nested_from = transcluded.diagnostics.len();
+            stack.push(canonical);
+            let nested = Some(path.to_string_lossy().to_string());
+            let body = self.expand_in(
+                &body,
+                nested.as_deref(),
+                stack,
+                &mut SourceMap::new(),
+                transcluded,
+            );
+            stack.pop();
+            let (body, mut code_imports, mut diagnostics) =
+                CodeImports::new(self.config, nested.as_deref())
+                    .expand(&body, &mut SourceMap::new());
+            transcluded.code_imports.append(&mut code_imports);
+            transcluded.diagnostics.append(&mut diagnostics);
+            for diagnostic in transcluded.diagnostics[nested_from..].iter_mut() {
+                diagnostic.message = format!("in {}: {}", request.src, diagnostic.message);
+                diagnostic.position = position;
+            }
+
+            let body = self.shift_headings(&body, request.shift);
+            let spliced = output.len();
+            for l in body.trim_end_matches('\n').split_inclusive('\n') {
+                if !l.trim().is_empty() {
+                    output.push_str(&request.indent);
+                }
+                output.push_str(l);
+            }
+            if line.ends_with('\n') {
+                output.push('\n');
+            }
+            source_map.splice(start..offset, spliced..output.len());
+        }
+
+        output
+    }
+
+    /// The include asked for by a line (if it is one)
+    fn request(&self, line: &str) -> Option<IncludeRequest> {
+        if let Some(captures) = self.import.captures(line) {
+            let mut request = IncludeRequest {
+                indent: captures[1].to_string(),
+                src: captures[2].to_string(),
+                shift: 0,
+                frontmatter: None,
+            };
+            let options = captures.get(3).map(|o| o.as_str()).unwrap_or("");
+            for token in options.split(|c: char| c == ',' || c.is_whitespace()) {
+                match token {
+                    "frontmatter" => request.frontmatter = Some(true),
+                    "" => (),
+                    shift => {
+                        if let Ok(shift) = shift.parse::<i8>() {
+                            request.shift = shift;
+                        }
+                    }
+                }
+            }
+            return Some(request);
+        }
+
+        let captures = self.directive.captures(line)?;
+        let attributes = Attributes::parse(&captures[2]);
+        let src = attributes
+            .get("src")
+            .map(|s| s.to_string())
+            .or_else(|| attributes.label.clone())?;
+
+        Some(IncludeRequest {
+            indent: captures[1].to_string(),
+            src,
+            shift: attributes
+                .get("shift")
+                .and_then(|s| s.parse::<i8>().ok())
+                .unwrap_or(0),
+            frontmatter: attributes.get("frontmatter").map(|f| f != "false"),
+        })
+    }
+
+    /// Moves the ATX headings outside of code by `shift` levels (keeping
+    /// them between `h1` and `h6`)
+    fn shift_headings(&self, md: &str, shift: i8) -> String {
+        if shift == 0 {
+            return md.to_string();
+        }
+        let mut output = String::with_capacity(md.len());
+        let mut fence: Option<(char, usize)> = None;
+        for line in md.split_inclusive('\n') {
+            if let Some((c, len)) = code_fence(line.trim_end()) {
+                fence = match fence {
+                    None => Some((c, len)),
+                    Some((fc, flen)) if fc == c && len >= flen => None,
+                    open => open,
+                };
+                output.push_str(line);
+                continue;
+            }
+            match self.heading.captures(line) {
+                Some(captures) if fence.is_none() => {
+                    let level = (captures[2].len() as i8 + shift).clamp(1, 6) as usize;
+                    output.push_str(&captures[1]);
+                    output.push_str(&"#".repeat(level));
+                    output.push_str(&line[captures[2].len() + captures[1].len()..]);
+                }
+                _ => output.push_str(line),
+            }
+        }
+
+        output
+    }
+}
+
+struct IncludeRequest {
+    indent: String,
+    src: String,
+    shift: i8,
+    frontmatter: Option<bool>,
+}
+
+/// Whether a file is a markdown document (which is included rather than
+/// imported as code)
+pub(crate) fn is_markdown(src: &str) -> bool {
+    let src = src.to_lowercase();
+    src.ends_with(".md") || src.ends_with(".dm")
+}
+
+/// The canonical form of a path where the file exists (so that the same
+/// document reached by different paths is recognized)
+pub(crate) fn canonical(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::Options,
+        models::{html::HtmlContent, markdown::MarkdownContent},
+    };
+
+    /// writes the partials into a directory and returns the path for a
+    /// page which sits alongside them
+    fn page() -> String {
+        let dir = std::env::temp_dir().join("dm-includes");
+        fs::create_dir_all(dir.join("parts")).unwrap();
+        fs::write(
+            dir.join("parts/intro.md"),
+            "---\ntitle: Intro\nauthor: Jane\n---\n# Intro\n\nSee the code:\n\n<<< ./snippet.rs\n\n::include ./footer.md\n",
+        )
+        .unwrap();
+        fs::write(dir.join("parts/snippet.rs"), "fn main() {}\n").unwrap();
+        fs::write(
+            dir.join("parts/footer.md"),
+            "## Footer\n```md\n# not a heading\n```\n",
+        )
+        .unwrap();
+        fs::write(dir.join("parts/a.md"), "a\n<<< ./b.md\n").unwrap();
+        fs::write(dir.join("parts/b.md"), "b\n<<< ./a.md\n").unwrap();
+
+        dir.join("page.md").to_string_lossy().to_string()
+    }
+
+    #[test]
+    fn include_splices_documents() {
+        let config = Config::default();
+        let transcluded =
+            Includes::new(&config).expand("# Page\n<<< ./parts/intro.md{+1}\nend", Some(&page()));
+
+        assert_eq!(
+            transcluded.content,
+            "# Page\n## Intro\n\nSee the code:\n\n```rs\nfn main() {}\n```\n\n### Footer\n```md\n# not a heading\n```\nend"
+        );
+        assert_eq!(transcluded.includes.len(), 2);
+        assert!(transcluded.includes[1]
+            .parent
+            .as_deref()
+            .unwrap()
+            .ends_with("intro.md"));
+        assert_eq!(transcluded.includes[1].shift, 0);
+        let dir = std::env::temp_dir().join("dm-includes");
+        assert_eq!(
+            transcluded.includes[0].file,
+            canonical(&dir.join("parts/intro.md")).to_string_lossy()
+        );
+        assert_eq!(
+            transcluded.code_imports[0].file,
+            canonical(&dir.join("parts/snippet.rs")).to_string_lossy()
+        );
+        assert!(transcluded.includes[0].frontmatter.is_none());
+        assert!(transcluded.diagnostics.is_empty());
+    }
+
+    #[test]
+    fn include_frontmatter_can_be_merged() {
+        let config = Config::default();
+        let raw = MarkdownContentRaw::new(
+            "---\ntitle: Page\n---\n::include ./parts/intro.md, frontmatter\n",
+        );
+        let (md, mut frontmatter) = raw.parse(&config).unwrap();
+        let html = HtmlContent::for_page(&md, Some(&page()), &config);
+        frontmatter.merge(&html.included_frontmatter()).unwrap();
+
+        assert_eq!(frontmatter.title.as_deref(), Some("Page"));
+        assert_eq!(frontmatter.other["author"], "Jane");
+        assert_eq!(html.dependencies().len(), 3);
+        assert!(html.content().starts_with("<h1 id=\"intro\">Intro</h1>"));
+    }
+
+    #[test]
+    fn include_cycles_are_reported() {
+        let config = Config::default();
+        let transcluded =
+            Includes::new(&config).expand("<<< ./parts/a.md\n<<< ./parts/nope.md", Some(&page()));
+
+        assert_eq!(transcluded.content, "a\nb\n<<< ./a.md\n<<< ./parts/nope.md");
+        assert_eq!(transcluded.diagnostics.len(), 2);
+        assert!(transcluded.diagnostics[0].message.contains("cycle"));
+        assert_eq!(transcluded.diagnostics[0].position.line, 1);
+        assert_eq!(transcluded.diagnostics[1].position.line, 2);
+    }
+
+    #[test]
+    fn include_positions_after_an_include_are_on_the_page() {
+        let config = Config::default();
+        let (md, _) = MarkdownContentRaw::new("# Page\n<<< ./parts/intro.md\n\n::tab \"Stray\"\n")
+            .parse(&config)
+            .unwrap();
+        let html = HtmlContent::for_page(&md, Some(&page()), &config);
+
+        assert_eq!(html.diagnostics.len(), 1, "{:?}", html.diagnostics);
+        assert!(html.diagnostics[0]
+            .message
+            .contains("outside of a ::tabs menu"));
+        assert_eq!(html.diagnostics[0].position.line, 4);
+        assert_eq!(html.diagnostics[0].position.column, 1);
+    }
+
+    #[test]
+    fn include_project_root_paths_use_the_configured_root() {
+        let page = page();
+        let config = Config::with_options(Options {
+            output: None,
+            dir_mapping: None,
+            project_root: Some(std::env::temp_dir().join("dm-includes")),
+            features: None,
+            hooks: None,
+        });
+        let transcluded = Includes::new(&config).expand("<<< @/parts/footer.md", Some(&page));
+
+        assert_eq!(
+            transcluded.content,
+            "## Footer\n```md\n# not a heading\n```"
+        );
+        assert!(transcluded.includes[0].found);
+        assert!(transcluded.diagnostics.is_empty());
+    }
+
+    #[test]
+    fn include_links_are_found_on_the_page() {
+        let page = page();
+        let dir = std::env::temp_dir().join("dm-includes");
+        fs::write(dir.join("parts/links.md"), "See [the intro](./intro.md).\n").unwrap();
+        let config = Config::default();
+        let (md, _) = MarkdownContentRaw::new("# Page\n<<< ./parts/links.md\n\n[top](#page)\n")
+            .parse(&config)
+            .unwrap();
+        let html = HtmlContent::for_page(&md, Some(&page), &config);
+        let links: Vec<(&str, usize)> = html
+            .links
+            .iter()
+            .map(|l| (l.href.as_str(), l.position.line))
+            .collect();
+
+        assert_eq!(links, vec![("./intro.md", 2), ("#page", 4)]);
+    }
+
+    #[test]
+    fn include_can_be_turned_off() {
+        let mut config = Config::default();
+        config.features.inline.markdown = false;
+        let md = MarkdownContent::new(&MarkdownContentRaw::new("<<< ./parts/footer.md"), &config)
+            .unwrap();
+        let html = HtmlContent::for_page(&md, Some(&page()), &config);
+
+        assert!(html.content().contains("&lt;&lt;&lt; ./parts/footer.md"));
+    }
+}
@@ -0,0 +1,400 @@
+use std::ops::Range;
+
+use pulldown_cmark::{
+    escape::{escape_href, escape_html},
+    Event, Options as ParserOptions, Parser, Tag,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::{features::link::LinkConfig, Config, OutputFormat},
+    models::{position::SourcePosition, route_map::is_asset},
+};
+
+/// File extensions which identify a link as pointing to another
+/// markdown page rather than a static asset
+const PAGE_EXTENSIONS: [&str; 2] = [".md", ".dm"];
+
+/// The broad categories which a link's destination falls into
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum LinkKind {
+    /// a link to a fragment on the _current_ page (e.g., `#overview`)
+    Anchor,
+    /// a `mailto:` link
+    Mailto,
+    /// a link which leaves the site (has a scheme or starts with `//`)
+    External,
+    /// an absolute path within the site (e.g., `/guide/intro`)
+    Internal,
+    /// a path relative to the current page (e.g., `./intro.md`)
+    Relative,
+}
+
+impl From<&str> for LinkKind {
+    fn from(href: &str) -> Self {
+        let lower = href.trim().to_lowercase();
+        if lower.starts_with('#') {
+            LinkKind::Anchor
+        } else if lower.starts_with("mailto:") {
+            LinkKind::Mailto
+        } else if lower.starts_with("//") || has_scheme(&lower) {
+            LinkKind::External
+        } else if lower.starts_with('/') {
+            LinkKind::Internal
+        } else {
+            LinkKind::Relative
+        }
+    }
+}
+
+impl LinkKind {
+    /// Whether the link targets another page on the same site
+    pub fn is_local(&self) -> bool {
+        matches!(self, LinkKind::Internal | LinkKind::Relative)
+    }
+}
+
+/// The outcome of validating a link
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum LinkStatus {
+    /// the link resolves to a page (and fragment) which exists
+    Valid,
+    /// no page exists for the resolved route
+    BrokenPage(String),
+    /// the page exists but has no heading with the given id
+    BrokenFragment(String),
+    /// the link points to a page which is still a draft
+    Draft(String),
+    /// the target only exists when compared case-insensitively; the
+    /// correctly cased route (or fragment) is provided
+    CaseMismatch(String),
+    /// an external link which responded successfully but only after
+    /// following redirects; the final URL is provided
+    Redirected(String),
+    /// an external link which responded with an error status code
+    HttpStatus(u16),
+    /// an external link which could not be reached at all
+    Unreachable(String),
+}
+
+/// A link found on a page along with where it was found and --
+/// once checked -- whether it is valid.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct LinkRef {
+    /// the link's destination as written on the page
+    pub href: String,
+    pub kind: LinkKind,
+    /// the text content of the link
+    pub text: String,
+    pub position: SourcePosition,
+    /// the validity of the link; `None` until checked
+    pub status: Option<LinkStatus>,
+}
+
+impl LinkRef {
+    /// the hash fragment of the link (without the `#`), if any
+    pub fn fragment(&self) -> Option<&str> {
+        self.href
+            .split_once('#')
+            .map(|(_, f)| f)
+            .filter(|f| !f.is_empty())
+    }
+}
+
+/// Extracts all the links found in the markdown content
+pub fn extract_links(md: &str, options: ParserOptions) -> Vec<LinkRef> {
+    let mut links: Vec<LinkRef> = vec![];
+    let mut open: Vec<usize> = vec![];
+
+    for (event, range) in Parser::new_ext(md, options).into_offset_iter() {
+        match event {
+            Event::Start(Tag::Link(_, dest, _)) => {
+                open.push(links.len());
+                links.push(LinkRef {
+                    href: dest.to_string(),
+                    kind: LinkKind::from(dest.as_ref()),
+                    text: String::new(),
+                    position: SourcePosition::from_offset(md, range.start),
+                    status: None,
+                });
+            }
+            Event::Text(text) | Event::Code(text) => {
+                if let Some(idx) = open.last() {
+                    links[*idx].text.push_str(&text);
+                }
+            }
+            Event::End(Tag::Link(..)) => {
+                open.pop();
+            }
+            _ => (),
+        }
+    }
+
+    links
+}
+
+/// Checks for a URI scheme such as `https:` or `tel:`
+fn has_scheme(href: &str) -> bool {
+    match href.find(':') {
+        Some(idx) if idx > 0 => href[..idx]
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '+' || c == '-' || c == '.'),
+        _ => false,
+    }
+}
+
+/// Splits an href into its _path_ and the remainder which starts
+/// with either a `?` or `#` character (or is empty).
+pub fn split_suffix(href: &str) -> (&str, &str) {
+    match href.find(['?', '#']) {
+        Some(idx) => href.split_at(idx),
+        None => (href, ""),
+    }
+}
+
+/// Converts a link destination which points to a markdown page
+/// into the route the router will know it by.
+///
+/// - `clean_all_routes` strips the `.md`/`.dm` file extension
+/// - `clean_index_routes` strips a trailing `index` page so that
+///   `/guide/index.md` becomes `/guide/`
+///
+/// Query parameters and hash fragments are always retained.
+pub fn clean_route(href: &str, config: &LinkConfig) -> String {
+    let (path, suffix) = split_suffix(href);
+    let mut path = path.to_string();
+
+    if config.clean_all_routes || config.clean_index_routes {
+        for ext in PAGE_EXTENSIONS {
+            if let Some(stripped) = path.strip_suffix(ext) {
+                let is_index = stripped == "index" || stripped.ends_with("/index");
+                if config.clean_all_routes || is_index {
+                    path = stripped.to_string();
+                }
+                break;
+            }
+        }
+    }
+
+    if config.clean_index_routes {
+        if path == "index" {
+            path = String::from("./");
+        } else if let Some(stripped) = path.strip_suffix("/index") {
+            path = format!("{}/", stripped);
+        }
+    }
+
+    [path.as_str(), suffix].concat()
+}
+
+/// Rewrites links to pages on the same site as `<router-link>`
+/// components when the output is an SFC and `use_router_links`
+/// is turned on. All other links -- including those to static assets
+/// such as `./guide.pdf` -- pass through untouched.
+///
+/// Every link which is rendered is also recorded so that it can be
+/// validated once the page is converted.
+#[derive(Debug)]
+pub struct RouterLinks {
+    enabled: bool,
+    config: LinkConfig,
+    /// tracks -- for each open link -- whether it was converted so
+    /// that the matching closing tag can be converted too
+    open: Vec<bool>,
+    links: Vec<LinkRef>,
+    /// the index of each open link in `links` so that its text can
+    /// be gathered
+    open_refs: Vec<usize>,
+}
+
+impl RouterLinks {
+    pub fn new(config: &Config) -> Self {
+        RouterLinks {
+            enabled: config.output == OutputFormat::SFC && config.features.links.use_router_links,
+            config: config.features.links.clone(),
+            open: vec![],
+            links: vec![],
+            open_refs: vec![],
+        }
+    }
+
+    /// The links found in the content (in the order they were found)
+    pub fn finish(self) -> Vec<LinkRef> {
+        self.links
+    }
+
+    /// `range` is where the event was found in `md`
+    pub fn handle<'a>(&mut self, event: Event<'a>, range: Range<usize>, md: &str) -> Event<'a> {
+        self.record(&event, range, md);
+        if !self.enabled {
+            return event;
+        }
+
+        match event {
+            Event::Start(Tag::Link(kind, dest, title)) => {
+                let (path, _) = split_suffix(&dest);
+                if LinkKind::from(dest.as_ref()).is_local() && !is_asset(path) {
+                    self.open.push(true);
+                    Event::Html(self.open_tag(&dest, &title).into())
+                } else {
+                    self.open.push(false);
+                    Event::Start(Tag::Link(kind, dest, title))
+                }
+            }
+            Event::End(Tag::Link(kind, dest, title)) => match self.open.pop() {
+                Some(true) => Event::Html("</router-link>".into()),
+                _ => Event::End(Tag::Link(kind, dest, title)),
+            },
+            _ => event,
+        }
+    }
+
+    fn record(&mut self, event: &Event, range: Range<usize>, md: &str) {
+        match event {
+            Event::Start(Tag::Link(_, dest, _)) => {
+                self.open_refs.push(self.links.len());
+                self.links.push(LinkRef {
+                    href: dest.to_string(),
+                    kind: LinkKind::from(dest.as_ref()),
+                    text: String::new(),
+                    position: SourcePosition::from_offset(md, range.start),
+                    status: None,
+                });
+            }
+            Event::Text(text) | Event::Code(text) => {
+                if let Some(idx) = self.open_refs.last() {
+                    self.links[*idx].text.push_str(text);
+                }
+            }
+            Event::End(Tag::Link(..)) => {
+                self.open_refs.pop();
+            }
+            _ => (),
+        }
+    }
+
+    fn open_tag(&self, dest: &str, title: &str) -> String {
+        let mut tag = String::from("<router-link to=\"");
+        escape_href(&mut tag, &clean_route(dest, &self.config)).unwrap();
+        tag.push('"');
+        if !self.config.router_link_class.is_empty() {
+            tag.push_str(" class=\"");
+            escape_html(&mut tag, &self.config.router_link_class).unwrap();
+            tag.push('"');
+        }
+        if !title.is_empty() {
+            tag.push_str(" title=\"");
+            escape_html(&mut tag, title).unwrap();
+            tag.push('"');
+        }
+        tag.push('>');
+
+        tag
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::html::HtmlContent;
+
+    #[test]
+    fn link_kind_from_href() {
+        assert_eq!(LinkKind::from("#intro"), LinkKind::Anchor);
+        assert_eq!(LinkKind::from("mailto:bob@acme.com"), LinkKind::Mailto);
+        assert_eq!(LinkKind::from("https://acme.com"), LinkKind::External);
+        assert_eq!(LinkKind::from("//acme.com/foo"), LinkKind::External);
+        assert_eq!(LinkKind::from("/guide/intro.md"), LinkKind::Internal);
+        assert_eq!(LinkKind::from("./intro.md"), LinkKind::Relative);
+        assert_eq!(LinkKind::from("intro.md"), LinkKind::Relative);
+    }
+
+    #[test]
+    fn link_refs_are_extracted_with_positions() {
+        let md = "# Title\n\nSee [the `intro`](./intro.md#setup) and\n[Acme](https://acme.com).";
+        let links = extract_links(md, ParserOptions::empty());
+
+        assert_eq!(links.len(), 2);
+        assert_eq!(links[0].text, "the intro");
+        assert_eq!(links[0].kind, LinkKind::Relative);
+        assert_eq!(links[0].fragment(), Some("setup"));
+        assert_eq!(links[0].position.line, 3);
+        assert_eq!(links[0].position.column, 5);
+        assert_eq!(links[1].kind, LinkKind::External);
+        assert_eq!(links[1].position.line, 4);
+        assert_eq!(links[1].fragment(), None);
+    }
+
+    #[test]
+    fn link_routes_are_cleaned() {
+        let config = LinkConfig::default();
+        assert_eq!(clean_route("/guide/intro.md", &config), "/guide/intro");
+        assert_eq!(clean_route("/guide/index.md", &config), "/guide/");
+        assert_eq!(clean_route("./index.md#setup", &config), "./#setup");
+        assert_eq!(clean_route("index.md#setup", &config), "./#setup");
+        assert_eq!(clean_route("../intro.md?a=1#b", &config), "../intro?a=1#b");
+        assert_eq!(clean_route("/logo.png", &config), "/logo.png");
+    }
+
+    #[test]
+    fn link_routes_only_clean_index_when_configured() {
+        let mut config = LinkConfig {
+            clean_all_routes: false,
+            ..LinkConfig::default()
+        };
+        assert_eq!(clean_route("/guide/intro.md", &config), "/guide/intro.md");
+        assert_eq!(clean_route("/guide/index.md#a", &config), "/guide/#a");
+
+        config.clean_index_routes = false;
+        assert_eq!(clean_route("/guide/index.md", &config), "/guide/index.md");
+    }
+
+    #[test]
+    fn link_internal_links_become_router_links() {
+        let html = HtmlContent::try_from(
+            "See [the intro](./intro.md#setup \"Intro\") or [Acme](https://acme.com).",
+        )
+        .unwrap();
+
+        assert_eq!(
+            html.content(),
+            "<p>See <router-link to=\"./intro#setup\" class=\"router-link\" title=\"Intro\">the intro</router-link> or <a href=\"https://acme.com\">Acme</a>.</p>\n"
+        );
+    }
+
+    #[test]
+    fn link_assets_do_not_become_router_links() {
+        let html = HtmlContent::try_from(
+            "[pdf](./guide.pdf), [logo](/img/a.png), [zip](/downloads/x.zip?v=2) and [up](../)",
+        )
+        .unwrap();
+
+        assert_eq!(
+            html.content(),
+            "<p><a href=\"./guide.pdf\">pdf</a>, <a href=\"/img/a.png\">logo</a>, <a href=\"/downloads/x.zip?v=2\">zip</a> and <router-link to=\"../\" class=\"router-link\">up</router-link></p>\n"
+        );
+    }
+
+    #[test]
+    fn link_router_links_not_used_for_html_output() {
+        let config = Config {
+            output: OutputFormat::HTML,
+            ..Config::default()
+        };
+        let mut links = RouterLinks::new(&config);
+        let event = links.handle(
+            Event::Start(Tag::Link(
+                pulldown_cmark::LinkType::Inline,
+                "/intro.md".into(),
+                "".into(),
+            )),
+            0..14,
+            "[a](/intro.md)",
+        );
+
+        assert!(matches!(event, Event::Start(Tag::Link(..))));
+    }
+}
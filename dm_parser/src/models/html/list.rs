@@ -0,0 +1,238 @@
+use std::ops::Range;
+
+use pulldown_cmark::{escape::escape_html, CowStr, Event, Options as ParserOptions, Parser, Tag};
+
+use crate::config::{
+    features::collapsible::{CollapsibleConfig, CollapsibleOutput},
+    Config,
+};
+
+/// Whether a collapsible list item starts out expanded or collapsed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Collapse {
+    Expanded,
+    Collapsed,
+}
+
+/// Determines -- for every list item on the page in document order --
+/// whether it is collapsible. The markdown parser does not retain which
+/// bullet character was used so it is recovered from the source:
+///
+/// - `+` items are collapsible and start expanded
+/// - `*` items are collapsible and start collapsed
+/// - `-` (and ordered) items are static unless `all_lists_are_collapsible`
+///
+/// Only items which have a sub-list have anything to collapse.
+pub fn collapsible_items(
+    md: &str,
+    options: ParserOptions,
+    config: &Config,
+) -> Vec<Option<Collapse>> {
+    let all_lists = config.features.lists.all_lists_are_collapsible;
+    // each item's collapse behavior and whether it has a sub-list
+    let mut items: Vec<(Option<Collapse>, bool)> = vec![];
+    let mut open: Vec<usize> = vec![];
+
+    for (event, range) in Parser::new_ext(md, options).into_offset_iter() {
+        match event {
+            Event::Start(Tag::Item) => {
+                open.push(items.len());
+                let collapse = match marker(md, &range) {
+                    Some('+') => Some(Collapse::Expanded),
+                    Some('*') => Some(Collapse::Collapsed),
+                    _ if all_lists => Some(Collapse::Expanded),
+                    _ => None,
+                };
+                items.push((collapse, false));
+            }
+            Event::End(Tag::Item) => {
+                open.pop();
+            }
+            Event::Start(Tag::List(_)) => {
+                if let Some(idx) = open.last() {
+                    items[*idx].1 = true;
+                }
+            }
+            _ => (),
+        }
+    }
+
+    items
+        .into_iter()
+        .map(|(collapse, has_sublist)| collapse.filter(|_| has_sublist))
+        .collect()
+}
+
+/// The bullet character which starts a list item
+fn marker(md: &str, range: &Range<usize>) -> Option<char> {
+    md.get(range.start..)
+        .and_then(|s| s.trim_start().chars().next())
+}
+
+/// Renders collapsible list items -- as identified by `collapsible_items` --
+/// using either `<details>`/`<summary>` or classes on the `<li>`.
+#[derive(Debug)]
+pub struct CollapsibleLists {
+    config: CollapsibleConfig,
+    items: std::vec::IntoIter<Option<Collapse>>,
+    /// for each open item: whether it is collapsible and -- if so --
+    /// whether its `<summary>` is still open
+    open: Vec<Option<bool>>,
+}
+
+impl CollapsibleLists {
+    pub fn new(md: &str, options: ParserOptions, config: &Config) -> Self {
+        let collapsible = &config.features.collapsable;
+        let items = match collapsible.enabled {
+            true => collapsible_items(md, options, config),
+            false => vec![],
+        };
+
+        CollapsibleLists {
+            config: collapsible.clone(),
+            items: items.into_iter(),
+            open: vec![],
+        }
+    }
+
+    pub fn handle<'a>(&mut self, event: Event<'a>) -> Event<'a> {
+        if !self.config.enabled {
+            return event;
+        }
+
+        match event {
+            Event::Start(Tag::Item) => match self.items.next().flatten() {
+                Some(collapse) => {
+                    self.open.push(Some(true));
+                    Event::Html(CowStr::from(self.open_item(collapse)))
+                }
+                None => {
+                    self.open.push(None);
+                    event
+                }
+            },
+            Event::Start(Tag::List(start)) => match self.open.last_mut() {
+                Some(Some(summary_open)) if *summary_open => {
+                    *summary_open = false;
+                    let list = match start {
+                        None => String::from("<ul>\n"),
+                        Some(1) => String::from("<ol>\n"),
+                        Some(start) => format!("<ol start=\"{}\">\n", start),
+                    };
+                    match self.config.output {
+                        CollapsibleOutput::Details => {
+                            Event::Html(CowStr::from(format!("</summary>\n{}", list)))
+                        }
+                        CollapsibleOutput::Classes => Event::Start(Tag::List(start)),
+                    }
+                }
+                _ => Event::Start(Tag::List(start)),
+            },
+            Event::End(Tag::Item) => match self.open.pop() {
+                Some(Some(_)) if self.config.output == CollapsibleOutput::Details => {
+                    Event::Html(CowStr::from("</details></li>\n"))
+                }
+                _ => event,
+            },
+            _ => event,
+        }
+    }
+
+    fn open_item(&self, collapse: Collapse) -> String {
+        let mut tag = String::from("<li class=\"");
+        escape_html(&mut tag, &self.config.class).unwrap();
+        match self.config.output {
+            CollapsibleOutput::Details => {
+                tag.push_str("\"><details");
+                if collapse == Collapse::Expanded {
+                    tag.push_str(" open");
+                }
+                tag.push_str("><summary>");
+            }
+            CollapsibleOutput::Classes => {
+                tag.push(' ');
+                let state = match collapse {
+                    Collapse::Expanded => &self.config.expanded_class,
+                    Collapse::Collapsed => &self.config.collapsed_class,
+                };
+                escape_html(&mut tag, state).unwrap();
+                tag.push_str("\">");
+            }
+        }
+
+        tag
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{html::HtmlContent, markdown::MarkdownContentRaw};
+
+    const LISTS: &str = "* foo\n  - f1\n\n+ bar\n  - b1\n\n- baz\n  - ba1\n\n+ leaf\n";
+
+    fn html(md: &str, config: &Config) -> String {
+        let (md, _) = MarkdownContentRaw::new(md).parse(config).unwrap();
+        HtmlContent::new(&md, config).content()
+    }
+
+    #[test]
+    fn list_markers_recovered_from_source() {
+        let items = collapsible_items(LISTS, ParserOptions::empty(), &Config::default());
+
+        assert_eq!(
+            items,
+            vec![
+                Some(Collapse::Collapsed),
+                None,
+                Some(Collapse::Expanded),
+                None,
+                None,
+                None,
+                // no sub-list so nothing to collapse
+                None,
+            ]
+        );
+    }
+
+    #[test]
+    fn list_collapsible_items_use_details() {
+        assert_eq!(
+            html(LISTS, &Config::default()),
+            [
+                "<ul>\n<li class=\"collapsible\"><details><summary>foo</summary>\n<ul>\n<li>f1</li>\n</ul>\n</details></li>\n</ul>\n",
+                "<ul>\n<li class=\"collapsible\"><details open><summary>bar</summary>\n<ul>\n<li>b1</li>\n</ul>\n</details></li>\n</ul>\n",
+                "<ul>\n<li>baz\n<ul>\n<li>ba1</li>\n</ul>\n</li>\n</ul>\n",
+                "<ul>\n<li>leaf</li>\n</ul>\n",
+            ]
+            .concat()
+        );
+    }
+
+    #[test]
+    fn list_collapsible_items_use_classes() {
+        let mut config = Config::default();
+        config.features.collapsable.output = CollapsibleOutput::Classes;
+        config.features.lists.all_lists_are_collapsible = true;
+
+        assert_eq!(
+            html("* foo\n  - f1\n\n- baz\n  1. ba1\n", &config),
+            [
+                "<ul>\n<li class=\"collapsible collapsed\">foo\n<ul>\n<li>f1</li>\n</ul>\n</li>\n</ul>\n",
+                "<ul>\n<li class=\"collapsible expanded\">baz\n<ol>\n<li>ba1</li>\n</ol>\n</li>\n</ul>\n",
+            ]
+            .concat()
+        );
+    }
+
+    #[test]
+    fn list_collapsible_can_be_turned_off() {
+        let mut config = Config::default();
+        config.features.collapsable.enabled = false;
+
+        assert_eq!(
+            html("* foo\n  - f1\n", &config),
+            "<ul>\n<li>foo\n<ul>\n<li>f1</li>\n</ul>\n</li>\n</ul>\n"
+        );
+    }
+}
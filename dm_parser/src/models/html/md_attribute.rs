@@ -0,0 +1,428 @@
+use regex::Regex;
+
+use crate::{
+    config::Config,
+    models::{
+        diagnostic::Diagnostic,
+        position::{SourceMap, SourcePosition},
+    },
+};
+
+use super::directive::code_fence;
+
+/// The content of an element with an `md` attribute which has been
+/// pulled out of the page so that it can be rendered as markdown
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MarkdownBlock {
+    /// the HTML comment left where the content was
+    pub placeholder: String,
+    /// the content with its common indentation removed
+    pub markdown: String,
+    /// the indentation which was removed from the content
+    pub indent: String,
+    /// whether the content sat on the same line as its tags (and so is
+    /// rendered without a wrapping paragraph)
+    pub inline: bool,
+    pub position: SourcePosition,
+}
+
+impl MarkdownBlock {
+    /// The HTML which replaces the placeholder once the block's markdown
+    /// has been rendered; the original indentation is restored to every
+    /// line outside of a `<pre>` element.
+    pub fn html(&self, rendered: &str) -> String {
+        if self.inline {
+            let trimmed = rendered.trim_end();
+            let paragraph = trimmed
+                .strip_prefix("<p>")
+                .and_then(|p| p.strip_suffix("</p>"))
+                .filter(|p| !p.contains("<p>"));
+            return paragraph.unwrap_or(trimmed).to_string();
+        }
+
+        let mut html = String::with_capacity(rendered.len());
+        let mut in_pre = false;
+        for line in rendered.trim_end().split_inclusive('\n') {
+            if !in_pre && !line.trim().is_empty() {
+                html.push_str(&self.indent);
+            }
+            html.push_str(line);
+            if line.contains("<pre") {
+                in_pre = true;
+            }
+            if line.contains("</pre>") {
+                in_pre = false;
+            }
+        }
+
+        html
+    }
+}
+
+/// Finds HTML elements which have an `md` attribute -- at any depth in
+/// the page's HTML -- and pulls their content out to be rendered as
+/// markdown (normally content inside an HTML block is left untouched):
+///
+/// ```md
+/// <table><tr>
+/// <td md>
+///     left _hand_ content
+/// </td>
+/// </tr></table>
+/// ```
+///
+/// The `md` attribute itself is removed from the element.
+#[derive(Debug)]
+pub struct MarkdownElements {
+    enabled: bool,
+    opener: Regex,
+    attribute: Regex,
+}
+
+impl MarkdownElements {
+    pub fn new(config: &Config) -> Self {
+        MarkdownElements {
+            enabled: config.features.markdown.md_attribute,
+            opener: Regex::new(
+                r#"<([A-Za-z][A-Za-z0-9-]*)((?:\s+[^\s=/>]+(?:\s*=\s*(?:"[^"]*"|'[^']*'|[^\s"'>]+))?)*)\s*(/?)>"#,
+            )
+            .unwrap(),
+            attribute: Regex::new(
+                r#"\s+([^\s=/>]+)(?:\s*=\s*(?:"[^"]*"|'[^']*'|[^\s"'>]+))?"#,
+            )
+            .unwrap(),
+        }
+    }
+
+    /// Replaces the content of every top-level element with an `md`
+    /// attribute with a placeholder; elements nested inside of that
+    /// content are found when the content itself is rendered. Where each
+    /// element's content was replaced is recorded in `source_map`.
+    pub fn extract(
+        &self,
+        md: &str,
+        source_map: &mut SourceMap,
+    ) -> (String, Vec<MarkdownBlock>, Vec<Diagnostic>) {
+        let mut blocks = vec![];
+        let mut diagnostics = vec![];
+        if !self.enabled {
+            return (md.to_string(), blocks, diagnostics);
+        }
+
+        let code = code_ranges(md);
+        let mut output = String::with_capacity(md.len());
+        let mut last = 0;
+        let mut search = 0;
+
+        while let Some(captures) = self.opener.captures(&md[search..]) {
+            let base = search;
+            let open = captures.get(0).unwrap();
+            let (open_start, open_end) = (base + open.start(), base + open.end());
+            search = open_end;
+            if code.iter().any(|r| r.contains(&open_start)) {
+                continue;
+            }
+            let attributes = captures.get(2).unwrap();
+            let md_attribute = self
+                .attribute
+                .captures_iter(attributes.as_str())
+                .find(|a| a[1].eq_ignore_ascii_case("md"))
+                .map(|a| a.get(0).unwrap().range());
+            let md_attribute = match md_attribute {
+                Some(range) => {
+                    base + attributes.start() + range.start..base + attributes.start() + range.end
+                }
+                None => continue,
+            };
+
+            let position = SourcePosition::from_offset(md, open_start);
+            if !captures[3].is_empty() {
+                diagnostics.push(Diagnostic::warning(
+                    "an element with the md attribute has no content to parse",
+                    position,
+                ));
+                continue;
+            }
+            let tag = &captures[1];
+            let close = match closing_tag(md, tag, open_end) {
+                Some(close) => close,
+                None => {
+                    diagnostics.push(Diagnostic::warning(
+                        &format!(
+                            "the <{}> element with the md attribute is never closed",
+                            tag
+                        ),
+                        position,
+                    ));
+                    continue;
+                }
+            };
+
+            let inner = &md[open_end..close.start];
+            let inline = !inner.contains('\n');
+            let (markdown, indent) = match inline {
+                true => (inner.trim().to_string(), String::new()),
+                false => dedent(inner),
+            };
+            let placeholder = format!("<!--dm-md-{}-->", blocks.len());
+
+            output.push_str(&md[last..md_attribute.start]);
+            let spliced = output.len();
+            output.push_str(&md[md_attribute.end..open_end]);
+            match inline {
+                true => output.push_str(&placeholder),
+                false => {
+                    output.push('\n');
+                    output.push_str(&placeholder);
+                    output.push('\n');
+                }
+            }
+            source_map.splice(md_attribute.start..close.start, spliced..output.len());
+            last = close.start;
+            search = close.end;
+
+            blocks.push(MarkdownBlock {
+                placeholder,
+                markdown,
+                indent,
+                inline,
+                position,
+            });
+        }
+        output.push_str(&md[last..]);
+
+        (output, blocks, diagnostics)
+    }
+}
+
+/// The range of the closing tag which matches an element opened just
+/// before `from` (allowing for elements of the same name nested inside)
+fn closing_tag(md: &str, tag: &str, from: usize) -> Option<std::ops::Range<usize>> {
+    let tags = Regex::new(&format!(r"(?i)<(/?){}(?:\s[^>]*)?>", regex::escape(tag))).ok()?;
+    let mut depth = 0;
+    for t in tags.captures_iter(&md[from..]) {
+        let all = t.get(0).unwrap();
+        match (&t[1], all.as_str().ends_with("/>")) {
+            ("/", _) if depth == 0 => return Some(from + all.start()..from + all.end()),
+            ("/", _) => depth -= 1,
+            (_, true) => (),
+            _ => depth += 1,
+        }
+    }
+
+    None
+}
+
+/// Removes the indentation which all (non-blank) lines share; a leading
+/// or trailing line break next to the tags is dropped too.
+fn dedent(content: &str) -> (String, String) {
+    let content = content.strip_prefix('\n').unwrap_or(content);
+    let indent = content
+        .lines()
+        .filter(|l| !l.trim().is_empty())
+        .map(|l| &l[..l.len() - l.trim_start().len()])
+        .min_by_key(|i| i.len())
+        .unwrap_or("")
+        .to_string();
+    let markdown = content
+        .split_inclusive('\n')
+        .map(|l| l.strip_prefix(indent.as_str()).unwrap_or(l.trim_start()))
+        .collect::<String>();
+
+    (markdown.trim_end_matches([' ', '\t']).to_string(), indent)
+}
+
+/// The byte ranges of the page which are inside fenced code blocks
+pub(crate) fn fenced_ranges(md: &str) -> Vec<std::ops::Range<usize>> {
+    let mut ranges = vec![];
+    let mut fence: Option<(char, usize, usize)> = None;
+    let mut offset = 0;
+
+    for line in md.split_inclusive('\n') {
+        let start = offset;
+        offset += line.len();
+        if let Some((c, len)) = code_fence(line.trim_end()) {
+            fence = match fence {
+                None => Some((c, len, start)),
+                Some((fc, flen, from)) if fc == c && len >= flen => {
+                    ranges.push(from..offset);
+                    None
+                }
+                open => open,
+            };
+        }
+    }
+    if let Some((_, _, from)) = fence {
+        ranges.push(from..md.len());
+    }
+
+    ranges
+}
+
+/// The byte ranges of the page which are inside of inline code spans;
+/// a span can not start or end inside of a fenced code block.
+fn code_spans(md: &str, fenced: &[std::ops::Range<usize>]) -> Vec<std::ops::Range<usize>> {
+    let bytes = md.as_bytes();
+    let run = |from: usize| bytes[from..].iter().take_while(|b| **b == b'`').count();
+    let fence_at = |idx: usize| fenced.iter().find(|f| f.contains(&idx)).map(|f| f.end);
+    let mut spans = vec![];
+    let mut idx = 0;
+
+    while idx < bytes.len() {
+        if let Some(end) = fence_at(idx) {
+            idx = end;
+            continue;
+        }
+        if bytes[idx] != b'`' {
+            idx += 1;
+            continue;
+        }
+        let len = run(idx);
+        let mut end = idx + len;
+        let mut closed = None;
+        while end < bytes.len() && fence_at(end).is_none() {
+            if bytes[end] == b'`' {
+                let close = run(end);
+                if close == len {
+                    closed = Some(end + close);
+                    break;
+                }
+                end += close;
+            } else {
+                end += 1;
+            }
+        }
+        match closed {
+            Some(close) => {
+                spans.push(idx..close);
+                idx = close;
+            }
+            None => idx += len,
+        }
+    }
+
+    spans
+}
+
+/// The byte ranges of the page which are inside of code (fenced code
+/// blocks or inline code spans)
+pub(crate) fn code_ranges(md: &str) -> Vec<std::ops::Range<usize>> {
+    let mut ranges = fenced_ranges(md);
+    ranges.extend(code_spans(md, &ranges));
+
+    ranges
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{html::HtmlContent, markdown::MarkdownContentRaw};
+
+    fn html(md: &str, config: &Config) -> HtmlContent {
+        let (md, _) = MarkdownContentRaw::new(md).parse(config).unwrap();
+        HtmlContent::new(&md, config)
+    }
+
+    #[test]
+    fn md_attribute_content_parsed_as_markdown() {
+        let html = html(
+            "This is markdown content.\n\n<table><tr>\n<td md>\n    left _hand_ content\n</td>\n<td>\n    right _hand_ content\n</td>\n</tr></table>\n",
+            &Config::default(),
+        );
+
+        assert_eq!(
+            html.content(),
+            "<p>This is markdown content.</p>\n<table><tr>\n<td>\n    <p>left <em>hand</em> content</p>\n</td>\n<td>\n    right _hand_ content\n</td>\n</tr></table>"
+        );
+    }
+
+    #[test]
+    fn md_attribute_nested_and_inline() {
+        let html = html(
+            "<div class=\"outer\" md>\n  ## Title\n\n  <div md data-x='1'>\n    - **a**\n  </div>\n</div>\n\n<span md>*inline*</span> text",
+            &Config::default(),
+        );
+
+        assert_eq!(
+            html.content(),
+            [
+                "<div class=\"outer\">\n",
+                "  <h2 id=\"title\">Title</h2>\n",
+                "  <div data-x='1'>\n",
+                "    <ul>\n",
+                "    <li><strong>a</strong></li>\n",
+                "    </ul>\n",
+                "  </div>\n",
+                "</div>\n",
+                "<p><span><em>inline</em></span> text</p>\n",
+            ]
+            .concat()
+        );
+        assert!(html.diagnostics.is_empty());
+    }
+
+    #[test]
+    fn md_attribute_in_inline_code_is_preserved() {
+        let html = html(
+            "Write `<span md>*x*</span>` to get <span md>*x*</span>\n",
+            &Config::default(),
+        );
+
+        assert_eq!(
+            html.content(),
+            "<p>Write <code>&lt;span md&gt;*x*&lt;/span&gt;</code> to get <span><em>x</em></span></p>\n"
+        );
+    }
+
+    #[test]
+    fn md_attribute_headings_have_unique_ids() {
+        let html = html(
+            "## Setup\n\n<div md>\n\n## Setup\n\n</div>\n\n<div md>\n\n## Setup\n\n</div>\n",
+            &Config::default(),
+        );
+
+        assert!(html.content().contains("<h2 id=\"setup\">Setup</h2>"));
+        assert!(html.content().contains("<h2 id=\"setup-1\">Setup</h2>"));
+        assert!(html.content().contains("<h2 id=\"setup-2\">Setup</h2>"));
+    }
+
+    #[test]
+    fn md_attribute_code_is_preserved() {
+        let md = "```html\n<div md>\n*no*\n</div>\n```\n\n<div md>\n```\n  x\n```\n</div>\n";
+        let html = html(md, &Config::default());
+
+        // the `md` attribute and the markdown inside the fence are left as is
+        assert!(html.content().contains(
+            "<span class=\"entity name tag block any html\">div</span> <span class=\"entity other attribute-name html\">md</span>"
+        ));
+        assert!(html.content().contains(
+            "<span class=\"line line-2 even\"><span class=\"text html basic\">*no*</span></span>\n"
+        ));
+        assert!(html.content().ends_with(
+            "<div>\n<pre><code><span class=\"line line-1 odd\"><span class=\"text plain\">  x</span></span>\n</code></pre>\n</div>"
+        ));
+    }
+
+    #[test]
+    fn md_attribute_problems_are_reported() {
+        let (output, blocks, diagnostics) = MarkdownElements::new(&Config::default())
+            .extract("<div md>\nnever closed\n\n<br md/>", &mut SourceMap::new());
+
+        assert_eq!(output, "<div md>\nnever closed\n\n<br md/>");
+        assert!(blocks.is_empty());
+        assert_eq!(diagnostics.len(), 2);
+        assert!(diagnostics[0].message.contains("never closed"));
+        assert_eq!(diagnostics[1].position.line, 4);
+    }
+
+    #[test]
+    fn md_attribute_can_be_turned_off() {
+        let mut config = Config::default();
+        config.features.markdown.md_attribute = false;
+        let (output, blocks, _) =
+            MarkdownElements::new(&config).extract("<div md>\n*a*\n</div>", &mut SourceMap::new());
+
+        assert_eq!(output, "<div md>\n*a*\n</div>");
+        assert!(blocks.is_empty());
+    }
+}
@@ -1,15 +1,52 @@
-use pulldown_cmark::{html::push_html, Options as ParserOptions, Parser};
+use std::collections::HashSet;
+
+use pulldown_cmark::{html::push_html, Event, Options as ParserOptions, Parser, Tag};
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
 use crate::{
     config::Config,
     errors::{md_err::MarkdownError, parser_err::ParserError},
-    pipeline::stages::Pipeline,
+    pipeline::{stages::d_parse_html::ParseHtml, Pipeline},
+};
+
+use self::{
+    code_block::CodeBlocks,
+    code_import::{CodeImport, CodeImports},
+    directive::{DirectiveContext, Directives},
+    emoji::{Emojis, MergeText},
+    heading::HeadingIds,
+    icons::{IconRef, Icons},
+    image::{ImageRef, Images},
+    include::{Include, Includes},
+    link::{LinkRef, RouterLinks},
+    list::CollapsibleLists,
+    md_attribute::MarkdownElements,
+    slots::{SlotContent, Slots},
+    tabs::TabRoute,
 };
 
-use super::markdown::{MarkdownContent, MarkdownContentRaw};
+use super::{
+    diagnostic::Diagnostic,
+    frontmatter::Frontmatter,
+    markdown::{MarkdownContent, MarkdownContentRaw},
+};
 
+pub mod code_block;
+pub mod code_import;
+pub mod columns;
+pub mod directive;
+pub mod emoji;
+pub mod heading;
+pub mod icons;
+pub mod image;
+pub mod include;
+pub mod link;
+pub mod list;
+pub mod md_attribute;
+pub mod responsive_image;
+pub mod slots;
+pub mod tabs;
 mod traits;
 
 #[derive(Error, Debug)]
@@ -18,9 +55,9 @@ pub enum HtmlError {
     FailedToConvertStr(#[from] MarkdownError),
 }
 
-fn get_parser_options(ctx: &Pipeline<ParseHtml>) -> ParserOptions {
+pub(crate) fn get_parser_options(config: &Config) -> ParserOptions {
     let mut options = ParserOptions::empty();
-    let config = &ctx.config.features.markdown;
+    let config = &config.features.markdown;
     if config.footnotes {
         options.insert(ParserOptions::ENABLE_FOOTNOTES);
     }
@@ -43,8 +80,226 @@ fn get_parser_options(ctx: &Pipeline<ParseHtml>) -> ParserOptions {
     options
 }
 
-fn parse_html(md: &MarkdownContent, config: &Config) -> (String, i32) {
-    todo!();
+/// The HTML rendered from a section of markdown
+struct Rendered {
+    html: String,
+    max_nesting: i32,
+    images: Vec<ImageRef>,
+    icons: Vec<IconRef>,
+    links: Vec<LinkRef>,
+    code_imports: Vec<CodeImport>,
+    code_languages: Vec<String>,
+    includes: Vec<Include>,
+    slots: Vec<SlotContent>,
+    tab_routes: Vec<TabRoute>,
+    heading_ids: Vec<String>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+/// Renders a section of a page; `ids` holds the heading ids already used
+/// elsewhere on the page so that every heading's id stays unique
+fn render(
+    md: &str,
+    file: Option<&str>,
+    config: &Config,
+    directives: &Directives,
+    ids: &mut HeadingIds,
+) -> Rendered {
+    let mut ctx = DirectiveContext::default();
+    let transcluded = Includes::new(config).expand(md, file);
+    let mut includes = transcluded.includes;
+    let mut diagnostics = transcluded.diagnostics;
+    let mut source_map = transcluded.source_map;
+    source_map.add_layer();
+    let (content, mut code_imports, mut import_diagnostics) =
+        CodeImports::new(config, file).expand(&transcluded.content, &mut source_map);
+    let included_imports = transcluded.code_imports.len();
+    code_imports.splice(0..0, transcluded.code_imports);
+    // everything from here on is found in the content with the includes
+    // and code imports spliced in
+    let spliced_from = diagnostics.len();
+    diagnostics.append(&mut import_diagnostics);
+    let content = directives.expand(&content, &mut ctx);
+    // ... and from here on in the content with the `md` elements pulled out
+    let spliced_map = source_map.clone();
+    source_map.add_layer();
+    let (content, blocks, mut md_diagnostics) =
+        MarkdownElements::new(config).extract(&content, &mut source_map);
+    diagnostics.append(&mut md_diagnostics);
+    diagnostics.append(&mut ctx.diagnostics);
+    let extracted_from = diagnostics.len();
+    let (content, mut icons, mut icon_diagnostics) = Icons::new(config).expand(&content);
+    diagnostics.append(&mut icon_diagnostics);
+    let options = get_parser_options(config);
+    let mut heading_ids = ids.ids_for(&content, options);
+    let mut headings = heading_ids.iter();
+    let parser = MergeText::new(Parser::new_ext(&content, options).into_offset_iter());
+    let mut code_blocks = CodeBlocks::new(config);
+    let mut emojis = Emojis::new(config);
+    let mut lists = CollapsibleLists::new(&content, options, config);
+    let mut router_links = RouterLinks::new(config);
+    let mut images = Images::new(config, file);
+    let mut max_nesting = 0;
+    let mut level = 0;
+    let parser = parser.filter_map(|(event, range)| {
+        let event = code_blocks.handle(event)?;
+        match &event {
+            Event::Start(_) => {
+                level += 1;
+                max_nesting = std::cmp::max(max_nesting, level);
+            }
+            Event::End(_) => level -= 1,
+            _ => (),
+        }
+
+        let event = match event {
+            Event::Start(Tag::Heading(heading, _, classes)) => Event::Start(Tag::Heading(
+                heading,
+                headings.next().map(|id| id.as_str()),
+                classes,
+            )),
+            _ => event,
+        };
+
+        images
+            .handle(lists.handle(emojis.handle(event)), range.clone(), &content)
+            .map(|event| router_links.handle(event, range, &content))
+    });
+
+    let mut html = String::new();
+    push_html(&mut html, parser);
+    let (mut images, image_diagnostics) = images.finish();
+    diagnostics.extend(image_diagnostics);
+    let mut links = router_links.finish();
+    let mut code_languages = code_blocks.finish();
+
+    // positions are reported against the section as it was written
+    let to_source = |position| source_map.position(md, position);
+    let from_spliced = |position| spliced_map.position(md, position);
+    for diagnostic in diagnostics[spliced_from..extracted_from].iter_mut() {
+        diagnostic.position = from_spliced(diagnostic.position);
+    }
+    for diagnostic in diagnostics[extracted_from..].iter_mut() {
+        diagnostic.position = to_source(diagnostic.position);
+    }
+    for import in code_imports[included_imports..].iter_mut() {
+        import.position = from_spliced(import.position);
+    }
+    for image in images.iter_mut() {
+        image.position = to_source(image.position);
+    }
+    for icon in icons.iter_mut() {
+        icon.position = to_source(icon.position);
+    }
+    for link in links.iter_mut() {
+        link.position = to_source(link.position);
+    }
+    for slot in ctx.slots.iter_mut() {
+        slot.position = from_spliced(slot.position);
+    }
+    for route in ctx.tab_routes.iter_mut() {
+        route.position = from_spliced(route.position);
+    }
+
+    // the content of elements with an `md` attribute is rendered on its own
+    for block in blocks {
+        let mut inner = render(&block.markdown, file, config, directives, ids);
+        html = html.replacen(&block.placeholder, &block.html(&inner.html), 1);
+        max_nesting = std::cmp::max(max_nesting, inner.max_nesting);
+        images.append(&mut inner.images);
+        icons.append(&mut inner.icons);
+        links.append(&mut inner.links);
+        code_imports.append(&mut inner.code_imports);
+        code_languages.append(&mut inner.code_languages);
+        includes.append(&mut inner.includes);
+        ctx.slots.append(&mut inner.slots);
+        ctx.tab_routes.append(&mut inner.tab_routes);
+        heading_ids.append(&mut inner.heading_ids);
+        diagnostics.append(&mut inner.diagnostics);
+    }
+
+    Rendered {
+        html,
+        max_nesting,
+        images,
+        icons,
+        links,
+        code_imports,
+        code_languages,
+        includes,
+        slots: ctx.slots,
+        tab_routes: ctx.tab_routes,
+        heading_ids,
+        diagnostics,
+    }
+}
+
+fn parse_html(
+    md: &MarkdownContent,
+    file: Option<&str>,
+    config: &Config,
+    directives: &Directives,
+) -> HtmlContent {
+    let slots = Slots::new(config);
+    let mut ids = HeadingIds::new();
+    let mut rendered = render(&md.content(), file, config, directives, &mut ids);
+    let mut images = rendered.images;
+    let mut icons = rendered.icons;
+    let mut links = rendered.links;
+    let mut code_imports = rendered.code_imports;
+    let mut code_languages = rendered.code_languages;
+    let mut includes = rendered.includes;
+    let mut slot_content = rendered.slots;
+    let mut tab_routes = rendered.tab_routes;
+    let mut heading_ids = rendered.heading_ids;
+    let mut diagnostics = rendered.diagnostics;
+
+    for slot in slot_content.iter_mut() {
+        let mut section = render(&slot.markdown, file, config, directives, &mut ids);
+        for nested in section.slots {
+            diagnostics.push(Diagnostic::warning(
+                &format!(
+                    "the slot \"{}\" is inside of the slot \"{}\" and is ignored",
+                    nested.name, slot.name
+                ),
+                nested.position,
+            ));
+        }
+        slot.html = section.html;
+        images.append(&mut section.images);
+        icons.append(&mut section.icons);
+        links.append(&mut section.links);
+        code_imports.append(&mut section.code_imports);
+        code_languages.append(&mut section.code_languages);
+        includes.append(&mut section.includes);
+        tab_routes.append(&mut section.tab_routes);
+        heading_ids.append(&mut section.heading_ids);
+        diagnostics.append(&mut section.diagnostics);
+        if Slots::inline_templates(config) {
+            rendered.html.push_str(&slots.template(slot));
+        }
+    }
+    let hash_initial = dm_utils::hash(&rendered.html, None);
+    let mut used = HashSet::new();
+    icons.retain(|i| used.insert((i.collection.clone(), i.name.clone())));
+    let mut used = HashSet::new();
+    code_languages.retain(|l| used.insert(l.clone()));
+
+    HtmlContent {
+        html: rendered.html,
+        max_nesting: rendered.max_nesting,
+        hash_initial,
+        images,
+        icons,
+        links,
+        code_imports,
+        code_languages,
+        includes,
+        slots: slot_content,
+        tab_routes,
+        heading_ids,
+        diagnostics,
+    }
 }
 
 /// a string which represents HTML content
@@ -53,37 +308,41 @@ pub struct HtmlContent {
     pub html: String,
     pub max_nesting: i32,
     pub hash_initial: u64,
+    /// the images found on the page
+    pub images: Vec<ImageRef>,
+    /// the Iconify icons used on the page (each listed once)
+    pub icons: Vec<IconRef>,
+    /// the links on the page; this includes the links brought in by
+    /// includes and directives or found in `md` elements
+    pub links: Vec<LinkRef>,
+    /// the code snippets imported from other files
+    pub code_imports: Vec<CodeImport>,
+    /// the languages of the page's fenced code blocks (each listed once)
+    pub code_languages: Vec<String>,
+    /// the markdown documents included in the page
+    pub includes: Vec<Include>,
+    /// the sections of the page which target a slot in the layout
+    pub slots: Vec<SlotContent>,
+    /// the tabs which are lazily loaded sub-routes of the page
+    pub tab_routes: Vec<TabRoute>,
+    /// the ids of every heading on the page; this includes the headings
+    /// brought in by includes and directives or found in `md` elements
+    pub heading_ids: Vec<String>,
+    /// problems found while converting the page
+    pub diagnostics: Vec<Diagnostic>,
 }
 
 /// Use the context provided by a Pipeline<ParseHtml> to
 /// parse the HTML and produce a `HtmlContent` structure.
-impl TryFrom<&Pipeline<ParseHtml>> for HtmlContent {
+impl TryFrom<&Pipeline<'_, ParseHtml>> for HtmlContent {
     type Error = ParserError;
     /// Generate HTML from the available pipeline content
-    fn try_from(ctx: &Pipeline<ParseHtml>) -> Result<Self, ParserError> {
-        let parser_options = get_parser_options(ctx);
-        let md = ctx.markdown.content();
-        let parser = Parser::new_ext(&md, parser_options);
-        let mut max_nesting = 0;
-        let mut level = 0;
-        // let parser = parser.map(|event| match event {
-        //     Event::Start(_) => {
-        //         level += 1;
-        //         max_nesting = std::cmp::max(max_nesting, level);
-        //     }
-        //     Event::End(_) => level -= 1,
-        //     _ => (),
-        // });
-
-        let mut html = String::new();
-        push_html(&mut html, parser);
-        let hash_initial = dm_utils::hash(&html, None);
-
-        Ok(HtmlContent {
-            html,
-            max_nesting,
-            hash_initial,
-        })
+    fn try_from(ctx: &Pipeline<'_, ParseHtml>) -> Result<Self, ParserError> {
+        Ok(HtmlContent::for_page(
+            &ctx.markdown,
+            Some(&ctx.id),
+            &ctx.config,
+        ))
     }
 }
 
@@ -102,37 +361,73 @@ impl TryFrom<&str> for HtmlContent {
 
 impl HtmlContent {
     pub fn new(md: &MarkdownContent, config: &Config) -> Self {
-        let (html, max_nesting) = parse_html(md, config);
-        let hash_initial = dm_utils::hash(&html, None);
+        parse_html(md, None, config, &Directives::new(config, None))
+    }
 
-        HtmlContent {
-            html,
-            max_nesting,
-            hash_initial,
-        }
+    /// Converts the markdown found in `file`; this allows relative
+    /// references (such as images) to be resolved against the page
+    pub fn for_page(md: &MarkdownContent, file: Option<&str>, config: &Config) -> Self {
+        parse_html(md, file, config, &Directives::new(config, file))
+    }
+
+    /// Converts the markdown using a registry which can include
+    /// directives beyond the built-in ones
+    pub fn with_directives(
+        md: &MarkdownContent,
+        file: Option<&str>,
+        config: &Config,
+        directives: &Directives,
+    ) -> Self {
+        parse_html(md, file, config, directives)
     }
 
     pub fn content(&self) -> String {
         self.html.to_string()
     }
+
+    /// The files which the page's content was built from; the page
+    /// needs to be rebuilt when any of them change
+    pub fn dependencies(&self) -> Vec<String> {
+        let mut dependencies: Vec<String> = vec![];
+        let included = self.includes.iter().map(|i| &i.file);
+        for file in included.chain(self.code_imports.iter().map(|i| &i.file)) {
+            if !dependencies.contains(file) {
+                dependencies.push(file.clone());
+            }
+        }
+
+        dependencies
+    }
+
+    /// The frontmatter of the included documents which asked for it to
+    /// be merged into the page; where documents set the same property
+    /// the one included first wins.
+    pub fn included_frontmatter(&self) -> Frontmatter {
+        let mut merged = Frontmatter::default();
+        for frontmatter in self.includes.iter().filter_map(|i| i.frontmatter.as_ref()) {
+            // every property came from valid frontmatter so can be set again
+            let _ = merged.merge(frontmatter);
+        }
+
+        merged
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{
-        config::Config, errors::parser_err::ParserError, models::markdown::MarkdownContent,
-    };
+    use crate::pipeline::stages::c_initial_darkmatter::InitialDarkmatter;
+
+    fn initial_darkmatter(md: &str) -> Pipeline<'static, InitialDarkmatter> {
+        let mut p = Pipeline::new("foobar", Config::default());
+        p.add_md_str(md);
+        p.next().unwrap().next().unwrap()
+    }
 
     /// an initialized Pipeline can use `try_from` to move to parsing stage
     #[test]
     fn html_parse_try_from() {
-        let init = Pipeline::new("foobar", Config::default())
-            .add_md_str("my document")
-            .next_stage()
-            .unwrap()
-            .next_stage()
-            .unwrap();
+        let init = initial_darkmatter("my document");
         let p: Result<Pipeline<ParseHtml>, ParserError> = Pipeline::try_from(init);
 
         assert!(p.is_ok());
@@ -140,12 +435,7 @@ mod tests {
 
     #[test]
     fn html_parse_into() {
-        let init = Pipeline::new("foobar", Config::default())
-            .add_md_str("my document")
-            .next_stage()
-            .unwrap()
-            .next_stage()
-            .unwrap();
+        let init = initial_darkmatter("my document");
         let p: Result<Pipeline<ParseHtml>, ParserError> = init.try_into();
 
         assert!(p.is_ok());
@@ -153,16 +443,11 @@ mod tests {
 
     #[test]
     fn html_parse_base_test() {
-        let p = Pipeline::new("foobar", Config::default())
-            .add_md_str("Hello world, this is a ~~complicated~~ *very simple* example.")
-            .next_stage()
-            .unwrap()
-            .next_stage()
-            .unwrap()
-            .next_stage()
-            .unwrap()
-            .parse_to_html()
-            .unwrap();
+        let mut p =
+            initial_darkmatter("Hello world, this is a ~~complicated~~ *very simple* example.")
+                .next_stage()
+                .unwrap();
+        p.parse_to_html().unwrap();
 
         let html = p.html;
 
@@ -175,19 +460,23 @@ mod tests {
         }
     }
 
-    fn html_max_nesting() {
-        let (markdown, frontmatter) =
-            MarkdownContent::from_file("test/fixtures/structures.md", &Config::default()).unwrap();
-        let ctx = BaseContext::new(
-            "test",
-            "test",
-            &frontmatter,
-            &markdown,
-            &None,
-            &Config::default(),
+    #[test]
+    fn html_headings_are_given_ids() {
+        let html = HtmlContent::try_from("# Hello World\n## Custom {#custom .big}").unwrap();
+
+        assert_eq!(
+            html.content(),
+            "<h1 id=\"hello-world\">Hello World</h1>\n<h2 id=\"custom\" class=\"big\">Custom</h2>\n"
         );
-        let html = HtmlContent::from(&ctx);
+    }
+
+    #[test]
+    fn html_max_nesting() {
+        let (markdown, _) =
+            MarkdownContent::from_file("test/fixtures/structured.md", &Config::default()).unwrap();
+        let html = HtmlContent::new(&markdown, &Config::default());
 
-        assert_eq!(html.max_nesting, 5);
+        // the list which is three levels deep (a `<ul>` and `<li>` each)
+        assert_eq!(html.max_nesting, 6);
     }
 }
@@ -0,0 +1,318 @@
+use std::{
+    fs,
+    io::Cursor,
+    path::{Path, PathBuf},
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use image::{imageops::FilterType, DynamicImage};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::features::image::{ImageConfig, PlaceholderKind, VariantFormat},
+    errors::image_err::ImageError,
+};
+
+use super::image::ImageFormat;
+
+/// The width of the tiny image which is inlined as a data URI
+const DATA_URI_WIDTH: u32 = 16;
+/// The size of the image which a blurhash is computed from
+const BLURHASH_SIZE: u32 = 32;
+
+/// A resized copy of an image
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ImageVariant {
+    /// the URL the variant is served on
+    pub url: String,
+    /// the file the variant was written to
+    pub file: String,
+    pub width: u32,
+    pub height: u32,
+    pub format: VariantFormat,
+}
+
+impl VariantFormat {
+    /// the variant format for an image's own format; only raster
+    /// formats which can be re-encoded have one
+    pub fn for_image(format: ImageFormat) -> Option<Self> {
+        match format {
+            ImageFormat::Png => Some(VariantFormat::Png),
+            ImageFormat::Jpeg => Some(VariantFormat::Jpeg),
+            ImageFormat::Webp => Some(VariantFormat::Webp),
+            ImageFormat::Gif | ImageFormat::Svg => None,
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            VariantFormat::Webp => "webp",
+            VariantFormat::Jpeg => "jpg",
+            VariantFormat::Png => "png",
+        }
+    }
+
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            VariantFormat::Webp => "image/webp",
+            VariantFormat::Jpeg => "image/jpeg",
+            VariantFormat::Png => "image/png",
+        }
+    }
+
+    fn codec(&self) -> image::ImageFormat {
+        match self {
+            VariantFormat::Webp => image::ImageFormat::WebP,
+            VariantFormat::Jpeg => image::ImageFormat::Jpeg,
+            VariantFormat::Png => image::ImageFormat::Png,
+        }
+    }
+}
+
+/// The variants and placeholder created for an image
+#[derive(Debug, Default)]
+pub struct ResponsiveImage {
+    pub variants: Vec<ImageVariant>,
+    pub placeholder: Option<String>,
+}
+
+/// The formats variants are created in; the image's own format is
+/// always first and the configured formats are only needed when a
+/// `<picture>` element can offer them to the browser.
+pub fn variant_formats(original: VariantFormat, config: &ImageConfig) -> Vec<VariantFormat> {
+    let mut formats = vec![original];
+    if config.use_picture {
+        for format in &config.formats {
+            if !formats.contains(format) {
+                formats.push(*format);
+            }
+        }
+    }
+
+    formats
+}
+
+/// The widths variants are created in; configured widths which are
+/// not smaller than the original are dropped (images are never
+/// upscaled) and the original width is always included.
+pub fn variant_widths(original: u32, config: &ImageConfig) -> Vec<u32> {
+    let mut widths: Vec<u32> = config
+        .widths
+        .iter()
+        .copied()
+        .filter(|w| *w < original)
+        .collect();
+    widths.push(original);
+    widths.sort_unstable();
+    widths.dedup();
+
+    widths
+}
+
+/// Creates the resized variants -- with fingerprinted file names -- and
+/// the placeholder for the image found in `file`. Variants which already
+/// exist are not created again as the fingerprint changes whenever the
+/// source image does.
+pub fn create_responsive_image(
+    file: &Path,
+    bytes: &[u8],
+    format: ImageFormat,
+    config: &ImageConfig,
+) -> Result<ResponsiveImage, ImageError> {
+    let name = file.to_string_lossy().to_string();
+    let original = match VariantFormat::for_image(format) {
+        Some(original) => original,
+        None => return Ok(ResponsiveImage::default()),
+    };
+    let image = image::load_from_memory(bytes).map_err(|e| ImageError::Codec(name.clone(), e))?;
+    let (width, height) = (image.width(), image.height());
+    let stem = file
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| String::from("image"));
+    let fingerprint = format!("{:016x}", dm_utils::hasher::hash_bytes(bytes));
+    let output_dir = PathBuf::from(&config.output_dir);
+
+    let mut variants = vec![];
+    for format in variant_formats(original, config) {
+        for w in variant_widths(width, config) {
+            let h = std::cmp::max(1, (height as u64 * w as u64 / width as u64) as u32);
+            let file_name = format!(
+                "{}-{}-{}.{}",
+                stem,
+                &fingerprint[..8],
+                w,
+                format.extension()
+            );
+            let target = output_dir.join(&file_name);
+
+            if !target.is_file() {
+                fs::create_dir_all(&output_dir)
+                    .map_err(|e| ImageError::Write(config.output_dir.clone(), e))?;
+                let resized = match w == width {
+                    true => image.clone(),
+                    false => image.resize_exact(w, h, FilterType::Lanczos3),
+                };
+                let resized = match format {
+                    VariantFormat::Jpeg => DynamicImage::ImageRgb8(resized.to_rgb8()),
+                    _ => DynamicImage::ImageRgba8(resized.to_rgba8()),
+                };
+                resized
+                    .save_with_format(&target, format.codec())
+                    .map_err(|e| ImageError::Codec(target.to_string_lossy().to_string(), e))?;
+            }
+
+            variants.push(ImageVariant {
+                url: format!("{}/{}", config.output_url.trim_end_matches('/'), file_name),
+                file: target.to_string_lossy().replace('\\', "/"),
+                width: w,
+                height: h,
+                format,
+            });
+        }
+    }
+
+    let placeholder = match config.placeholder {
+        PlaceholderKind::None => None,
+        PlaceholderKind::DataUri => Some(data_uri_placeholder(&image, &name)?),
+        PlaceholderKind::Blurhash => Some(blurhash_placeholder(&image, &name)?),
+    };
+
+    Ok(ResponsiveImage {
+        variants,
+        placeholder,
+    })
+}
+
+/// A tiny, blurred PNG of the image as a base64 data URI
+fn data_uri_placeholder(image: &DynamicImage, name: &str) -> Result<String, ImageError> {
+    let tiny = image.thumbnail(DATA_URI_WIDTH, DATA_URI_WIDTH).blur(1.0);
+    let mut png = Cursor::new(vec![]);
+    DynamicImage::ImageRgba8(tiny.to_rgba8())
+        .write_to(&mut png, image::ImageFormat::Png)
+        .map_err(|e| ImageError::Codec(name.to_string(), e))?;
+
+    Ok(format!(
+        "data:image/png;base64,{}",
+        STANDARD.encode(png.into_inner())
+    ))
+}
+
+fn blurhash_placeholder(image: &DynamicImage, name: &str) -> Result<String, ImageError> {
+    let small = image.thumbnail(BLURHASH_SIZE, BLURHASH_SIZE).to_rgba8();
+    blurhash::encode(4, 3, small.width(), small.height(), small.as_raw())
+        .map_err(|e| ImageError::Placeholder(name.to_string(), e.to_string()))
+}
+
+/// The `srcset` attribute's value for the variants in a given format
+pub fn srcset(variants: &[ImageVariant], format: VariantFormat) -> String {
+    variants
+        .iter()
+        .filter(|v| v.format == format)
+        .map(|v| format!("{} {}w", v.url, v.width))
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgba, RgbaImage};
+
+    #[test]
+    fn responsive_widths_never_upscale() {
+        let config = ImageConfig::default();
+        assert_eq!(variant_widths(1000, &config), vec![480, 960, 1000]);
+        assert_eq!(variant_widths(960, &config), vec![480, 960]);
+        assert_eq!(variant_widths(200, &config), vec![200]);
+    }
+
+    #[test]
+    fn responsive_extra_formats_only_with_picture() {
+        let mut config = ImageConfig::default();
+        assert_eq!(
+            variant_formats(VariantFormat::Png, &config),
+            vec![VariantFormat::Png, VariantFormat::Webp]
+        );
+        assert_eq!(
+            variant_formats(VariantFormat::Webp, &config),
+            vec![VariantFormat::Webp]
+        );
+        config.use_picture = false;
+        assert_eq!(
+            variant_formats(VariantFormat::Png, &config),
+            vec![VariantFormat::Png]
+        );
+    }
+
+    #[test]
+    fn responsive_variants_are_written_with_fingerprints() {
+        let dir = std::env::temp_dir().join(format!("dm-responsive-{}", std::process::id()));
+        let config = ImageConfig {
+            widths: vec![8, 16],
+            output_dir: dir.to_string_lossy().to_string(),
+            ..ImageConfig::default()
+        };
+        let mut png = Cursor::new(vec![]);
+        RgbaImage::from_pixel(20, 10, Rgba([200, 40, 40, 255]))
+            .write_to(&mut png, image::ImageFormat::Png)
+            .unwrap();
+        let png = png.into_inner();
+
+        let responsive =
+            create_responsive_image(Path::new("hero.png"), &png, ImageFormat::Png, &config)
+                .unwrap();
+        let files: Vec<bool> = responsive
+            .variants
+            .iter()
+            .map(|v| Path::new(&v.file).is_file())
+            .collect();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(responsive.variants.len(), 6);
+        assert!(files.iter().all(|f| *f));
+        let first = &responsive.variants[0];
+        assert!(first.url.starts_with("/_images/hero-"));
+        assert!(first.url.ends_with("-8.png"));
+        assert_eq!((first.width, first.height), (8, 4));
+        assert_eq!(
+            srcset(&responsive.variants, VariantFormat::Webp)
+                .matches("w, ")
+                .count(),
+            2
+        );
+        assert!(responsive
+            .placeholder
+            .unwrap()
+            .starts_with("data:image/png;base64,"));
+    }
+
+    #[test]
+    fn responsive_blurhash_placeholder() {
+        let dir = std::env::temp_dir().join(format!("dm-blurhash-{}", std::process::id()));
+        let config = ImageConfig {
+            widths: vec![],
+            formats: vec![],
+            output_dir: dir.to_string_lossy().to_string(),
+            placeholder: PlaceholderKind::Blurhash,
+            ..ImageConfig::default()
+        };
+        let mut png = Cursor::new(vec![]);
+        RgbaImage::from_pixel(4, 4, Rgba([0, 0, 255, 255]))
+            .write_to(&mut png, image::ImageFormat::Png)
+            .unwrap();
+
+        let responsive = create_responsive_image(
+            Path::new("blue.png"),
+            &png.into_inner(),
+            ImageFormat::Png,
+            &config,
+        )
+        .unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(responsive.variants.len(), 1);
+        assert!(!responsive.placeholder.unwrap().starts_with("data:"));
+    }
+}
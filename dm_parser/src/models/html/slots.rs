@@ -0,0 +1,293 @@
+use pulldown_cmark::escape::escape_html;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::{
+    config::{features::slots::SlotConfig, Config, OutputFormat},
+    models::{diagnostic::Diagnostic, position::SourcePosition},
+};
+
+use super::directive::{Directive, DirectiveContext, DirectiveKind, DirectiveNode, Directives};
+
+/// A section of a page which targets a named slot in its layout
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SlotContent {
+    pub name: String,
+    /// the props passed to the slot (always a JSON object)
+    pub props: Value,
+    /// the markdown for the slot
+    pub markdown: String,
+    /// the slot's content rendered as HTML
+    pub html: String,
+    /// where the slot was defined on the page
+    pub position: SourcePosition,
+}
+
+/// Pulls the sections which target a slot out of the body of a page:
+///
+/// ```md
+/// This is my document.
+/// :::slot sidebar { selected: "foobar" }
+/// but this goes in the sidebar
+/// :::
+/// ```
+///
+/// A slot's content runs until a closing `:::`, the next slot or the end
+/// of the page. Props are written in JSON5. The slot's markdown is kept
+/// as written so that it can be rendered on its own.
+#[derive(Debug, Clone)]
+pub struct Slots {
+    config: SlotConfig,
+    /// the slot's name and props
+    args: Regex,
+}
+
+impl Slots {
+    pub fn new(config: &Config) -> Self {
+        Slots {
+            config: config.features.slots.clone(),
+            args: Regex::new(r"^([A-Za-z][\w-]*)\s*(\{.*\})?$").unwrap(),
+        }
+    }
+
+    /// Adds the content for a slot; a slot which is targeted more than
+    /// once has its content appended
+    fn add_slot(
+        &self,
+        slots: &mut Vec<SlotContent>,
+        name: &str,
+        props: Value,
+        markdown: &str,
+        position: SourcePosition,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) {
+        match slots.iter().position(|s| s.name == name) {
+            Some(idx) => {
+                diagnostics.push(Diagnostic::info(
+                    &format!(
+                        "the slot \"{}\" was already targeted; its content will be appended",
+                        name
+                    ),
+                    position,
+                ));
+                if let (Value::Object(existing), Value::Object(props)) =
+                    (&mut slots[idx].props, props)
+                {
+                    existing.extend(props);
+                }
+                slots[idx].markdown.push_str(markdown);
+            }
+            None => slots.push(SlotContent {
+                name: name.to_string(),
+                props,
+                markdown: markdown.to_string(),
+                html: String::new(),
+                position,
+            }),
+        }
+    }
+
+    /// Parses the JSON5 props of a slot
+    fn props(
+        &self,
+        props: Option<&str>,
+        position: SourcePosition,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> Value {
+        let props = match props {
+            Some(props) => props,
+            None => return Value::Object(Map::new()),
+        };
+
+        match json5::from_str::<Value>(props) {
+            Ok(Value::Object(props)) => Value::Object(props),
+            Ok(_) => Value::Object(Map::new()),
+            Err(e) => {
+                diagnostics.push(Diagnostic::error(
+                    &format!("the slot props are not valid JSON5: {}", e),
+                    position,
+                ));
+                Value::Object(Map::new())
+            }
+        }
+    }
+
+    /// The `<template>` which sends a slot's content to the layout in
+    /// an SFC; props are bound to the element wrapping the content.
+    pub fn template(&self, slot: &SlotContent) -> String {
+        let mut html = String::from("<template #");
+        html.push_str(&slot.name);
+        html.push_str(">\n<div class=\"");
+        escape_html(&mut html, &self.config.class).unwrap();
+        html.push(' ');
+        escape_html(&mut html, &format!("{}-{}", self.config.class, slot.name)).unwrap();
+        html.push('"');
+        if matches!(&slot.props, Value::Object(props) if !props.is_empty()) {
+            html.push_str(" v-bind=\"");
+            escape_html(&mut html, &slot.props.to_string()).unwrap();
+            html.push('"');
+        }
+        html.push_str(">\n");
+        html.push_str(&slot.html);
+        html.push_str("</div>\n</template>\n");
+
+        html
+    }
+
+    /// Whether slots are sent into `<template>` elements as part of the
+    /// page's own HTML (rather than left for a layout to place)
+    pub fn inline_templates(config: &Config) -> bool {
+        config.output == OutputFormat::SFC
+    }
+}
+
+impl Directive for Slots {
+    fn name(&self) -> &str {
+        "slot"
+    }
+
+    fn kind(&self) -> DirectiveKind {
+        DirectiveKind::Container
+    }
+
+    fn matches(&self, name: &str, args: &str) -> bool {
+        name == self.name() && self.args.is_match(args)
+    }
+
+    fn nests(&self) -> bool {
+        false
+    }
+
+    fn render(
+        &self,
+        node: &DirectiveNode,
+        _directives: &Directives,
+        ctx: &mut DirectiveContext,
+    ) -> String {
+        let captures = self.args.captures(&node.args).unwrap();
+        let name = &captures[1];
+        let props = self.props(
+            captures.get(2).map(|p| p.as_str()),
+            node.position,
+            &mut ctx.diagnostics,
+        );
+        if !self.config.known_slots.iter().any(|s| s == name) {
+            ctx.diagnostics.push(Diagnostic::warning(
+                &format!(
+                    "the slot \"{}\" is not one of the known slots: {}",
+                    name,
+                    self.config.known_slots.join(", ")
+                ),
+                node.position,
+            ));
+        }
+        self.add_slot(
+            &mut ctx.slots,
+            name,
+            props,
+            &node.inner,
+            node.position,
+            &mut ctx.diagnostics,
+        );
+
+        String::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{html::HtmlContent, markdown::MarkdownContentRaw};
+
+    fn extract(md: &str, config: &Config) -> (String, Vec<SlotContent>, Vec<Diagnostic>) {
+        let mut ctx = DirectiveContext::default();
+        let body = Directives::new(config, None).expand(md, &mut ctx);
+
+        (body, ctx.slots, ctx.diagnostics)
+    }
+
+    fn html(md: &str, config: &Config) -> HtmlContent {
+        let (md, _) = MarkdownContentRaw::new(md).parse(config).unwrap();
+        HtmlContent::new(&md, config)
+    }
+
+    #[test]
+    fn slots_are_extracted_from_the_body() {
+        let md = "This is my document.\n:::slot sidebar { selected: \"foobar\", count: 2, }\nbut this goes in the **sidebar**\n:::\nback in the body\n";
+        let (body, slots, diagnostics) = extract(md, &Config::default());
+
+        assert_eq!(body, "This is my document.\nback in the body\n");
+        assert_eq!(slots.len(), 1);
+        assert_eq!(slots[0].name, "sidebar");
+        assert_eq!(slots[0].props["selected"], "foobar");
+        assert_eq!(slots[0].props["count"], 2);
+        assert_eq!(slots[0].markdown, "but this goes in the **sidebar**\n");
+        assert_eq!(slots[0].position.line, 2);
+        assert!(diagnostics.is_empty());
+    }
+
+    #[test]
+    fn slots_rendered_into_templates_for_sfc() {
+        let html = html(
+            "Body\n\n:::slot sidebar { selected: 'foobar' }\n## Side\n",
+            &Config::default(),
+        );
+
+        assert_eq!(
+            html.content(),
+            "<p>Body</p>\n<template #sidebar>\n<div class=\"slot slot-sidebar\" v-bind=\"{&quot;selected&quot;:&quot;foobar&quot;}\">\n<h2 id=\"side\">Side</h2>\n</div>\n</template>\n"
+        );
+        assert_eq!(html.slots[0].html, "<h2 id=\"side\">Side</h2>\n");
+    }
+
+    #[test]
+    fn slots_left_for_layouts_with_html_output() {
+        let config = Config {
+            output: OutputFormat::HTML,
+            ..Config::default()
+        };
+        let html = html("Body\n:::slot footer\nthe end", &config);
+
+        assert_eq!(html.content(), "<p>Body</p>\n");
+        assert_eq!(html.slots[0].name, "footer");
+        assert_eq!(html.slots[0].html, "<p>the end</p>\n");
+    }
+
+    #[test]
+    fn slots_close_after_nested_containers() {
+        let md = ":::slot aside\n::: 2 columns\na\n::: next\nb\n:::\nstill aside\n:::\nbody";
+        let (body, slots, _) = extract(md, &Config::default());
+
+        assert_eq!(body, "body");
+        assert!(slots[0].markdown.ends_with(":::\nstill aside\n"));
+    }
+
+    #[test]
+    fn slots_diagnostics() {
+        let md = ":::slot nav\nx\n:::slot sidebar { bad: }\ny\n:::slot sidebar\nz\n";
+        let (_, slots, diagnostics) = extract(md, &Config::default());
+
+        assert_eq!(slots.len(), 2);
+        assert_eq!(slots[1].markdown, "y\nz\n");
+        assert_eq!(diagnostics.len(), 3);
+        assert!(diagnostics[0]
+            .message
+            .contains("\"nav\" is not one of the known slots"));
+        assert_eq!(diagnostics[1].position.line, 3);
+        assert!(diagnostics[1].message.contains("JSON5"));
+        assert_eq!(diagnostics[2].position.line, 5);
+    }
+
+    #[test]
+    fn slots_can_be_turned_off() {
+        let mut config = Config::default();
+        config.features.enable_slots = false;
+        let md = "a\n:::slot sidebar\nb";
+        let (body, slots, _) = extract(md, &config);
+
+        assert_eq!(body, md);
+        assert!(slots.is_empty());
+    }
+}
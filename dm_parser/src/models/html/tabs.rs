@@ -0,0 +1,596 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use pulldown_cmark::escape::{escape_href, escape_html};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::{features::tabs::theme_pair, Config, OutputFormat},
+    models::{diagnostic::Diagnostic, markdown::MarkdownContentRaw, position::SourcePosition},
+};
+
+use super::{
+    directive::{Directive, DirectiveContext, DirectiveKind, DirectiveNode, Directives, Node},
+    include::canonical,
+    link::clean_route,
+};
+
+/// A tab whose content is only brought in at run-time -- when the tab
+/// is selected -- by treating it as a sub-route of the page
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct TabRoute {
+    /// the tab's label
+    pub name: String,
+    /// the route as written on the page (e.g., `./sub-page3.md`)
+    pub route: String,
+    /// the path the sub-route is registered under
+    pub path: String,
+    /// the markdown file which provides the sub-route's component
+    pub file: String,
+    pub position: SourcePosition,
+}
+
+/// Where the content of a tab comes from
+#[derive(Debug, PartialEq, Eq)]
+enum TabSource {
+    /// the markdown which follows the `::tab` line
+    Content,
+    /// another markdown file which is rendered in place at build time
+    Inline(String),
+    /// another markdown file which is a lazily loaded sub-route
+    Route(String),
+}
+
+#[derive(Debug)]
+struct Tab {
+    name: String,
+    source: TabSource,
+    markdown: String,
+}
+
+/// A tab menu which is being rendered
+#[derive(Debug)]
+struct Menu {
+    id: String,
+    theme: (String, String),
+    tabs: Vec<Tab>,
+    position: SourcePosition,
+}
+
+/// Converts tab menus into accessible HTML (a `tablist` of `tab` buttons
+/// followed by a `tabpanel` for each tab):
+///
+/// ```md
+/// ::tabs theme=light,dark
+/// ::tab "One"
+/// This is the first tab which we called "One"
+///
+/// ::tab name="Two", inline="./sub-page2.md"
+/// ::tab "Three", route="./sub-page3.md"
+/// ::end-tabs
+/// ```
+///
+/// The first tab is selected and the other panels start out `hidden`;
+/// switching between tabs is left to the site's scripts. Route tabs are
+/// only lazily loaded when the output is an SFC; otherwise they are
+/// inlined like any other tab.
+#[derive(Debug)]
+pub struct Tabs<'a> {
+    config: &'a Config,
+    /// the page's file (in its canonical form) when it is known
+    page: Option<PathBuf>,
+    /// the directory which the page's inline and route tabs are resolved
+    /// against
+    page_dir: PathBuf,
+}
+
+impl<'a> Tabs<'a> {
+    pub fn new(config: &'a Config, file: Option<&str>) -> Self {
+        let page_dir = file
+            .and_then(|f| Path::new(f).parent())
+            .map(|p| p.to_path_buf())
+            .unwrap_or_default();
+        let page = file.map(|f| canonical(Path::new(f)));
+
+        Tabs {
+            config,
+            page,
+            page_dir,
+        }
+    }
+
+    /// The file a tab's `inline` or `route` refers to; paths are relative
+    /// to the file the tab is in (which is not the page when the tab
+    /// itself came from an inline tab)
+    fn resolve(&self, src: &str, ctx: &DirectiveContext) -> PathBuf {
+        match ctx.files.last().and_then(|f| f.parent()) {
+            Some(dir) => dir.join(src),
+            None => self.page_dir.join(src),
+        }
+    }
+
+    /// Reads the properties of a `::tab` line along with the content of
+    /// tabs which come from another file
+    fn tab(
+        &self,
+        node: &DirectiveNode,
+        index: usize,
+        directives: &Directives,
+        ctx: &mut DirectiveContext,
+    ) -> Tab {
+        let position = node.position;
+        let attributes = node.attributes();
+        let mut name = attributes.label;
+        let mut source = TabSource::Content;
+        for (key, value) in attributes.values {
+            match key.as_str() {
+                "name" => name = Some(value),
+                "inline" => source = TabSource::Inline(value),
+                "route" => source = TabSource::Route(value),
+                _ => ctx
+                    .diagnostics
+                    .push(unknown_property(&key, "::tab", position)),
+            }
+        }
+        let name = name.unwrap_or_else(|| format!("Tab {}", index + 1));
+
+        let mut markdown = String::new();
+        match &source {
+            TabSource::Inline(src) => markdown = self.load(src, position, directives, ctx),
+            TabSource::Route(src) if self.config.output == OutputFormat::SFC => {
+                let file = self.resolve(src, ctx);
+                if !file.is_file() {
+                    ctx.diagnostics.push(Diagnostic::warning(
+                        &format!(
+                            "the route for the tab \"{}\" was not found at {}",
+                            name,
+                            file.display()
+                        ),
+                        position,
+                    ));
+                }
+                ctx.tab_routes.push(TabRoute {
+                    name: name.clone(),
+                    route: src.clone(),
+                    path: clean_route(src, &self.config.features.links),
+                    file: file.to_string_lossy().replace('\\', "/"),
+                    position,
+                });
+            }
+            TabSource::Route(src) => markdown = self.load(src, position, directives, ctx),
+            TabSource::Content => (),
+        }
+
+        Tab {
+            name,
+            source,
+            markdown,
+        }
+    }
+
+    /// Reads the markdown for a tab from another file (without its
+    /// frontmatter); directives in the file are expanded too unless the
+    /// file is already being expanded (or is the page itself) in which
+    /// case an error is reported rather than looping forever.
+    fn load(
+        &self,
+        src: &str,
+        position: SourcePosition,
+        directives: &Directives,
+        ctx: &mut DirectiveContext,
+    ) -> String {
+        let file = self.resolve(src, ctx);
+        let canonical = canonical(&file);
+        if self.page.as_ref() == Some(&canonical) || ctx.files.contains(&canonical) {
+            let mut chain: Vec<String> = self
+                .page
+                .iter()
+                .chain(ctx.files.iter())
+                .map(|f| f.to_string_lossy().to_string())
+                .collect();
+            chain.push(file.to_string_lossy().to_string());
+            ctx.diagnostics.push(Diagnostic::error(
+                &format!(
+                    "loading the tab content \"{}\" would create a cycle: {}",
+                    src,
+                    chain.join(" → ")
+                ),
+                position,
+            ));
+            return String::new();
+        }
+
+        match fs::read_to_string(&file) {
+            Ok(raw) => {
+                let md = match MarkdownContentRaw::new(&raw).parse(self.config) {
+                    Ok((md, _)) => md.content(),
+                    Err(_) => raw,
+                };
+                ctx.files.push(canonical);
+                let expanded = directives.expand(&md, ctx);
+                ctx.files.pop();
+
+                expanded
+            }
+            Err(e) => {
+                ctx.diagnostics.push(Diagnostic::error(
+                    &format!(
+                        "unable to read the tab content \"{}\" from {}: {}",
+                        src,
+                        file.display(),
+                        e
+                    ),
+                    position,
+                ));
+                String::new()
+            }
+        }
+    }
+
+    fn menu(&self, menu: &Menu, diagnostics: &mut Vec<Diagnostic>) -> String {
+        let config = &self.config.features.tabs;
+        if menu.tabs.is_empty() {
+            diagnostics.push(Diagnostic::warning(
+                "a ::tabs menu has no tabs",
+                menu.position,
+            ));
+        }
+
+        let mut html = String::from("\n<div class=\"");
+        escape_html(&mut html, &config.class).unwrap();
+        html.push_str("\" data-theme-light=\"");
+        escape_html(&mut html, &menu.theme.0).unwrap();
+        html.push_str("\" data-theme-dark=\"");
+        escape_html(&mut html, &menu.theme.1).unwrap();
+        html.push_str("\">\n<div class=\"");
+        escape_html(&mut html, &format!("{}-list", config.class)).unwrap();
+        html.push_str("\" role=\"tablist\">\n");
+
+        for (idx, tab) in menu.tabs.iter().enumerate() {
+            let selected = idx == 0;
+            html.push_str("<button type=\"button\" class=\"");
+            escape_html(&mut html, &config.tab_class).unwrap();
+            html.push_str("\" role=\"tab\" id=\"");
+            escape_html(&mut html, &tab_id(menu, idx)).unwrap();
+            html.push_str("\" aria-controls=\"");
+            escape_html(&mut html, &panel_id(menu, idx)).unwrap();
+            html.push_str(match selected {
+                true => "\" aria-selected=\"true\" tabindex=\"0\"",
+                false => "\" aria-selected=\"false\" tabindex=\"-1\"",
+            });
+            if let (TabSource::Route(src), OutputFormat::SFC) = (&tab.source, &self.config.output) {
+                html.push_str(" data-route=\"");
+                escape_href(&mut html, &clean_route(src, &self.config.features.links)).unwrap();
+                html.push('"');
+            }
+            html.push('>');
+            escape_html(&mut html, &tab.name).unwrap();
+            html.push_str("</button>\n");
+        }
+        html.push_str("</div>\n");
+
+        for (idx, tab) in menu.tabs.iter().enumerate() {
+            html.push_str("<div class=\"");
+            escape_html(&mut html, &config.panel_class).unwrap();
+            html.push_str("\" role=\"tabpanel\" id=\"");
+            escape_html(&mut html, &panel_id(menu, idx)).unwrap();
+            html.push_str("\" aria-labelledby=\"");
+            escape_html(&mut html, &tab_id(menu, idx)).unwrap();
+            html.push_str("\" tabindex=\"0\"");
+            if idx > 0 {
+                html.push_str(" hidden");
+            }
+            html.push_str(">\n");
+            match (&tab.source, &self.config.output) {
+                (TabSource::Route(_), OutputFormat::SFC) => html.push_str("<router-view />\n"),
+                _ => {
+                    html.push('\n');
+                    html.push_str(&tab.markdown);
+                    if !tab.markdown.ends_with('\n') {
+                        html.push('\n');
+                    }
+                    html.push('\n');
+                }
+            }
+            html.push_str("</div>\n");
+        }
+        html.push_str("</div>\n\n");
+
+        html
+    }
+}
+
+/// The warning for content in a menu which does not belong to a tab
+/// that can hold it (blank lines are always ignored quietly)
+fn ignored(tab: Option<&Tab>, node: &Node) -> Option<Diagnostic> {
+    let position = match node {
+        Node::Line(line, _) if line.trim().is_empty() => return None,
+        Node::Line(_, position) => *position,
+        Node::Separator { position, .. } => *position,
+        Node::Directive(directive) => directive.position,
+    };
+
+    Some(match tab {
+        Some(tab) => Diagnostic::warning(
+            &format!(
+                "the tab \"{}\" gets its content from another file so this content is ignored",
+                tab.name
+            ),
+            position,
+        ),
+        None => Diagnostic::warning(
+            "content must follow a ::tab line; this content is ignored",
+            position,
+        ),
+    })
+}
+
+fn tab_id(menu: &Menu, idx: usize) -> String {
+    format!("{}-tab-{}", menu.id, idx + 1)
+}
+
+fn panel_id(menu: &Menu, idx: usize) -> String {
+    format!("{}-panel-{}", menu.id, idx + 1)
+}
+
+fn unknown_property(key: &str, directive: &str, position: SourcePosition) -> Diagnostic {
+    Diagnostic::warning(
+        &format!("\"{}\" is not a known property of {}", key, directive),
+        position,
+    )
+}
+
+impl<'a> Directive for Tabs<'a> {
+    fn name(&self) -> &str {
+        "tabs"
+    }
+
+    fn kind(&self) -> DirectiveKind {
+        DirectiveKind::Section
+    }
+
+    fn render(
+        &self,
+        node: &DirectiveNode,
+        directives: &Directives,
+        ctx: &mut DirectiveContext,
+    ) -> String {
+        let mut theme = self.config.features.tabs.theme.clone();
+        for (key, value) in node.attributes().values {
+            match key.as_str() {
+                "theme" => {
+                    let themes: Vec<&str> = value.split(',').map(|t| t.trim()).collect();
+                    if let Some(pair) = theme_pair(&themes) {
+                        theme = pair;
+                    }
+                }
+                _ => ctx
+                    .diagnostics
+                    .push(unknown_property(&key, "::tabs", node.position)),
+            }
+        }
+        let mut menu = Menu {
+            id: ctx.next_id(&self.config.features.tabs.class),
+            theme,
+            tabs: vec![],
+            position: node.position,
+        };
+
+        for child in &node.children {
+            match child {
+                Node::Directive(tab) if tab.kind == DirectiveKind::Leaf && tab.name == "tab" => {
+                    let tab = self.tab(tab, menu.tabs.len(), directives, ctx);
+                    menu.tabs.push(tab);
+                }
+                _ => match menu.tabs.last_mut() {
+                    Some(tab) if tab.source == TabSource::Content => {
+                        let md = directives.render_node(child, ctx);
+                        tab.markdown.push_str(&md);
+                    }
+                    tab => ctx.diagnostics.extend(ignored(tab.map(|t| &*t), child)),
+                },
+            }
+        }
+
+        if !node.closed() {
+            ctx.diagnostics.push(Diagnostic::error(
+                "tabs were opened but never closed with `::end-tabs`",
+                node.position,
+            ));
+        }
+
+        self.menu(&menu, &mut ctx.diagnostics)
+    }
+}
+
+/// A `::tab` which is not inside of a `::tabs` menu
+#[derive(Debug)]
+pub struct TabItem;
+
+impl Directive for TabItem {
+    fn name(&self) -> &str {
+        "tab"
+    }
+
+    fn kind(&self) -> DirectiveKind {
+        DirectiveKind::Leaf
+    }
+
+    fn render(
+        &self,
+        node: &DirectiveNode,
+        _directives: &Directives,
+        ctx: &mut DirectiveContext,
+    ) -> String {
+        ctx.diagnostics.push(Diagnostic::warning(
+            "a ::tab was found outside of a ::tabs menu",
+            node.position,
+        ));
+
+        node.open.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::html::HtmlContent;
+
+    fn expand(md: &str, config: &Config) -> (String, Vec<TabRoute>, Vec<Diagnostic>) {
+        let mut ctx = DirectiveContext::default();
+        let expanded = Directives::new(config, None).expand(md, &mut ctx);
+
+        (expanded, ctx.tab_routes, ctx.diagnostics)
+    }
+
+    fn html(md: &str, file: Option<&str>, config: &Config) -> HtmlContent {
+        let (md, _) = MarkdownContentRaw::new(md).parse(config).unwrap();
+        HtmlContent::for_page(&md, file, config)
+    }
+
+    #[test]
+    fn tabs_are_accessible() {
+        let html = html(
+            "# Nav\n\n::tabs\n::tab \"One\"\nThe **first** tab\n\n::tab Two\n- a\n::end-tabs\n\nafter",
+            None,
+            &Config::default(),
+        );
+
+        assert_eq!(
+            html.content(),
+            [
+                "<h1 id=\"nav\">Nav</h1>\n",
+                "<div class=\"tabs\" data-theme-light=\"light\" data-theme-dark=\"dark\">\n",
+                "<div class=\"tabs-list\" role=\"tablist\">\n",
+                "<button type=\"button\" class=\"tab\" role=\"tab\" id=\"tabs-1-tab-1\" aria-controls=\"tabs-1-panel-1\" aria-selected=\"true\" tabindex=\"0\">One</button>\n",
+                "<button type=\"button\" class=\"tab\" role=\"tab\" id=\"tabs-1-tab-2\" aria-controls=\"tabs-1-panel-2\" aria-selected=\"false\" tabindex=\"-1\">Two</button>\n",
+                "</div>\n",
+                "<div class=\"tab-panel\" role=\"tabpanel\" id=\"tabs-1-panel-1\" aria-labelledby=\"tabs-1-tab-1\" tabindex=\"0\">\n",
+                "<p>The <strong>first</strong> tab</p>\n",
+                "</div>\n",
+                "<div class=\"tab-panel\" role=\"tabpanel\" id=\"tabs-1-panel-2\" aria-labelledby=\"tabs-1-tab-2\" tabindex=\"0\" hidden>\n",
+                "<ul>\n<li>a</li>\n</ul>\n",
+                "</div>\n",
+                "</div>\n",
+                "<p>after</p>\n",
+            ]
+            .concat()
+        );
+        assert!(html.diagnostics.is_empty());
+    }
+
+    #[test]
+    fn tabs_inline_and_route_tabs() {
+        let dir = std::env::temp_dir().join(format!("dm-tabs-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("sub-page2.md"),
+            "---\ntitle: Two\n---\n## Second\n",
+        )
+        .unwrap();
+        fs::write(dir.join("sub-page3.md"), "Third").unwrap();
+        let page = dir.join("index.md").to_string_lossy().to_string();
+        let md = "::tabs theme=solarized\n::tab \"One\"\nfirst\n::tab name=\"Two\", inline=\"./sub-page2.md\"\n::tab \"Three\", route=\"./sub-page3.md\"\n::end-tabs";
+
+        let sfc = html(md, Some(&page), &Config::default());
+        let config = Config {
+            output: OutputFormat::HTML,
+            ..Config::default()
+        };
+        let plain = html(md, Some(&page), &config);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(sfc
+            .content()
+            .contains("data-theme-light=\"solarized\" data-theme-dark=\"solarized\""));
+        assert!(sfc.content().contains("<h2 id=\"second\">Second</h2>"));
+        assert!(!sfc.content().contains("title: Two"));
+        assert!(sfc
+            .content()
+            .contains("data-route=\"./sub-page3\">Three</button>"));
+        assert!(sfc.content().contains(
+            "aria-labelledby=\"tabs-1-tab-3\" tabindex=\"0\" hidden>\n<router-view />\n</div>"
+        ));
+        assert_eq!(sfc.tab_routes.len(), 1);
+        assert_eq!(sfc.tab_routes[0].name, "Three");
+        assert_eq!(sfc.tab_routes[0].path, "./sub-page3");
+        assert!(sfc.tab_routes[0].file.ends_with("/sub-page3.md"));
+        assert!(sfc.diagnostics.is_empty());
+
+        assert!(plain.tab_routes.is_empty());
+        assert!(plain.content().contains("<p>Third</p>"));
+        assert!(!plain.content().contains("router-view"));
+    }
+
+    #[test]
+    fn tabs_nested_files_resolve_against_their_file_and_cycles_are_reported() {
+        let dir = std::env::temp_dir().join(format!("dm-tabs-nested-{}", std::process::id()));
+        fs::create_dir_all(dir.join("parts")).unwrap();
+        fs::write(
+            dir.join("parts/one.md"),
+            "::tabs\n::tab \"Two\", inline=\"./two.md\"\n::end-tabs\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("parts/two.md"),
+            "## Second\n\n::tabs\n::tab \"Back\", inline=\"../index.md\"\n::tab \"Again\", inline=\"./one.md\"\n::end-tabs\n",
+        )
+        .unwrap();
+        let page = dir.join("index.md");
+        let md = "::tabs\n::tab \"One\", inline=\"./parts/one.md\"\n::end-tabs\n";
+        fs::write(&page, md).unwrap();
+
+        let mut ctx = DirectiveContext::default();
+        let expanded =
+            Directives::new(&Config::default(), Some(&page.to_string_lossy())).expand(md, &mut ctx);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(expanded.contains("## Second"));
+        assert!(ctx.files.is_empty());
+        let messages: Vec<&str> = ctx.diagnostics.iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(messages.len(), 2, "{:?}", messages);
+        assert!(
+            messages[0].starts_with("loading the tab content \"../index.md\" would create a cycle")
+        );
+        assert!(
+            messages[1].starts_with("loading the tab content \"./one.md\" would create a cycle")
+        );
+    }
+
+    #[test]
+    fn tabs_diagnostics() {
+        let md = "::tab \"Stray\"\n::tabs\nlost\n::tab \"One\", color=red, inline=\"./nope.md\"\n```md\n::end-tabs\n```\n";
+        let (expanded, routes, diagnostics) = expand(md, &Config::default());
+
+        assert!(routes.is_empty());
+        assert!(expanded.ends_with("</div>\n\n"));
+        let messages: Vec<(usize, &str)> = diagnostics
+            .iter()
+            .map(|d| (d.position.line, d.message.as_str()))
+            .collect();
+        assert_eq!(messages.len(), 8, "{:?}", messages);
+        assert!(messages[0].1.contains("outside of a ::tabs menu"));
+        assert_eq!(messages[1].0, 3);
+        assert!(messages[2].1.contains("\"color\" is not a known property"));
+        assert!(messages[3].1.contains("unable to read the tab content"));
+        // the code block (and the `::end-tabs` inside of it) is ignored by
+        // a tab whose content is in another file
+        assert_eq!(messages[5], (6, messages[4].1));
+        assert!(messages[4].1.contains("gets its content from another file"));
+        assert!(messages[7].1.contains("never closed"));
+    }
+
+    #[test]
+    fn tabs_can_be_turned_off() {
+        let mut config = Config::default();
+        config.features.tabs.enabled = false;
+        let md = "::tabs\n::tab One\n::end-tabs\n";
+        let (expanded, _, diagnostics) = expand(md, &config);
+
+        assert_eq!(expanded, md);
+        assert!(diagnostics.is_empty());
+    }
+}
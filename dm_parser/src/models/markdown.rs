@@ -68,6 +68,16 @@ impl MarkdownContent {
         Ok(md)
     }
 
+    /// the markdown content which remains after the frontmatter has been
+    /// extracted from the raw content
+    pub(crate) fn extracted(raw: &MarkdownContentRaw, content: &str) -> Self {
+        MarkdownContent {
+            raw_hash: raw.hash(),
+            hash: hash(content, None),
+            content: content.to_string(),
+        }
+    }
+
     /// Load markdown content from a file and return a tuple with the **Markdown**
     /// and **Frontmatter**.
     #[instrument]
@@ -1,9 +1,16 @@
 pub mod content_type;
 pub mod darkmatter;
+pub mod dependency_graph;
+pub mod diagnostic;
+pub mod external_links;
 pub mod frontmatter;
 pub mod handler;
-// pub mod html;
+pub mod html;
 pub mod markdown;
+pub mod position;
+pub mod route_map;
 pub mod sentiment;
 pub mod sfc;
+pub mod shortcode;
+pub mod template;
 pub mod toc;
@@ -0,0 +1,139 @@
+use std::ops::Range;
+
+use serde::{Deserialize, Serialize};
+
+/// A location within the markdown body of a page
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct SourcePosition {
+    /// the 1-based line number
+    pub line: usize,
+    /// the 1-based column (in characters)
+    pub column: usize,
+    /// the byte offset from the start of the content
+    pub offset: usize,
+}
+
+impl SourcePosition {
+    /// Determines the line and column of a byte offset within the content
+    pub fn from_offset(content: &str, offset: usize) -> Self {
+        let offset = std::cmp::min(offset, content.len());
+        let before = &content[..offset];
+        let line = before.matches('\n').count() + 1;
+        let line_start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+        let column = before[line_start..].chars().count() + 1;
+
+        SourcePosition {
+            line,
+            column,
+            offset,
+        }
+    }
+}
+
+/// Traces offsets in content which had other content spliced into it
+/// (e.g., an included document) back to the content it was built from.
+///
+/// Each step which splices content in adds a layer; an offset is traced
+/// back through the layers from the most recent to the first.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SourceMap {
+    /// for each layer, the range of the input which was replaced and the
+    /// range of the output which replaced it (in order)
+    layers: Vec<Vec<(Range<usize>, Range<usize>)>>,
+}
+
+impl SourceMap {
+    pub fn new() -> Self {
+        SourceMap::default()
+    }
+
+    /// Starts a layer for the splices made by the next step
+    pub fn add_layer(&mut self) {
+        self.layers.push(vec![]);
+    }
+
+    /// Records -- in the current layer -- that `input` was replaced by
+    /// the content at `output`
+    pub fn splice(&mut self, input: Range<usize>, output: Range<usize>) {
+        if self.layers.is_empty() {
+            self.add_layer();
+        }
+        self.layers.last_mut().unwrap().push((input, output));
+    }
+
+    /// The offset in the original content which an offset in the expanded
+    /// content came from; spliced in content is traced back to the start
+    /// of what it replaced.
+    pub fn source_offset(&self, offset: usize) -> usize {
+        self.layers.iter().rev().fold(offset, |offset, splices| {
+            let mut shift: isize = 0;
+            for (input, output) in splices {
+                if offset < output.start {
+                    break;
+                }
+                if offset < output.end {
+                    return input.start;
+                }
+                shift = output.end as isize - input.end as isize;
+            }
+
+            (offset as isize - shift) as usize
+        })
+    }
+
+    /// The position in the original `source` which a position in the
+    /// expanded content came from
+    pub fn position(&self, source: &str, position: SourcePosition) -> SourcePosition {
+        match self.layers.is_empty() {
+            true => position,
+            false => SourcePosition::from_offset(source, self.source_offset(position.offset)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn position_from_offset() {
+        let content = "# Title\n\nSee [here](./foo.md)";
+        let offset = content.find('[').unwrap();
+        let pos = SourcePosition::from_offset(content, offset);
+
+        assert_eq!(pos.line, 3);
+        assert_eq!(pos.column, 5);
+        assert_eq!(pos.offset, offset);
+    }
+
+    #[test]
+    fn source_map_traces_spliced_content() {
+        let source = "a\n<<< x\nb\n<<< y\nc";
+        // "<<< x\n" became three lines and "<<< y\n" became one
+        let expanded = "a\nx1\nx2\nx3\nb\ny1\nc";
+        let mut map = SourceMap::new();
+        map.splice(2..8, 2..11);
+        map.splice(10..16, 13..16);
+
+        let at = |s: &str| {
+            map.position(
+                source,
+                SourcePosition::from_offset(expanded, expanded.find(s).unwrap()),
+            )
+        };
+        assert_eq!(at("x2").line, 2);
+        assert_eq!(at("b").line, 3);
+        assert_eq!(at("y1").line, 4);
+        assert_eq!(at("c").line, 5);
+        assert_eq!(at("c").offset, source.find('c').unwrap());
+
+        // a second layer is traced back through the first
+        map.add_layer();
+        map.splice(0..2, 0..6);
+        assert_eq!(
+            map.source_offset(expanded.find('b').unwrap() + 4),
+            source.find('b').unwrap()
+        );
+    }
+}
@@ -0,0 +1,387 @@
+use std::collections::HashMap;
+
+use serde_json::Value;
+
+use crate::config::{features::link::LinkConfig, Config};
+
+use super::{
+    frontmatter::Frontmatter,
+    html::{
+        link::{clean_route, split_suffix, LinkKind, LinkRef, LinkStatus},
+        HtmlContent,
+    },
+};
+
+/// The file extensions which are considered pages when resolving links;
+/// links to any other extension are treated as static assets.
+const PAGE_EXTENSIONS: [&str; 2] = ["md", "dm"];
+
+/// A page which is part of the corpus
+#[derive(Debug, Clone)]
+pub struct PageEntry {
+    /// the route the page is served on
+    pub route: String,
+    /// the file (or database id) the page was loaded from
+    pub file: String,
+    /// the ids of all the headings found on the page
+    pub heading_ids: Vec<String>,
+    /// whether the page's frontmatter marks it as a draft
+    pub draft: bool,
+}
+
+/// A map of all the routes in a corpus of pages which allows links
+/// on any one page to be validated against the rest of the corpus.
+#[derive(Debug)]
+pub struct RouteMap {
+    links: LinkConfig,
+    draft_prop: String,
+    pages: HashMap<String, PageEntry>,
+    /// lowercased route keys mapped to the actual route key so
+    /// that case mismatches can be reported
+    lowercase: HashMap<String, String>,
+}
+
+impl RouteMap {
+    pub fn new(config: &Config) -> Self {
+        RouteMap {
+            links: config.features.links.clone(),
+            draft_prop: config.features.meta.draft_prop.clone(),
+            pages: HashMap::new(),
+            lowercase: HashMap::new(),
+        }
+    }
+
+    /// The route which a file in the `root_dir` will be served on
+    pub fn route_for_file(&self, file: &str) -> String {
+        clean_route(&self.page_path(file), &self.links)
+    }
+
+    /// Adds a page to the corpus; the page's headings are taken from its
+    /// HTML so that those which only exist once the page is rendered (from
+    /// an include, a directive, etc.) can be linked to
+    pub fn add_page(&mut self, file: &str, html: &HtmlContent, fm: &Frontmatter) {
+        let route = self.route_for_file(file);
+        let draft = matches!(fm.other.get(&self.draft_prop), Some(Value::Bool(true)));
+        let entry = PageEntry {
+            route: route.clone(),
+            file: file.to_string(),
+            heading_ids: html.heading_ids.clone(),
+            draft,
+        };
+
+        let key = route_key(&route);
+        self.lowercase.insert(key.to_lowercase(), key.clone());
+        self.pages.insert(key, entry);
+    }
+
+    /// Gets the page served on the given route
+    pub fn get(&self, route: &str) -> Option<&PageEntry> {
+        self.pages.get(&route_key(route))
+    }
+
+    /// the number of pages in the corpus
+    pub fn len(&self) -> usize {
+        self.pages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pages.is_empty()
+    }
+
+    /// Resolves a link found in `file` to the route it targets along
+    /// with the fragment (if there is one).
+    pub fn resolve(&self, file: &str, href: &str) -> (String, Option<String>) {
+        let (path, suffix) = split_suffix(href);
+        let fragment = suffix
+            .split_once('#')
+            .map(|(_, f)| f.to_string())
+            .filter(|f| !f.is_empty());
+
+        if path.is_empty() {
+            return (self.route_for_file(file), fragment);
+        }
+
+        let absolute = if path.starts_with('/') {
+            path.to_string()
+        } else {
+            let page = self.page_path(file);
+            let dir = &page[..page.rfind('/').map(|i| i + 1).unwrap_or(0)];
+            [dir, path].concat()
+        };
+
+        (clean_route(&normalize(&absolute), &self.links), fragment)
+    }
+
+    /// Validates a link found in `file`; links which do not target a
+    /// page on this site (external links, static assets, etc.) are not
+    /// checked and return `None`.
+    pub fn check(&self, file: &str, link: &LinkRef) -> Option<LinkStatus> {
+        if !(link.kind.is_local() || link.kind == LinkKind::Anchor) {
+            return None;
+        }
+        let (path, _) = split_suffix(&link.href);
+        if is_asset(path) {
+            return None;
+        }
+
+        let (route, fragment) = self.resolve(file, &link.href);
+        let key = route_key(&route);
+        let page = match self.pages.get(&key) {
+            Some(page) => page,
+            None => {
+                return match self.lowercase.get(&key.to_lowercase()) {
+                    Some(actual) => {
+                        Some(LinkStatus::CaseMismatch(self.pages[actual].route.clone()))
+                    }
+                    None => Some(LinkStatus::BrokenPage(route)),
+                }
+            }
+        };
+
+        if page.draft {
+            return Some(LinkStatus::Draft(page.route.clone()));
+        }
+
+        if let Some(fragment) = fragment {
+            if !page.heading_ids.contains(&fragment) {
+                return match page
+                    .heading_ids
+                    .iter()
+                    .find(|id| id.to_lowercase() == fragment.to_lowercase())
+                {
+                    Some(id) => Some(LinkStatus::CaseMismatch(format!("{}#{}", page.route, id))),
+                    None => Some(LinkStatus::BrokenFragment(fragment)),
+                };
+            }
+        }
+
+        Some(LinkStatus::Valid)
+    }
+
+    /// The links on a page -- when `validate_internal_links` is
+    /// configured -- checked against the corpus.
+    pub fn check_page(&self, file: &str, html: &HtmlContent) -> Vec<LinkRef> {
+        let mut links = html.links.clone();
+        if self.links.validate_internal_links {
+            for link in links.iter_mut() {
+                link.status = self.check(file, link);
+            }
+        }
+
+        links
+    }
+
+    /// The path of a file relative to `root_dir` (with a leading `/`)
+    fn page_path(&self, file: &str) -> String {
+        let file = file.replace('\\', "/");
+        let file = file.trim_start_matches("./");
+        let root = self
+            .links
+            .root_dir
+            .trim_start_matches("./")
+            .trim_matches('/');
+        let path = file.strip_prefix(root).unwrap_or(file);
+
+        format!("/{}", path.trim_start_matches('/'))
+    }
+}
+
+/// Routes are matched without regard to a trailing `/`
+fn route_key(route: &str) -> String {
+    let (path, _) = split_suffix(route);
+    match path.trim_end_matches('/') {
+        "" => String::from("/"),
+        path => path.to_string(),
+    }
+}
+
+/// Removes `.` and `..` segments from an absolute path
+fn normalize(path: &str) -> String {
+    let mut segments: Vec<&str> = vec![];
+    for segment in path.split('/') {
+        match segment {
+            "" | "." => (),
+            ".." => {
+                segments.pop();
+            }
+            _ => segments.push(segment),
+        }
+    }
+    let trailing = if path.ends_with('/') && !segments.is_empty() {
+        "/"
+    } else {
+        ""
+    };
+
+    format!("/{}{}", segments.join("/"), trailing)
+}
+
+/// whether the path points to a file other than a page
+pub(crate) fn is_asset(path: &str) -> bool {
+    let file = path.rsplit('/').next().unwrap_or(path);
+    if file == "." || file == ".." {
+        return false;
+    }
+    match file.rsplit_once('.') {
+        Some((name, ext)) if !name.is_empty() => !PAGE_EXTENSIONS.contains(&ext),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::markdown::MarkdownContentRaw;
+
+    fn corpus(pages: &[(&str, &str)]) -> RouteMap {
+        let config = Config::default();
+        let mut map = RouteMap::new(&config);
+        for (file, content) in pages {
+            let (md, fm) = MarkdownContentRaw::new(content).parse(&config).unwrap();
+            let html = HtmlContent::for_page(&md, Some(file), &config);
+            map.add_page(file, &html, &fm);
+        }
+
+        map
+    }
+
+    fn page_links(map: &RouteMap, file: &str, content: &str) -> Vec<Option<LinkStatus>> {
+        let config = Config::default();
+        let (md, _) = MarkdownContentRaw::new(content).parse(&config).unwrap();
+        let html = HtmlContent::for_page(&md, Some(file), &config);
+        map.check_page(file, &html)
+            .into_iter()
+            .map(|l| l.status)
+            .collect()
+    }
+
+    #[test]
+    fn route_map_routes_from_files() {
+        let map = corpus(&[]);
+        assert_eq!(
+            map.route_for_file("src/pages/guide/intro.md"),
+            "/guide/intro"
+        );
+        assert_eq!(map.route_for_file("./src/pages/guide/index.md"), "/guide/");
+        assert_eq!(map.route_for_file("src/pages/index.md"), "/");
+    }
+
+    #[test]
+    fn route_map_resolves_relative_links() {
+        let map = corpus(&[]);
+        let file = "src/pages/guide/intro.md";
+        assert_eq!(
+            map.resolve(file, "./setup.md#install"),
+            (String::from("/guide/setup"), Some(String::from("install")))
+        );
+        assert_eq!(map.resolve(file, "../index.md"), (String::from("/"), None));
+        assert_eq!(
+            map.resolve(file, "#overview"),
+            (String::from("/guide/intro"), Some(String::from("overview")))
+        );
+    }
+
+    #[test]
+    fn route_map_knows_headings_which_are_only_in_the_html() {
+        let map = corpus(&[(
+            "src/pages/guide/index.md",
+            "# Guide\n\n<div md>\n## Inside\n</div>\n\n<section md>\n  ## Also Inside\n</section>\n",
+        )]);
+        let links = page_links(
+            &map,
+            "src/pages/guide/index.md",
+            "[inside](#inside) and [also](#also-inside)",
+        );
+
+        assert_eq!(
+            links,
+            vec![Some(LinkStatus::Valid), Some(LinkStatus::Valid)]
+        );
+    }
+
+    #[test]
+    fn route_map_checks_links_which_are_only_in_the_html() {
+        let map = corpus(&[
+            ("src/pages/index.md", "# Home"),
+            ("src/pages/guide.md", "# Guide"),
+        ]);
+        let config = Config::default();
+        let (md, _) = MarkdownContentRaw::new(
+            "# Guide\n\n<div md>\n[home](/) and [gone](/gone)\n</div>\n\n[bad](#nope)\n",
+        )
+        .parse(&config)
+        .unwrap();
+        let html = HtmlContent::for_page(&md, Some("src/pages/guide.md"), &config);
+        let links = map.check_page("src/pages/guide.md", &html);
+
+        assert_eq!(links.len(), 3);
+        assert_eq!(links[0].href, "#nope");
+        assert_eq!(links[0].position.line, 7);
+        assert_eq!(
+            links[0].status,
+            Some(LinkStatus::BrokenFragment(String::from("nope")))
+        );
+        assert_eq!(links[1].text, "home");
+        assert_eq!(links[1].status, Some(LinkStatus::Valid));
+        assert_eq!(
+            links[2].status,
+            Some(LinkStatus::BrokenPage(String::from("/gone")))
+        );
+    }
+
+    #[test]
+    fn route_map_validates_links_across_corpus() {
+        let map = corpus(&[
+            ("src/pages/index.md", "# Home"),
+            ("src/pages/guide/index.md", "# Guide\n## Getting Started"),
+            ("src/pages/guide/Setup.md", "# Setup\n## Install"),
+            ("src/pages/wip.md", "---\ndraft: true\n---\n# WIP"),
+        ]);
+        let links = page_links(
+            &map,
+            "src/pages/guide/index.md",
+            r#"
+- [home](/)
+- [start](#getting-started)
+- [bad start](#getting-going)
+- [case](#Getting-Started)
+- [setup](./Setup.md#install)
+- [setup case](./setup.md)
+- [missing](../missing.md)
+- [draft](/wip)
+- [external](https://acme.com)
+- [asset](./logo.png)
+"#,
+        );
+
+        assert_eq!(
+            links,
+            vec![
+                Some(LinkStatus::Valid),
+                Some(LinkStatus::Valid),
+                Some(LinkStatus::BrokenFragment(String::from("getting-going"))),
+                Some(LinkStatus::CaseMismatch(String::from(
+                    "/guide/#getting-started"
+                ))),
+                Some(LinkStatus::Valid),
+                Some(LinkStatus::CaseMismatch(String::from("/guide/Setup"))),
+                Some(LinkStatus::BrokenPage(String::from("/missing"))),
+                Some(LinkStatus::Draft(String::from("/wip"))),
+                None,
+                None,
+            ]
+        );
+    }
+
+    #[test]
+    fn route_map_validation_can_be_turned_off() {
+        let mut config = Config::default();
+        config.features.links.validate_internal_links = false;
+        let map = RouteMap::new(&config);
+
+        assert_eq!(
+            page_links(&map, "src/pages/index.md", "[missing](./missing.md)"),
+            vec![None]
+        );
+    }
+}
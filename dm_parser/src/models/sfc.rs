@@ -3,6 +3,8 @@ use thiserror::Error;
 
 use crate::config::{Config, OutputFormat};
 
+use super::html::icons::IconRef;
+
 #[derive(Error, Debug)]
 pub enum SfcError {}
 
@@ -29,4 +31,16 @@ impl Sfc {
             style_blocks: vec![],
         }
     }
+
+    /// Imports the `unplugin-icons` component for each icon used on
+    /// the page into the setup script
+    pub fn add_icon_imports(&mut self, icons: &[IconRef]) {
+        for icon in icons {
+            let import = icon.import();
+            if !self.setup_script.lines().any(|l| l.trim() == import) {
+                self.setup_script.push_str(&import);
+                self.setup_script.push('\n');
+            }
+        }
+    }
 }
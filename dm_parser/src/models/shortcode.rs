@@ -0,0 +1,442 @@
+use std::{
+    collections::HashMap,
+    fs,
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use pulldown_cmark::escape::escape_html;
+use regex::{Captures, Regex};
+
+use crate::config::{features::shortcodes::ShortcodeConfig, Config};
+
+use super::{
+    content_type::ContentType, diagnostic::Diagnostic, html::md_attribute::code_ranges,
+    position::SourcePosition,
+};
+
+/// A shortcode used on a page along with the content between its tags
+#[derive(Debug)]
+struct Shortcode {
+    name: String,
+    /// the parameters in the order given; named parameters have a key
+    params: Vec<(Option<String>, String)>,
+    /// the opening tag
+    tag: Range<usize>,
+    /// the closing tag (when the shortcode wraps content)
+    close: Option<Range<usize>>,
+    children: Vec<Node>,
+}
+
+#[derive(Debug)]
+enum Node {
+    Text(Range<usize>),
+    Shortcode(Shortcode),
+}
+
+/// Expands the shortcodes on a page using HTML templates:
+///
+/// ```md
+/// {{< alert type="warning" "Heads up" >}}
+/// This is **important**
+/// {{< /alert >}}
+/// ```
+///
+/// A shortcode's template is taken from the configured `templates` or
+/// else read from `{name}.html` in one of the `ShortCode` directories of
+/// `dir_mapping`. In a template `{{ .type }}` is replaced with a named
+/// parameter, `{{ .0 }}` with a positional one and `{{ .inner }}` with
+/// the content between the tags; a fallback can be given for missing
+/// parameters with `{{ .type | "info" }}`. Shortcodes can be nested and
+/// are left alone inside of code.
+#[derive(Debug)]
+pub struct Shortcodes {
+    config: ShortcodeConfig,
+    /// the directories which hold the shortcode templates
+    dirs: Vec<PathBuf>,
+    tag: Regex,
+    param: Regex,
+    placeholder: Regex,
+    /// the templates loaded so far (`None` when none was found)
+    templates: HashMap<String, Option<String>>,
+}
+
+impl Shortcodes {
+    pub fn new(config: &Config) -> Self {
+        let shortcodes = &config.features.shortcodes;
+        Shortcodes {
+            config: shortcodes.clone(),
+            dirs: template_dirs(config),
+            tag: Regex::new(&format!(
+                r#"{}\s*(/?)([A-Za-z][\w-]*)((?:\s+(?:[A-Za-z_][\w-]*\s*=\s*)?(?:"[^"]*"|'[^']*'|[^\s"']+?))*?)\s*(/?)\s*{}"#,
+                regex::escape(&shortcodes.open),
+                regex::escape(&shortcodes.close)
+            ))
+            .unwrap(),
+            param: Regex::new(r#"(?:([A-Za-z_][\w-]*)\s*=\s*)?(?:"([^"]*)"|'([^']*)'|(\S+))"#)
+                .unwrap(),
+            placeholder: Regex::new(r#"\{\{\s*\.([\w-]+)(?:\s*\|\s*"([^"]*)")?\s*\}\}"#).unwrap(),
+            templates: HashMap::new(),
+        }
+    }
+
+    /// Applies the static replacements and then expands every shortcode
+    /// on the page
+    pub fn expand(&mut self, md: &str) -> (String, Vec<Diagnostic>) {
+        let mut diagnostics = vec![];
+        if !self.config.enabled {
+            return (md.to_string(), diagnostics);
+        }
+
+        let md = self.replace(md);
+        let nodes = self.parse(&md, &mut diagnostics);
+        let output = self.render(&nodes, &md, &mut diagnostics);
+
+        (output, diagnostics)
+    }
+
+    /// Substitutes the static find/replace values outside of code
+    fn replace(&self, md: &str) -> String {
+        if self.config.replacements.is_empty() {
+            return md.to_string();
+        }
+        // longer keys win when one key contains another
+        let mut keys: Vec<&String> = self.config.replacements.keys().collect();
+        keys.sort_by(|a, b| b.len().cmp(&a.len()).then(a.cmp(b)));
+        let pattern = keys
+            .iter()
+            .map(|k| regex::escape(k))
+            .collect::<Vec<_>>()
+            .join("|");
+        let find = Regex::new(&pattern).unwrap();
+        let code = code_ranges(md);
+
+        let mut output = String::with_capacity(md.len());
+        let mut last = 0;
+        for m in find.find_iter(md) {
+            if code.iter().any(|r| r.contains(&m.start())) {
+                continue;
+            }
+            output.push_str(&md[last..m.start()]);
+            output.push_str(&self.config.replacements[m.as_str()]);
+            last = m.end();
+        }
+        output.push_str(&md[last..]);
+
+        output
+    }
+
+    /// Pairs up the opening and closing tags; an opening tag which is
+    /// never closed is a shortcode without content.
+    fn parse(&self, md: &str, diagnostics: &mut Vec<Diagnostic>) -> Vec<Node> {
+        let code = code_ranges(md);
+        let mut root = vec![];
+        let mut stack: Vec<Shortcode> = vec![];
+        let mut last = 0;
+
+        for captures in self.tag.captures_iter(md) {
+            let range = captures.get(0).unwrap().range();
+            if code.iter().any(|r| r.contains(&range.start)) {
+                continue;
+            }
+            push(&mut stack, &mut root, Node::Text(last..range.start));
+            last = range.end;
+            let name = captures[2].to_string();
+
+            if &captures[1] == "/" {
+                match stack.iter().rposition(|s| s.name == name) {
+                    Some(idx) => {
+                        while stack.len() > idx + 1 {
+                            let unclosed = stack.pop().unwrap();
+                            flatten(&mut stack, &mut root, unclosed);
+                        }
+                        let mut shortcode = stack.pop().unwrap();
+                        shortcode.close = Some(range);
+                        push(&mut stack, &mut root, Node::Shortcode(shortcode));
+                    }
+                    None => {
+                        diagnostics.push(Diagnostic::warning(
+                            &format!(
+                                "the closing tag for the shortcode \"{}\" has no opening tag",
+                                name
+                            ),
+                            SourcePosition::from_offset(md, range.start),
+                        ));
+                        push(&mut stack, &mut root, Node::Text(range));
+                    }
+                }
+                continue;
+            }
+
+            let shortcode = Shortcode {
+                name,
+                params: self.params(&captures[3]),
+                tag: range,
+                close: None,
+                children: vec![],
+            };
+            match &captures[4] {
+                "/" => push(&mut stack, &mut root, Node::Shortcode(shortcode)),
+                _ => stack.push(shortcode),
+            }
+        }
+        while let Some(unclosed) = stack.pop() {
+            flatten(&mut stack, &mut root, unclosed);
+        }
+        root.push(Node::Text(last..md.len()));
+
+        root
+    }
+
+    fn params(&self, params: &str) -> Vec<(Option<String>, String)> {
+        self.param
+            .captures_iter(params)
+            .map(|p| {
+                let value = p.get(2).or_else(|| p.get(3)).or_else(|| p.get(4));
+                (
+                    p.get(1).map(|k| k.as_str().to_string()),
+                    value.map(|v| v.as_str()).unwrap_or("").to_string(),
+                )
+            })
+            .collect()
+    }
+
+    fn render(&mut self, nodes: &[Node], md: &str, diagnostics: &mut Vec<Diagnostic>) -> String {
+        let mut output = String::new();
+        for node in nodes {
+            match node {
+                Node::Text(range) => output.push_str(&md[range.clone()]),
+                Node::Shortcode(shortcode) => {
+                    let inner = shortcode
+                        .close
+                        .as_ref()
+                        .map(|_| self.render(&shortcode.children, md, diagnostics));
+                    output.push_str(&self.shortcode(shortcode, inner, md, diagnostics));
+                }
+            }
+        }
+
+        output
+    }
+
+    /// The HTML for a shortcode; a shortcode without a template is left
+    /// as it was written.
+    fn shortcode(
+        &mut self,
+        shortcode: &Shortcode,
+        inner: Option<String>,
+        md: &str,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> String {
+        let template = match self.template(&shortcode.name) {
+            Some(template) => template,
+            None => {
+                diagnostics.push(Diagnostic::warning(
+                    &format!(
+                        "no template was found for the shortcode \"{}\"",
+                        shortcode.name
+                    ),
+                    SourcePosition::from_offset(md, shortcode.tag.start),
+                ));
+                let mut original = md[shortcode.tag.clone()].to_string();
+                if let (Some(inner), Some(close)) = (inner, &shortcode.close) {
+                    original.push_str(&inner);
+                    original.push_str(&md[close.clone()]);
+                }
+                return original;
+            }
+        };
+
+        let inner = inner.unwrap_or_default();
+        let inner = inner.strip_prefix('\n').unwrap_or(&inner);
+        let inner = inner.strip_suffix('\n').unwrap_or(inner);
+        let positional: Vec<&String> = shortcode
+            .params
+            .iter()
+            .filter(|(key, _)| key.is_none())
+            .map(|(_, value)| value)
+            .collect();
+
+        self.placeholder
+            .replace_all(&template, |c: &Captures| {
+                let key = &c[1];
+                if key == "inner" {
+                    return inner.to_string();
+                }
+                let value = match key.parse::<usize>() {
+                    Ok(idx) => positional.get(idx).copied(),
+                    Err(_) => shortcode
+                        .params
+                        .iter()
+                        .find(|(k, _)| k.as_deref() == Some(key))
+                        .map(|(_, value)| value),
+                };
+                let value = value
+                    .map(|v| v.as_str())
+                    .or_else(|| c.get(2).map(|d| d.as_str()))
+                    .unwrap_or("");
+                let mut escaped = String::new();
+                escape_html(&mut escaped, value).unwrap();
+
+                escaped
+            })
+            .to_string()
+    }
+
+    /// The template for a shortcode; configured templates take precedence
+    /// over template files.
+    fn template(&mut self, name: &str) -> Option<String> {
+        if let Some(template) = self.config.templates.get(name) {
+            return Some(template.clone());
+        }
+        let dirs = &self.dirs;
+        self.templates
+            .entry(name.to_string())
+            .or_insert_with(|| {
+                dirs.iter()
+                    .find_map(|dir| fs::read_to_string(dir.join(format!("{}.html", name))).ok())
+            })
+            .clone()
+    }
+}
+
+/// Adds a node to the innermost open shortcode (or the page)
+fn push(stack: &mut [Shortcode], root: &mut Vec<Node>, node: Node) {
+    match stack.last_mut() {
+        Some(open) => open.children.push(node),
+        None => root.push(node),
+    }
+}
+
+/// Turns a shortcode which was never closed into one without content;
+/// what followed it belongs to the enclosing shortcode (or the page).
+fn flatten(stack: &mut [Shortcode], root: &mut Vec<Node>, mut shortcode: Shortcode) {
+    let children = std::mem::take(&mut shortcode.children);
+    push(stack, root, Node::Shortcode(shortcode));
+    for child in children {
+        push(stack, root, child);
+    }
+}
+
+/// The directories of the `ShortCode` entries in `dir_mapping` (the part
+/// of each glob before any wildcard)
+fn template_dirs(config: &Config) -> Vec<PathBuf> {
+    config
+        .dir_mapping
+        .iter()
+        .filter(|(_, content_type)| matches!(content_type, ContentType::ShortCode))
+        .map(|(glob, _)| {
+            Path::new(glob)
+                .iter()
+                .take_while(|part| !part.to_string_lossy().contains(['*', '?', '[', '{', '(']))
+                .collect()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(templates: &[(&str, &str)]) -> Config {
+        let mut config = Config::default();
+        config.features.shortcodes.templates = templates
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+
+        config
+    }
+
+    #[test]
+    fn shortcodes_expand_with_params() {
+        let config = config(&[(
+            "alert",
+            "<div class=\"alert {{ .type | \"info\" }}\" title=\"{{ .0 }}\">{{ .inner }}</div>",
+        )]);
+        let (md, diagnostics) = Shortcodes::new(&config).expand(
+            "a {{< alert type=\"warning\" \"Heads <up>\" >}}\n*careful*\n{{< /alert >}} b\n{{< alert />}}",
+        );
+
+        assert_eq!(
+            md,
+            "a <div class=\"alert warning\" title=\"Heads &lt;up&gt;\">*careful*</div> b\n<div class=\"alert info\" title=\"\"></div>"
+        );
+        assert!(diagnostics.is_empty());
+    }
+
+    #[test]
+    fn shortcodes_nest() {
+        let config = config(&[
+            ("box", "<section>{{ .inner }}</section>"),
+            ("icon", "<i class=\"{{ .0 }}\"></i>"),
+        ]);
+        let (md, _) = Shortcodes::new(&config)
+            .expand("{{< box >}}{{< box >}}{{< icon star >}} text{{< /box >}}{{< /box >}}");
+
+        assert_eq!(
+            md,
+            "<section><section><i class=\"star\"></i> text</section></section>"
+        );
+    }
+
+    #[test]
+    fn shortcodes_in_code_are_left_alone() {
+        let config = config(&[("x", "X")]);
+        let md = "`{{< x >}}` and\n```\n{{< x >}}\n```\n{{< x >}}";
+        let (md, _) = Shortcodes::new(&config).expand(md);
+
+        assert_eq!(md, "`{{< x >}}` and\n```\n{{< x >}}\n```\nX");
+    }
+
+    #[test]
+    fn shortcodes_static_replacements() {
+        let mut config = config(&[]);
+        config.features.shortcodes.replacements = HashMap::from([
+            (String::from("%co%"), String::from("ACME")),
+            (String::from("%company%"), String::from("ACME Corp.")),
+        ]);
+        let (md, _) = Shortcodes::new(&config).expand("%company% (%co%) `%co%`");
+
+        assert_eq!(md, "ACME Corp. (ACME) `%co%`");
+    }
+
+    #[test]
+    fn shortcodes_templates_from_files() {
+        let dir = std::env::temp_dir().join("dm-shortcodes");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("hello.html"), "<b>Hello {{ .name }}</b>").unwrap();
+        let config = Config {
+            dir_mapping: vec![(
+                format!("{}/**/*.html", dir.to_string_lossy()),
+                ContentType::ShortCode,
+            )],
+            ..Config::default()
+        };
+        let (md, _) = Shortcodes::new(&config).expand("{{< hello name=Bob >}}");
+
+        assert_eq!(md, "<b>Hello Bob</b>");
+    }
+
+    #[test]
+    fn shortcodes_problems_are_reported() {
+        let config = config(&[("x", "X")]);
+        let (md, diagnostics) = Shortcodes::new(&config)
+            .expand("{{< x >}}\n{{< nope a=1 >}}in{{< /nope >}}\n{{< /y >}}");
+
+        assert_eq!(md, "X\n{{< nope a=1 >}}in{{< /nope >}}\n{{< /y >}}");
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].position.line, 3);
+        assert!(diagnostics[1].message.contains("\"nope\""));
+    }
+
+    #[test]
+    fn shortcodes_configurable_syntax() {
+        let mut config = config(&[("x", "[{{ .0 }}]")]);
+        config.features.shortcodes.open = String::from("{%");
+        config.features.shortcodes.close = String::from("%}");
+        let (md, _) = Shortcodes::new(&config).expand("{% x 1 %} {{< x 2 >}}");
+
+        assert_eq!(md, "[1] {{< x 2 >}}");
+    }
+}
@@ -0,0 +1,375 @@
+use chrono::{
+    format::{Item, StrftimeItems},
+    NaiveDate, NaiveDateTime,
+};
+use pulldown_cmark::escape::escape_html;
+use regex::{Captures, Regex};
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::config::{features::templates::TemplateConfig, Config, OutputFormat};
+
+use super::{
+    diagnostic::Diagnostic, frontmatter::Frontmatter, html::md_attribute::code_ranges,
+    position::SourcePosition,
+};
+
+/// The values which a page's template expressions can refer to; a value
+/// which isn't known yet (such as the darkmatter before it has been
+/// gathered) leaves its expressions untouched so that a later pass can
+/// resolve them.
+#[derive(Debug, Default, Clone)]
+pub struct TemplateContext {
+    frontmatter: Option<Value>,
+    darkmatter: Option<Value>,
+    route: Option<String>,
+}
+
+impl TemplateContext {
+    pub fn new() -> Self {
+        TemplateContext::default()
+    }
+
+    pub fn with_frontmatter(mut self, frontmatter: &Frontmatter) -> Self {
+        self.frontmatter = serde_json::to_value(frontmatter).ok();
+        self
+    }
+
+    pub fn with_darkmatter<T: Serialize>(mut self, darkmatter: &T) -> Self {
+        self.darkmatter = serde_json::to_value(darkmatter).ok();
+        self
+    }
+
+    pub fn with_route(mut self, route: &str) -> Self {
+        self.route = Some(route.to_string());
+        self
+    }
+}
+
+/// Replaces the template expressions in a page's text with their values:
+///
+/// ```md
+/// # {{ frontmatter.title | upper }}
+/// Published {{ fm.date | date("%B %e, %Y") }}, a {{ dm.time_to_read }}
+/// minute read found at `{{ route }}`
+/// ```
+///
+/// Expressions start with `frontmatter` (or `fm`), `darkmatter` (or
+/// `dm`) or `route` and may be followed by the `upper`, `lower`,
+/// `default("...")` and `date("...")` filters. Anything else between
+/// `{{ }}` is left for VueJS to interpolate, as is an expression written
+/// as `\{{ fm.title }}`, and nothing inside of code is touched.
+#[derive(Debug)]
+pub struct Templates {
+    config: TemplateConfig,
+    output: OutputFormat,
+    expression: Regex,
+    filter: Regex,
+    date: Regex,
+}
+
+impl Templates {
+    pub fn new(config: &Config) -> Self {
+        Templates {
+            config: config.features.templates.clone(),
+            output: config.output.clone(),
+            expression: Regex::new(
+                r#"(\\)?\{\{\s*([A-Za-z_]\w*)((?:\.[\w-]+)*)\s*((?:\|\s*[A-Za-z_]\w*\s*(?:\(\s*(?:"[^"]*"|'[^']*')?\s*\))?\s*)*)\}\}"#,
+            )
+            .unwrap(),
+            filter: Regex::new(
+                r#"\|\s*([A-Za-z_]\w*)\s*(?:\(\s*(?:"([^"]*)"|'([^']*)')?\s*\))?"#,
+            )
+            .unwrap(),
+            date: Regex::new(r"^(\d{4})-(\d{1,2})-(\d{1,2})(?:[T ](\d{1,2}):(\d{2})(?::(\d{2}))?)?")
+                .unwrap(),
+        }
+    }
+
+    /// Replaces each expression outside of code which the context has a
+    /// value for
+    pub fn interpolate(&self, md: &str, context: &TemplateContext) -> (String, Vec<Diagnostic>) {
+        let mut diagnostics = vec![];
+        if !self.config.enabled {
+            return (md.to_string(), diagnostics);
+        }
+
+        let code = code_ranges(md);
+        let mut output = String::with_capacity(md.len());
+        let mut last = 0;
+        for captures in self.expression.captures_iter(md) {
+            let range = captures.get(0).unwrap().range();
+            if captures.get(1).is_some() || code.iter().any(|r| r.contains(&range.start)) {
+                continue;
+            }
+            let position = SourcePosition::from_offset(md, range.start);
+            if let Some(value) = self.resolve(&captures, context, position, &mut diagnostics) {
+                output.push_str(&md[last..range.start]);
+                output.push_str(&escape(&value));
+                last = range.end;
+            }
+        }
+        output.push_str(&md[last..]);
+
+        (output, diagnostics)
+    }
+
+    /// The value of an expression after its filters are applied or
+    /// `None` when the expression is to be left as it is
+    fn resolve(
+        &self,
+        captures: &Captures,
+        context: &TemplateContext,
+        position: SourcePosition,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> Option<String> {
+        let root = &captures[2];
+        let path: Vec<&str> = captures[3].split('.').filter(|p| !p.is_empty()).collect();
+        let value = match root {
+            "frontmatter" | "fm" => lookup(context.frontmatter.as_ref()?, &path),
+            "darkmatter" | "dm" => lookup(context.darkmatter.as_ref()?, &path),
+            "route" if path.is_empty() => context.route.clone().map(Value::String),
+            _ => {
+                // for SFC output this is most likely one of VueJS's own
+                // expressions
+                if matches!(self.output, OutputFormat::HTML) {
+                    diagnostics.push(Diagnostic::warning(
+                        &format!("\"{}\" is not something a template can refer to", root),
+                        position,
+                    ));
+                }
+                return None;
+            }
+        };
+
+        let mut value = value.filter(|v| !v.is_null());
+        let mut defaulted = false;
+        for filter in self.filter.captures_iter(&captures[4]) {
+            let argument = filter.get(2).or_else(|| filter.get(3)).map(|a| a.as_str());
+            value = match (&filter[1], value) {
+                ("default", None) => {
+                    defaulted = true;
+                    Some(Value::String(argument.unwrap_or_default().to_string()))
+                }
+                ("default", value) => value,
+                (_, None) => None,
+                ("upper", Some(v)) => Some(Value::String(to_string(&v).to_uppercase())),
+                ("lower", Some(v)) => Some(Value::String(to_string(&v).to_lowercase())),
+                ("date", Some(v)) => {
+                    let format = argument.unwrap_or(&self.config.date_format);
+                    match self.format_date(&v, format) {
+                        Ok(date) => Some(Value::String(date)),
+                        Err(message) => {
+                            diagnostics.push(Diagnostic::warning(&message, position));
+                            Some(v)
+                        }
+                    }
+                }
+                (name, v) => {
+                    diagnostics.push(Diagnostic::warning(
+                        &format!("unknown template filter \"{}\"", name),
+                        position,
+                    ));
+                    v
+                }
+            };
+        }
+
+        match value {
+            Some(value) => Some(to_string(&value)),
+            None => {
+                if !defaulted {
+                    diagnostics.push(Diagnostic::warning(
+                        &format!(
+                            "\"{}{}\" has no value; use the default filter to give it one",
+                            root, &captures[3]
+                        ),
+                        position,
+                    ));
+                }
+                Some(String::new())
+            }
+        }
+    }
+
+    /// Formats an ISO 8601 date (`2023-01-31` or `2023-01-31T09:30:00`)
+    /// or a unix timestamp with `strftime` style specifiers
+    fn format_date(&self, value: &Value, format: &str) -> Result<String, String> {
+        let not_a_date = || format!("{} is not a date which can be formatted", value);
+        let date = match value {
+            Value::String(s) => {
+                let c = self.date.captures(s.trim()).ok_or_else(not_a_date)?;
+                let part = |i: usize| c.get(i).map_or(0, |m| m.as_str().parse::<u32>().unwrap());
+                let year = c[1].parse::<i32>().unwrap();
+                NaiveDate::from_ymd_opt(year, part(2), part(3))
+                    .and_then(|d| d.and_hms_opt(part(4), part(5), part(6)))
+                    .ok_or_else(not_a_date)?
+            }
+            Value::Number(n) => {
+                let mut timestamp = n.as_i64().ok_or_else(not_a_date)?;
+                // javascript timestamps are in milliseconds
+                if timestamp.abs() > 100_000_000_000 {
+                    timestamp /= 1000;
+                }
+                NaiveDateTime::from_timestamp_opt(timestamp, 0).ok_or_else(not_a_date)?
+            }
+            _ => return Err(not_a_date()),
+        };
+        let items: Vec<Item> = StrftimeItems::new(format).collect();
+        if items.contains(&Item::Error) {
+            return Err(format!("\"{}\" is not a valid date format", format));
+        }
+
+        Ok(date.format_with_items(items.into_iter()).to_string())
+    }
+}
+
+/// Follows a path of properties (or array indexes) into a value; a
+/// `snake_case` property also finds its `camelCase` equivalent
+fn lookup(value: &Value, path: &[&str]) -> Option<Value> {
+    let mut current = value;
+    for property in path {
+        current = match current {
+            Value::Object(map) => map
+                .get(*property)
+                .or_else(|| map.get(&camel_case(property)))?,
+            Value::Array(list) => list.get(property.parse::<usize>().ok()?)?,
+            _ => return None,
+        };
+    }
+
+    Some(current.clone())
+}
+
+fn camel_case(property: &str) -> String {
+    let mut camel = String::with_capacity(property.len());
+    let mut upper = false;
+    for c in property.chars() {
+        match c {
+            '_' => upper = true,
+            c if upper => {
+                camel.extend(c.to_uppercase());
+                upper = false;
+            }
+            c => camel.push(c),
+        }
+    }
+
+    camel
+}
+
+fn to_string(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Null => String::new(),
+        Value::Array(list) => list.iter().map(to_string).collect::<Vec<_>>().join(", "),
+        other => other.to_string(),
+    }
+}
+
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    escape_html(&mut escaped, value).unwrap();
+
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn context() -> TemplateContext {
+        let frontmatter = Frontmatter::new(Some(json!({
+            "title": "Hello & Welcome",
+            "date": "2023-01-31",
+            "tags": ["rust", "markdown"],
+            "imageWidth": 200,
+        })))
+        .unwrap();
+
+        TemplateContext::new()
+            .with_frontmatter(&frontmatter)
+            .with_darkmatter(&json!({ "time_to_read": 4 }))
+            .with_route("/blog/hello")
+    }
+
+    #[test]
+    fn expressions_are_interpolated() {
+        let (md, diagnostics) = Templates::new(&Config::default()).interpolate(
+            "# {{ frontmatter.title }}\n\n{{ dm.time_to_read }} minutes at {{route}} about {{ fm.tags }}, {{ fm.image_width }}px\n",
+            &context(),
+        );
+
+        assert_eq!(
+            md,
+            "# Hello &amp; Welcome\n\n4 minutes at /blog/hello about rust, markdown, 200px\n"
+        );
+        assert!(diagnostics.is_empty());
+    }
+
+    #[test]
+    fn filters_are_applied() {
+        let (md, diagnostics) = Templates::new(&Config::default()).interpolate(
+            r#"{{ fm.title | upper }} {{ fm.date | date("%A, %B %e %Y") }} {{ fm.date | date }} {{ fm.author | default("anonymous") | upper }}"#,
+            &context(),
+        );
+
+        assert_eq!(
+            md,
+            "HELLO &amp; WELCOME Tuesday, January 31 2023 2023-01-31 ANONYMOUS"
+        );
+        assert!(diagnostics.is_empty());
+    }
+
+    #[test]
+    fn dates_are_formatted() {
+        let templates = Templates::new(&Config::default());
+        let date = |value: Value, format: &str| templates.format_date(&value, format);
+
+        assert_eq!(
+            date(json!("2023-02-05"), "%e %b"),
+            Ok(String::from(" 5 Feb"))
+        );
+        assert_eq!(
+            date(json!("2023-02-05T09:07"), "%a %d/%m/%y %H:%M:%S"),
+            Ok(String::from("Sun 05/02/23 09:07:00"))
+        );
+        assert_eq!(
+            date(json!(1_675_123_200_000_i64), "%Y-%m-%d"),
+            Ok(String::from("2023-01-31"))
+        );
+        assert!(date(json!("2023-02-30"), "%Y").is_err());
+        assert!(date(json!(true), "%Y").is_err());
+        assert_eq!(
+            date(json!("2023-02-05"), "%Q"),
+            Err(String::from("\"%Q\" is not a valid date format"))
+        );
+    }
+
+    #[test]
+    fn code_vue_and_escaped_expressions_are_left_alone() {
+        let md =
+            "`{{ fm.title }}` {{ count + 1 }} \\{{ fm.title }}\n\n```md\n{{ fm.title }}\n```\n";
+        let (output, diagnostics) = Templates::new(&Config::default()).interpolate(md, &context());
+
+        assert_eq!(output, md);
+        assert!(diagnostics.is_empty());
+    }
+
+    #[test]
+    fn unknown_values_are_reported() {
+        let (md, diagnostics) = Templates::new(&Config::default()).interpolate(
+            "by {{ fm.author }}",
+            &TemplateContext::new().with_route("/"),
+        );
+        // the frontmatter isn't part of this context so is left for later
+        assert_eq!(md, "by {{ fm.author }}");
+        assert!(diagnostics.is_empty());
+
+        let (md, diagnostics) =
+            Templates::new(&Config::default()).interpolate("by {{ fm.author }}", &context());
+        assert_eq!(md, "by ");
+        assert_eq!(diagnostics.len(), 1);
+    }
+}
@@ -23,7 +23,7 @@ impl Stage for Initialize {
 }
 
 impl<'a> Pipeline<'a, Initialize> {
-    pub fn load_content(&mut self) -> Result<&mut Pipeline<Initialize>, ParserError> {
+    pub fn load_content(&mut self) -> Result<&mut Self, ParserError> {
         let file = self.id.to_owned();
         match self.source {
             Source::Database => {
@@ -38,51 +38,51 @@ impl<'a> Pipeline<'a, Initialize> {
     }
 
     /// add raw markdown content from a string slice
-    pub fn add_md_str(&mut self, md: &str) -> &mut Pipeline<Initialize> {
+    pub fn add_md_str(&mut self, md: &str) -> &mut Self {
         self.markdown = Some(MarkdownContentRaw::new(md));
 
         self
     }
 
     /// add raw markdown content from a file
-    pub fn add_md_file(&mut self, file: &str) -> Result<&mut Pipeline<Initialize>, ParserError> {
+    pub fn add_md_file(&mut self, file: &str) -> Result<&mut Self, ParserError> {
         todo!();
     }
 
     /// add raw markdown content from a database connection
-    pub fn add_md_db(&mut self, db: &str) -> Result<&mut Pipeline<Initialize>, ParserError> {
+    pub fn add_md_db(&mut self, db: &str) -> Result<&mut Self, ParserError> {
         todo!();
     }
 
     /// apply userland transforms from the raw_markdown hook
-    pub fn h_raw_markdown(&mut self) -> Result<&mut Pipeline<Initialize>, ParserError> {
+    pub fn h_raw_markdown(&mut self) -> Result<&mut Self, ParserError> {
         Ok(self)
     }
 
     /// after providing the raw markdown content you can move the
     /// next stage which involves parsing the "raw md" into both
     /// `Frontmatter` and `MarkdownContent`
-    pub fn next(&mut self) -> Result<Pipeline<ParseRawMd>, ParserError> {
+    pub fn next(self) -> Result<Pipeline<'a, ParseRawMd>, ParserError> {
         // Pipeline::try_from(self)
-        if self.markdown.is_none() {
-            Err(ParserError::Markdown(
+        match self.markdown {
+            None => Err(ParserError::Markdown(
                 MarkdownError::NotReadyForParseRawMdState,
-            ))
-        } else {
-            let markdown = self.markdown.unwrap();
-            let (markdown, frontmatter) = Frontmatter::extract(&markdown, &self.config)?;
-
-            Ok(Pipeline {
-                id: self.id.clone(),
-                route: self.route,
-                config: self.config,
-                source: self.source,
-                markdown,
-                frontmatter,
-                darkmatter: self.darkmatter,
-                html: self.html,
-                sfc: self.sfc,
-            })
+            )),
+            Some(markdown) => {
+                let (markdown, frontmatter) = Frontmatter::extract(&markdown, &self.config)?;
+
+                Ok(Pipeline {
+                    id: self.id.clone(),
+                    route: self.route,
+                    config: self.config,
+                    source: self.source,
+                    markdown,
+                    frontmatter,
+                    darkmatter: self.darkmatter,
+                    html: self.html,
+                    sfc: self.sfc,
+                })
+            }
         }
     }
 }
@@ -95,15 +95,16 @@ mod tests {
 
     #[test]
     fn p_init_can_add_md_str() {
-        let p = Pipeline::new("foobar.md", Config::default());
+        let mut p = Pipeline::new("foobar.md", Config::default());
         p.add_md_str("# Testing\nOne two three.");
         assert!(p.markdown.is_some());
     }
 
     // it's a pre-requisite that the markdown be set before calling "next()"
+    #[test]
     fn p_init_can_move_to_next_stage_with_md() {
-        let p = Pipeline::new("foobar.md", Config::default())
-            .add_md_str("---\ntitle: this is a test\n---\n# Testing\nOne two three.");
+        let mut p = Pipeline::new("foobar.md", Config::default());
+        p.add_md_str("---\ntitle: this is a test\n---\n# Testing\nOne two three.");
         let p = p.next().unwrap();
 
         assert!(p.frontmatter.title.is_some());
@@ -1,11 +1,19 @@
 use serde::{Deserialize, Serialize};
+use tracing::warn;
 
 use crate::{
     errors::parser_err::ParserError,
-    models::{frontmatter::Frontmatter, markdown::MarkdownContent},
+    models::{
+        frontmatter::Frontmatter,
+        markdown::MarkdownContent,
+        shortcode::Shortcodes,
+        template::{TemplateContext, Templates},
+    },
     pipeline::{Pipeline, Stage},
 };
 
+use super::c_initial_darkmatter::InitialDarkmatter;
+
 /// Stage in the pipeline where the raw markdown content
 /// is split into separate `Frontmatter` and `Markdown`
 /// structs.
@@ -48,26 +56,52 @@ impl Stage for ParseRawMd {
 // }
 
 impl<'a> Pipeline<'a, ParseRawMd> {
-    pub fn h_frontmatter_defaults(&mut self) -> Result<&'a mut Self, ParserError> {
+    pub fn h_frontmatter_defaults(&mut self) -> Result<&mut Self, ParserError> {
+        Ok(self)
+    }
+
+    pub fn h_frontmatter_overrides(&mut self) -> Result<&mut Self, ParserError> {
         Ok(self)
     }
 
-    pub fn h_frontmatter_overrides(&mut self) -> Result<&'a mut Self, ParserError> {
+    /// expands the shortcodes (and static replacements) found in
+    /// the markdown content
+    pub fn expand_shortcodes(&mut self) -> Result<&mut Self, ParserError> {
+        let (content, diagnostics) = Shortcodes::new(&self.config).expand(&self.markdown.content());
+        for diagnostic in diagnostics {
+            warn!("{}: {}", self.id, diagnostic);
+        }
+        self.markdown.mutate(&content);
+
         Ok(self)
     }
 
-    pub fn expand_shortcodes(&mut self) -> Result<&'a mut Self, ParserError> {
+    /// replaces the template expressions which refer to the frontmatter
+    /// or route (e.g., `{{ frontmatter.title }}`) with their values;
+    /// darkmatter expressions are left until darkmatter is available
+    pub fn interpolate_templates(&mut self) -> Result<&mut Self, ParserError> {
+        let context = TemplateContext::new()
+            .with_frontmatter(&self.frontmatter)
+            .with_route(self.route);
+        let (content, diagnostics) =
+            Templates::new(&self.config).interpolate(&self.markdown.content(), &context);
+        for diagnostic in diagnostics {
+            warn!("{}: {}", self.id, diagnostic);
+        }
+        self.markdown.mutate(&content);
+
         Ok(self)
     }
+
     /// allows the raw markdown content to be mutated before any
     /// processing is done
-    pub fn h_mutate_markdown(&mut self) -> Result<&'a mut Self, ParserError> {
+    pub fn h_mutate_markdown(&mut self) -> Result<&mut Self, ParserError> {
         Ok(self)
     }
 
-    // /// Gather the first part of the darkmatter metadata which will
-    // /// then become available to all subsequent hooks/callbacks.
-    // pub fn next(self) -> Result<Pipeline<InitialDarkmatter>, ParserError> {
-    //     Pipeline::try_from(self)
-    // }
+    /// Gather the first part of the darkmatter metadata which will
+    /// then become available to all subsequent hooks/callbacks.
+    pub fn next(self) -> Result<Pipeline<'a, InitialDarkmatter>, ParserError> {
+        Pipeline::try_from(self)
+    }
 }
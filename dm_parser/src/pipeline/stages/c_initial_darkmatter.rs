@@ -1,9 +1,12 @@
+use tracing::warn;
+
 use crate::{
     errors::parser_err::ParserError,
     models::{
         darkmatter::{Darkmatter, DmInitial},
         frontmatter::Frontmatter,
         markdown::MarkdownContent,
+        template::{TemplateContext, Templates},
     },
     pipeline::{Pipeline, Stage},
 };
@@ -20,33 +23,43 @@ impl Stage for InitialDarkmatter {
     type SFC = bool;
 }
 
-impl TryFrom<Pipeline<ParseRawMd>> for Pipeline<InitialDarkmatter> {
+impl<'a> TryFrom<Pipeline<'a, ParseRawMd>> for Pipeline<'a, InitialDarkmatter> {
     type Error = ParserError;
 
-    fn try_from(value: Pipeline<ParseRawMd>) -> Result<Self, Self::Error> {
-        todo!();
+    fn try_from(value: Pipeline<'a, ParseRawMd>) -> Result<Self, Self::Error> {
+        Ok(Pipeline {
+            id: value.id,
+            route: value.route,
+            config: value.config,
+            source: value.source,
+            markdown: value.markdown,
+            frontmatter: value.frontmatter,
+            darkmatter: Darkmatter::default(),
+            html: false,
+            sfc: false,
+        })
     }
 }
 
-impl Pipeline<InitialDarkmatter> {
+impl<'a> Pipeline<'a, InitialDarkmatter> {
     /// detects language or uses default
-    pub fn lang_detection(&self) -> Result<Pipeline<InitialDarkmatter>, ParserError> {
+    pub fn lang_detection(&self) -> Result<Self, ParserError> {
         todo!();
     }
 
-    pub fn tokenize(&self) -> Result<Pipeline<InitialDarkmatter>, ParserError> {
+    pub fn tokenize(&self) -> Result<Self, ParserError> {
         todo!();
     }
 
     /// Produces a sentiment analysis score if so
     /// configured.
-    pub fn sentiment(&self) -> Result<Pipeline<InitialDarkmatter>, ParserError> {
+    pub fn sentiment(&self) -> Result<Self, ParserError> {
         todo!();
     }
 
     /// uses the XXX algorithm to determine language
     /// complexity (if so configured)
-    pub fn complexity(&self) -> Result<Pipeline<InitialDarkmatter>, ParserError> {
+    pub fn complexity(&self) -> Result<Self, ParserError> {
         todo!();
     }
 
@@ -55,17 +68,34 @@ impl Pipeline<InitialDarkmatter> {
     /// Note: if complexity is used then this will be
     /// incorporated into the TTR score but does not
     /// require it.
-    pub fn ttr(&self) -> Result<Pipeline<InitialDarkmatter>, ParserError> {
+    pub fn ttr(&self) -> Result<Self, ParserError> {
         todo!();
     }
 
     /// Produces a bloom filter for content searching
     /// if so configured.
-    pub fn bloom(&self) -> Result<Pipeline<InitialDarkmatter>, ParserError> {
+    pub fn bloom(&self) -> Result<Self, ParserError> {
         todo!();
     }
 
-    pub fn next_stage(self) -> Result<Pipeline<ParseHtml>, ParserError> {
+    /// replaces the template expressions which were waiting on the
+    /// darkmatter (e.g., `{{ dm.time_to_read }}`) with their values
+    pub fn interpolate_templates(&mut self) -> Result<&mut Self, ParserError> {
+        let context = TemplateContext::new()
+            .with_frontmatter(&self.frontmatter)
+            .with_darkmatter(&self.darkmatter)
+            .with_route(self.route);
+        let (content, diagnostics) =
+            Templates::new(&self.config).interpolate(&self.markdown.content(), &context);
+        for diagnostic in diagnostics {
+            warn!("{}: {}", self.id, diagnostic);
+        }
+        self.markdown.mutate(&content);
+
+        Ok(self)
+    }
+
+    pub fn next_stage(self) -> Result<Pipeline<'a, ParseHtml>, ParserError> {
         Pipeline::try_from(self)
     }
 }
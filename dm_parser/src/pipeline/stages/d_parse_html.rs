@@ -2,9 +2,12 @@ use crate::{
     errors::parser_err::ParserError,
     models::{
         darkmatter::{Darkmatter, DmWhileParsing},
+        dependency_graph::DependencyGraph,
+        external_links::ExternalLinkChecker,
         frontmatter::Frontmatter,
         html::HtmlContent,
         markdown::MarkdownContent,
+        route_map::RouteMap,
     },
     pipeline::{Pipeline, Stage},
 };
@@ -23,18 +26,28 @@ impl Stage for ParseHtml {
     type SFC = bool;
 }
 
-impl TryFrom<Pipeline<InitialDarkmatter>> for Pipeline<ParseHtml> {
+impl<'a> TryFrom<Pipeline<'a, InitialDarkmatter>> for Pipeline<'a, ParseHtml> {
     type Error = ParserError;
 
-    fn try_from(value: Pipeline<InitialDarkmatter>) -> Result<Self, Self::Error> {
-        todo!()
+    fn try_from(value: Pipeline<'a, InitialDarkmatter>) -> Result<Self, Self::Error> {
+        Ok(Pipeline {
+            id: value.id,
+            route: value.route,
+            config: value.config,
+            source: value.source,
+            markdown: value.markdown,
+            frontmatter: value.frontmatter,
+            darkmatter: value.darkmatter.into(),
+            html: None,
+            sfc: false,
+        })
     }
 }
 
-impl Pipeline<ParseHtml> {
+impl<'a> Pipeline<'a, ParseHtml> {
     /// The body is wrapped by a block wrapper element if so configured
-    pub fn wrap_html_body(&self) -> Self {
-        *self
+    pub fn wrap_html_body(&mut self) -> &mut Self {
+        self
     }
 
     /// Use the pulldown parser to convert markdown
@@ -43,20 +56,197 @@ impl Pipeline<ParseHtml> {
     /// such as `<h1>..<h6>`, `<a>`, etc. tags will be
     /// called to provide efficient transforms _during_
     /// the parsing process.
-    pub fn parse_to_html(&self) -> Result<Pipeline<ParseHtml>, ParserError> {
-        Ok(*self)
+    ///
+    /// The images, Iconify icons and links found while parsing are
+    /// recorded in the darkmatter so that later stages (e.g., SFC
+    /// conversion) can make use of them.
+    pub fn parse_to_html(&mut self) -> Result<&mut Self, ParserError> {
+        let html = HtmlContent::try_from(&*self)?;
+        self.darkmatter.set_images(html.images.clone());
+        self.darkmatter.set_icons(html.icons.clone());
+        self.darkmatter.set_links(html.links.clone());
+        self.html = Some(html);
+
+        Ok(self)
+    }
+
+    /// Validates the links on the page against the rest of the corpus;
+    /// the darkmatter's links are replaced by the checked links.
+    pub fn check_links(&mut self, routes: &RouteMap) -> Result<&mut Self, ParserError> {
+        if let Some(html) = &self.html {
+            self.darkmatter.set_links(routes.check_page(&self.id, html));
+        }
+
+        Ok(self)
+    }
+
+    /// Validates the external links on the page (when so configured); the
+    /// checker should be shared by every page in the build so that its
+    /// cache and per-host rate limit apply across them.
+    pub fn check_external_links(
+        &mut self,
+        checker: &ExternalLinkChecker,
+    ) -> Result<&mut Self, ParserError> {
+        let mut links = self.darkmatter.links().clone();
+        checker.check_links(&mut links);
+        self.darkmatter.set_links(links);
+
+        Ok(self)
+    }
+
+    /// Records the files (included documents and imported code) which
+    /// the page was built from so that a watcher can find the pages to
+    /// rebuild when one of them changes.
+    pub fn add_dependencies(
+        &mut self,
+        graph: &mut DependencyGraph,
+    ) -> Result<&mut Self, ParserError> {
+        if let Some(html) = &self.html {
+            graph.add_page(&self.id, html);
+        }
+
+        Ok(self)
     }
 
     /// Allows userland to hook into the darkmatter metadata
     /// generated in this initial stage prior to the HTML
     /// parsing.
-    pub fn h_initial_darkmatter(&self) -> Result<Pipeline<ParseHtml>, ParserError> {
-        Ok(*self)
+    pub fn h_initial_darkmatter(&mut self) -> Result<&mut Self, ParserError> {
+        Ok(self)
     }
 
     /// Gather the remaining darkmatter which depended on HTML parsing
     /// to be complete.
-    pub fn next_stage(&mut self) -> Result<Pipeline<FinalizeDarkmatter>, ParserError> {
-        Pipeline::try_from(*self)
+    pub fn next_stage(self) -> Result<Pipeline<'a, FinalizeDarkmatter>, ParserError> {
+        Pipeline::try_from(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::Config,
+        models::{
+            external_links::{HttpClient, HttpMethod, HttpResponse},
+            html::link::LinkStatus,
+            markdown::MarkdownContentRaw,
+        },
+        source::Source,
+    };
+
+    fn pipeline(md: &str) -> Pipeline<'static, ParseHtml> {
+        let config = Config::default();
+        let (markdown, frontmatter) = MarkdownContentRaw::new(md).parse(&config).unwrap();
+
+        Pipeline {
+            id: String::from("src/pages/index.md"),
+            route: "src/pages/index.md",
+            config,
+            source: Source::File,
+            markdown,
+            frontmatter,
+            darkmatter: Darkmatter::default(),
+            html: None,
+            sfc: false,
+        }
+    }
+
+    #[test]
+    fn parse_html_records_icons_in_darkmatter() {
+        let mut p = pipeline("# Home <mdi:home />\n\n<mdi:home /> <mdi:check class=\"ok\" />");
+        p.parse_to_html().unwrap();
+
+        let icons: Vec<&str> = p
+            .darkmatter
+            .icons()
+            .iter()
+            .map(|i| i.name.as_str())
+            .collect();
+        assert_eq!(icons, vec!["home", "check"]);
+        assert!(p.html.is_some());
+    }
+
+    #[test]
+    fn parse_html_records_images_in_darkmatter() {
+        let mut p =
+            pipeline("![logo](./logo.png \"Logo\")\n\n<div md>\n![hero](/img/hero.jpg)\n</div>\n");
+        p.parse_to_html().unwrap();
+
+        let images: Vec<&str> = p
+            .darkmatter
+            .images()
+            .iter()
+            .map(|i| i.src.as_str())
+            .collect();
+        assert_eq!(images, vec!["./logo.png", "/img/hero.jpg"]);
+    }
+
+    #[test]
+    fn parse_html_records_checked_links_in_darkmatter() {
+        let mut p = pipeline("# Home\n\n<div md>\n[top](#home) and [gone](./gone.md)\n</div>\n");
+        p.parse_to_html().unwrap();
+        assert_eq!(p.darkmatter.links().len(), 2);
+        assert!(p.darkmatter.links().iter().all(|l| l.status.is_none()));
+
+        let mut routes = RouteMap::new(&p.config);
+        routes.add_page(&p.id, p.html.as_ref().unwrap(), &p.frontmatter);
+        p.check_links(&routes).unwrap();
+
+        assert_eq!(
+            p.darkmatter.invalid_links()[0].status,
+            Some(LinkStatus::BrokenPage(String::from("/gone")))
+        );
+        assert_eq!(p.darkmatter.links()[0].status, Some(LinkStatus::Valid));
+    }
+    /// A client which finds every page except those at `/missing`
+    struct StubClient;
+
+    impl HttpClient for StubClient {
+        fn request(&self, _method: HttpMethod, url: &str) -> Result<HttpResponse, String> {
+            Ok(HttpResponse {
+                status: if url.ends_with("/missing") { 404 } else { 200 },
+                location: None,
+            })
+        }
+    }
+
+    #[test]
+    fn parse_html_records_external_link_status_in_darkmatter() {
+        let mut p = pipeline("[ok](https://acme.com) and [missing](https://acme.com/missing)");
+        p.config.features.links.validate_external_links = true;
+        p.config.features.links.external_host_interval_ms = 0;
+        let checker = ExternalLinkChecker::with_client(&p.config, Box::new(StubClient)).unwrap();
+        p.parse_to_html().unwrap();
+        p.check_external_links(&checker).unwrap();
+
+        let statuses: Vec<_> = p
+            .darkmatter
+            .links()
+            .iter()
+            .map(|l| l.status.clone())
+            .collect();
+        assert_eq!(
+            statuses,
+            vec![Some(LinkStatus::Valid), Some(LinkStatus::HttpStatus(404))]
+        );
+    }
+    #[test]
+    fn parse_html_records_dependencies() {
+        let dir = std::env::temp_dir().join("dm-stage-dependencies");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("code.ts"), "let a = 1;\n").unwrap();
+        let mut p = pipeline("<<< ./code.ts\n");
+        p.id = dir.join("page.md").to_string_lossy().to_string();
+        let mut graph = DependencyGraph::new();
+        p.parse_to_html()
+            .unwrap()
+            .add_dependencies(&mut graph)
+            .unwrap();
+
+        assert_eq!(
+            graph.dependents(&dir.join("code.ts").to_string_lossy()),
+            vec![p.id.clone()]
+        );
     }
 }
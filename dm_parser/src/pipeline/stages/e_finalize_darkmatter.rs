@@ -24,26 +24,26 @@ impl Stage for FinalizeDarkmatter {
     type SFC = bool;
 }
 
-impl TryFrom<Pipeline<ParseHtml>> for Pipeline<FinalizeDarkmatter> {
+impl<'a> TryFrom<Pipeline<'a, ParseHtml>> for Pipeline<'a, FinalizeDarkmatter> {
     type Error = ParserError;
-    fn try_from(ingress: Pipeline<ParseHtml>) -> Result<Pipeline<FinalizeDarkmatter>, ParserError> {
+    fn try_from(ingress: Pipeline<'a, ParseHtml>) -> Result<Self, ParserError> {
         todo!()
     }
 }
 
-impl Pipeline<FinalizeDarkmatter> {
-    pub fn darkmatter_metrics(&self) -> Self {
-        *self
+impl<'a> Pipeline<'a, FinalizeDarkmatter> {
+    pub fn darkmatter_metrics(&mut self) -> &mut Self {
+        self
     }
 
     /// Builds the table of contents from the `<h1>`
     /// to `<h6>` tags discovered in the document and
     /// hashes it appropriately.
-    pub fn toc(&self) -> Result<Self, ParserError> {
-        Ok(*self)
+    pub fn toc(&mut self) -> Result<&mut Self, ParserError> {
+        Ok(self)
     }
 
-    pub fn next_stage(&mut self) -> Result<Pipeline<FinalizeHtml>, ParserError> {
-        Pipeline::try_from(*self)
+    pub fn next_stage(self) -> Result<Pipeline<'a, FinalizeHtml>, ParserError> {
+        Pipeline::try_from(self)
     }
 }
@@ -24,16 +24,14 @@ impl Stage for FinalizeHtml {
     type SFC = bool;
 }
 
-impl TryFrom<Pipeline<FinalizeDarkmatter>> for Pipeline<FinalizeHtml> {
+impl<'a> TryFrom<Pipeline<'a, FinalizeDarkmatter>> for Pipeline<'a, FinalizeHtml> {
     type Error = ParserError;
-    fn try_from(
-        ingress: Pipeline<FinalizeDarkmatter>,
-    ) -> Result<Pipeline<FinalizeHtml>, ParserError> {
+    fn try_from(ingress: Pipeline<'a, FinalizeDarkmatter>) -> Result<Self, ParserError> {
         todo!()
     }
 }
 
-impl Pipeline<FinalizeHtml> {
+impl<'a> Pipeline<'a, FinalizeHtml> {
     /// Provides access to the HTML body at large and allows mutations
     ///
     /// Note: this is _after_ all the parsing hooks were executed and
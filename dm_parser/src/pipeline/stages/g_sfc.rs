@@ -8,7 +8,7 @@ use crate::{
         markdown::MarkdownContent,
         sfc::Sfc,
     },
-    pipeline::Stage,
+    pipeline::{Pipeline, Stage},
 };
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -21,3 +21,12 @@ impl Stage for SfcConversion {
     type HTML = HtmlContent;
     type SFC = Sfc;
 }
+
+impl<'a> Pipeline<'a, SfcConversion> {
+    /// Imports an `unplugin-icons` component into the setup script
+    /// for each of the icons recorded in the darkmatter
+    pub fn icon_imports(&mut self) -> &mut Self {
+        self.sfc.add_icon_imports(self.darkmatter.icons());
+        self
+    }
+}
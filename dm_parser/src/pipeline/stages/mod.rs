@@ -1,7 +1,7 @@
 pub mod a_initialize;
 pub mod b_parse_raw_md;
-// pub mod c_initial_darkmatter;
-// pub mod d_parse_html;
-// pub mod e_finalize_darkmatter;
-// pub mod f_finalize_html;
-// pub mod g_sfc;
+pub mod c_initial_darkmatter;
+pub mod d_parse_html;
+pub mod e_finalize_darkmatter;
+pub mod f_finalize_html;
+pub mod g_sfc;
@@ -0,0 +1,122 @@
+use serde::{Deserialize, Serialize};
+use syntect::util::LinesWithEndings;
+
+/// What a line of code has been marked as; either by the `+`/`-` at the
+/// start of a line in a `diff-{lang}` code block or by a comment such as
+/// `// [!code ++]` at the end of the line
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum LineAnnotation {
+    /// `+` or `[!code ++]`
+    Added,
+    /// `-` or `[!code --]`
+    Removed,
+    /// `[!code focus]`
+    Focus,
+    /// `[!code error]`
+    Error,
+    /// `[!code warning]`
+    Warning,
+}
+
+impl LineAnnotation {
+    /// the classes added to an annotated line
+    pub fn class(&self) -> &'static str {
+        match self {
+            LineAnnotation::Added => "diff add",
+            LineAnnotation::Removed => "diff remove",
+            LineAnnotation::Focus => "focused",
+            LineAnnotation::Error => "highlighted error",
+            LineAnnotation::Warning => "highlighted warning",
+        }
+    }
+
+    fn from_marker(marker: &str) -> Option<Self> {
+        match marker {
+            "++" => Some(LineAnnotation::Added),
+            "--" => Some(LineAnnotation::Removed),
+            "focus" => Some(LineAnnotation::Focus),
+            "error" => Some(LineAnnotation::Error),
+            "warning" => Some(LineAnnotation::Warning),
+            _ => None,
+        }
+    }
+}
+
+/// The characters which can start the comment a `[!code ...]` marker is in
+const COMMENT_OPENERS: [&str; 7] = ["<!--", "//", "/*", "--", "#", ";", "%"];
+
+/// Takes the annotations out of the code; returning the code without them
+/// and the (1-based) line numbers which were annotated.
+///
+/// A marker can be followed by a count -- `// [!code focus:3]` -- to
+/// annotate that many lines starting with the marker's line. When `diff`
+/// is set the `+` or `-` at the start of a line is taken out too (as is
+/// the space at the start of unchanged lines when every line has one of
+/// the three).
+pub(crate) fn strip_annotations(
+    code: &str,
+    diff: bool,
+) -> (String, Vec<(usize, LineAnnotation)>) {
+    let unified = diff
+        && LinesWithEndings::from(code)
+            .all(|l| l.trim_end().is_empty() || l.starts_with(['+', '-', ' ']));
+    let mut stripped = String::with_capacity(code.len());
+    let mut annotations = vec![];
+
+    for (i, line) in LinesWithEndings::from(code).enumerate() {
+        let number = i + 1;
+        let mut line = line;
+        if diff {
+            if let Some(rest) = line.strip_prefix('+') {
+                annotations.push((number, LineAnnotation::Added));
+                line = rest;
+            } else if let Some(rest) = line.strip_prefix('-') {
+                annotations.push((number, LineAnnotation::Removed));
+                line = rest;
+            } else if unified {
+                line = line.strip_prefix(' ').unwrap_or(line);
+            }
+        }
+
+        match marker(line) {
+            Some((text, annotation, count)) => {
+                for n in number..number + count {
+                    annotations.push((n, annotation));
+                }
+                stripped.push_str(text);
+                stripped.push_str(
+                    &line[line.trim_end_matches(['\n', '\r']).len()..],
+                );
+            }
+            None => stripped.push_str(line),
+        }
+    }
+
+    (stripped, annotations)
+}
+
+/// The text of a line which ends in a `[!code ...]` comment (without the
+/// comment) along with the annotation and how many lines it applies to
+fn marker(line: &str) -> Option<(&str, LineAnnotation, usize)> {
+    let text = line.trim_end();
+    let start = text.rfind("[!code ")?;
+    let end = start + text[start..].find(']')?;
+    let closer = text[end + 1..].trim();
+    if !["", "*/", "-->"].contains(&closer) {
+        return None;
+    }
+
+    let (marker, count) = match text[start + 7..end].trim().split_once(':') {
+        Some((marker, count)) => (marker, count.trim().parse().ok()?),
+        None => (text[start + 7..end].trim(), 1),
+    };
+    let annotation = LineAnnotation::from_marker(marker)?;
+    let before = text[..start].trim_end();
+    let opener = COMMENT_OPENERS.iter().find(|o| before.ends_with(*o))?;
+
+    Some((
+        before[..before.len() - opener.len()].trim_end(),
+        annotation,
+        count.max(1),
+    ))
+}
@@ -0,0 +1,287 @@
+use serde::{Deserialize, Serialize};
+use strum_macros::Display;
+
+use super::grammar::{syntax_set, Grammar};
+
+/// How the language of a code block was worked out
+#[derive(
+    Debug, Serialize, Deserialize, Display, Clone, Copy, PartialEq, Eq,
+)]
+#[serde(rename_all = "camelCase")]
+pub enum DetectionMethod {
+    /// the code block's language was given
+    Language,
+    /// from the extension of the code block's filename
+    Filename,
+    /// a grammar recognized the first line (a shebang, `<?php`, `<?xml`,
+    /// etc.)
+    FirstLine,
+    /// the code looks more like this language than any other
+    Content,
+}
+
+/// The language chosen for a code block along with how sure we are of it
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct DetectedLanguage {
+    /// the name of the grammar in the syntax set
+    pub grammar: String,
+    pub method: DetectionMethod,
+    /// between `0.0` and `1.0`
+    pub confidence: f32,
+}
+
+impl DetectedLanguage {
+    fn new(grammar: &str, method: DetectionMethod, confidence: f32) -> Self {
+        DetectedLanguage {
+            grammar: grammar.to_string(),
+            method,
+            confidence,
+        }
+    }
+}
+
+/// The snippets of code which hint at a language and how strongly they do
+/// so; each language is looked up with `Grammar::get` so the aliases apply
+const SIGNALS: &[(&str, &[(&str, u32)])] = &[
+    (
+        "rust",
+        &[
+            ("fn ", 2),
+            ("let mut ", 3),
+            ("impl ", 2),
+            ("pub fn ", 3),
+            ("use std::", 3),
+            ("println!", 3),
+            ("&self", 3),
+            ("#[derive", 3),
+            ("-> ", 1),
+            ("::", 1),
+        ],
+    ),
+    (
+        "js",
+        &[
+            ("function ", 2),
+            ("const ", 1),
+            ("=> ", 2),
+            ("console.log", 3),
+            ("require(", 3),
+            ("export ", 1),
+            ("===", 3),
+            ("document.", 3),
+        ],
+    ),
+    (
+        "ts",
+        &[
+            ("interface ", 2),
+            (": string", 3),
+            (": number", 3),
+            (": boolean", 3),
+            ("import type ", 3),
+            ("as const", 3),
+            ("readonly ", 2),
+        ],
+    ),
+    (
+        "python",
+        &[
+            ("def ", 2),
+            ("):\n", 2),
+            ("self.", 2),
+            ("elif ", 3),
+            ("print(", 2),
+            ("__init__", 3),
+            ("import ", 1),
+            ("None", 1),
+        ],
+    ),
+    (
+        "go",
+        &[
+            ("package ", 3),
+            ("func ", 3),
+            (":= ", 3),
+            ("fmt.", 3),
+            ("chan ", 2),
+        ],
+    ),
+    (
+        "java",
+        &[
+            ("public class ", 3),
+            ("public static void", 3),
+            ("System.out", 3),
+            ("@Override", 3),
+            ("import java", 3),
+            ("private ", 1),
+        ],
+    ),
+    (
+        "c",
+        &[
+            ("#include", 3),
+            ("int main", 3),
+            ("printf(", 2),
+            ("malloc(", 3),
+            ("NULL", 2),
+        ],
+    ),
+    (
+        "cpp",
+        &[
+            ("std::", 3),
+            ("cout", 2),
+            ("template<", 3),
+            ("nullptr", 3),
+            ("namespace ", 2),
+        ],
+    ),
+    (
+        "cs",
+        &[
+            ("using System", 3),
+            ("Console.Write", 3),
+            ("{ get; set; }", 3),
+            ("namespace ", 1),
+        ],
+    ),
+    (
+        "php",
+        &[
+            ("<?php", 5),
+            ("$this->", 3),
+            ("echo ", 1),
+            ("=> $", 2),
+        ],
+    ),
+    (
+        "ruby",
+        &[
+            ("puts ", 3),
+            ("require '", 3),
+            ("attr_accessor", 3),
+            ("do |", 3),
+            ("end\n", 1),
+            ("def ", 1),
+        ],
+    ),
+    (
+        "bash",
+        &[
+            ("echo ", 2),
+            ("$(", 2),
+            ("fi\n", 3),
+            ("then\n", 2),
+            ("sudo ", 3),
+            ("npm ", 2),
+            ("cargo ", 2),
+            ("cd ", 2),
+            ("export ", 1),
+        ],
+    ),
+    (
+        "sql",
+        &[
+            ("SELECT ", 3),
+            ("FROM ", 2),
+            ("WHERE ", 2),
+            ("INSERT INTO ", 3),
+            ("CREATE TABLE ", 3),
+            ("JOIN ", 2),
+        ],
+    ),
+    (
+        "html",
+        &[
+            ("<!DOCTYPE", 3),
+            ("<html", 3),
+            ("<div", 2),
+            ("<p>", 2),
+            ("class=\"", 2),
+            ("</", 1),
+        ],
+    ),
+    (
+        "css",
+        &[
+            ("px;", 3),
+            ("color:", 2),
+            ("margin:", 2),
+            ("display:", 2),
+            ("@media", 3),
+            ("!important", 3),
+        ],
+    ),
+    (
+        "markdown",
+        &[
+            ("\n# ", 2),
+            ("\n## ", 2),
+            ("](", 2),
+            ("**", 1),
+            ("```", 3),
+        ],
+    ),
+];
+
+/// No language is chosen from the code's content unless it scores at
+/// least this much
+const MIN_SCORE: u32 = 4;
+
+/// Works out the language of code which has no language or filename to go
+/// on; first by the grammars which recognize a first line (shebangs,
+/// `<?php`, `<?xml`, etc.) and then by looking for snippets of code which
+/// are typical of the common languages.
+pub fn detect_code_language(code: &str) -> Option<DetectedLanguage> {
+    let first_line = code.lines().find(|l| !l.trim().is_empty())?;
+    if let Some(syntax) = syntax_set().find_syntax_by_first_line(first_line) {
+        return Some(DetectedLanguage::new(
+            &syntax.name,
+            DetectionMethod::FirstLine,
+            0.95,
+        ));
+    }
+
+    let mut scores: Vec<(&str, u32)> = SIGNALS
+        .iter()
+        .map(|(language, signals)| {
+            let score = signals
+                .iter()
+                .map(|(signal, weight)| {
+                    // a signal counts for at most three of its occurrences
+                    code.matches(signal).count().min(3) as u32 * weight
+                })
+                .sum();
+            (*language, score)
+        })
+        .collect();
+    if is_json(code) {
+        scores.push(("json", 2 * MIN_SCORE));
+    }
+    scores.sort_by_key(|(_, score)| std::cmp::Reverse(*score));
+
+    let total: u32 = scores.iter().map(|(_, score)| score).sum();
+    let (language, best) = scores.first().copied()?;
+    if best < MIN_SCORE {
+        return None;
+    }
+    // how far ahead of the other languages the best one is, discounted
+    // when there is little to go on
+    let lead = best as f32 / total as f32;
+    let evidence = (best as f32 / (3 * MIN_SCORE) as f32).min(1.0);
+    let confidence = (lead * evidence * 100.0).round() / 100.0;
+
+    Grammar::get(language).map(|g| {
+        DetectedLanguage::new(&g.name, DetectionMethod::Content, confidence)
+    })
+}
+
+/// Whether the code is a JSON object or array
+fn is_json(code: &str) -> bool {
+    let code = code.trim();
+    let wrapped = (code.starts_with('{') && code.ends_with('}'))
+        || (code.starts_with('[') && code.ends_with(']'));
+
+    wrapped && code.contains("\":") && !code.contains(';')
+}
@@ -1,4 +1,4 @@
-use syntect::Error as SyntectError;
+use syntect::{Error as SyntectError, LoadingError};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -7,4 +7,10 @@ pub enum CodeBlockError {
     InvalidLanguage(String),
     #[error("Problem parsing the CodeBlock content into to HTML.")]
     Parsing(#[from] SyntectError),
+    #[error("Failed to load the syntax definitions in [{0}]: {1}")]
+    SyntaxLoading(String, LoadingError),
+    #[error("The theme [{0}] is not one of the available themes!")]
+    InvalidTheme(String),
+    #[error("Failed to load the themes in [{0}]: {1}")]
+    ThemeLoading(String, LoadingError),
 }
@@ -0,0 +1,260 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+use syntect::dumps::{dump_to_file, from_dump_file};
+use syntect::parsing::{SyntaxReference, SyntaxSet};
+use tracing::{info, instrument, warn};
+
+use super::errors::CodeBlockError;
+
+/// The syntax set used for highlighting; `None` until it is first used
+/// or until custom grammars are registered with `use_syntaxes`
+static SYNTAX: RwLock<Option<Arc<SyntaxSet>>> = RwLock::new(None);
+
+/// Aliases added from configuration; these are checked before `ALIASES`
+static CUSTOM_ALIASES: RwLock<Vec<(String, String)>> = RwLock::new(Vec::new());
+
+/// Maps the names people write in a code fence to the names of syntect
+/// syntaxes. Where more than one syntax is listed the first one found in
+/// the syntax set wins so that -- for instance -- `ts` is highlighted as
+/// TypeScript when a TypeScript grammar has been loaded and falls back to
+/// JavaScript when it hasn't.
+const ALIASES: &[(&str, &[&str])] = &[
+    ("js", &["JavaScript"]),
+    ("mjs", &["JavaScript"]),
+    ("cjs", &["JavaScript"]),
+    ("jsx", &["JavaScript (Babel)", "JSX", "JavaScript"]),
+    ("ts", &["TypeScript", "JavaScript"]),
+    ("mts", &["TypeScript", "JavaScript"]),
+    ("cts", &["TypeScript", "JavaScript"]),
+    ("typescript", &["TypeScript", "JavaScript"]),
+    ("tsx", &["TypeScriptReact", "TSX", "JavaScript"]),
+    ("vue", &["Vue Component", "Vue", "HTML"]),
+    ("svelte", &["Svelte", "HTML"]),
+    ("toml", &["TOML"]),
+    ("dockerfile", &["Dockerfile"]),
+    ("docker", &["Dockerfile"]),
+    ("kotlin", &["Kotlin"]),
+    ("kt", &["Kotlin"]),
+    ("kts", &["Kotlin"]),
+    ("swift", &["Swift"]),
+    ("c++", &["C++"]),
+    ("cpp", &["C++"]),
+    ("cc", &["C++"]),
+    ("cxx", &["C++"]),
+    ("cs", &["C#"]),
+    ("csharp", &["C#"]),
+    ("objc", &["Objective-C"]),
+    ("md", &["Markdown"]),
+    ("mdx", &["Markdown"]),
+    ("sh", &["Bourne Again Shell (bash)"]),
+    ("bash", &["Bourne Again Shell (bash)"]),
+    ("zsh", &["Bourne Again Shell (bash)"]),
+    ("nsh", &["Bourne Again Shell (bash)"]),
+    ("shell", &["Bourne Again Shell (bash)"]),
+    ("shellscript", &["Bourne Again Shell (bash)"]),
+    ("regex", &["Regular Expression"]),
+    ("regexp", &["Regular Expression"]),
+    ("re", &["Regular Expression"]),
+    ("py", &["Python"]),
+    ("rb", &["Ruby"]),
+    ("rs", &["Rust"]),
+    ("yml", &["YAML"]),
+    ("golang", &["Go"]),
+    ("pl", &["Perl"]),
+    ("tex", &["LaTeX"]),
+    ("graphviz", &["Graphviz (DOT)"]),
+    ("dot", &["Graphviz (DOT)"]),
+    ("batchfile", &["Batch File"]),
+    ("bat", &["Batch File"]),
+    ("rails", &["Ruby on Rails"]),
+    ("text", &["Plain Text"]),
+    ("txt", &["Plain Text"]),
+    ("plain", &["Plain Text"]),
+    ("binary", &["Plain Text"]),
+];
+
+/// The syntax set which code is highlighted with; unless custom grammars
+/// have been registered this is syntect's default set
+pub fn syntax_set() -> Arc<SyntaxSet> {
+    if let Some(set) = SYNTAX.read().unwrap().as_ref() {
+        return set.clone();
+    }
+    let mut syntax = SYNTAX.write().unwrap();
+
+    syntax
+        .get_or_insert_with(|| Arc::new(SyntaxSet::load_defaults_newlines()))
+        .clone()
+}
+
+/// Replaces the syntax set which code is highlighted with (usually with
+/// one from `load_syntaxes`)
+pub fn use_syntaxes(set: SyntaxSet) {
+    *SYNTAX.write().unwrap() = Some(Arc::new(set));
+}
+
+/// Adds aliases -- from what is written in a code fence to the name of a
+/// syntax -- which take precedence over the built-in aliases
+pub fn add_aliases(aliases: &HashMap<String, String>) {
+    let mut custom = CUSTOM_ALIASES.write().unwrap();
+    for (alias, name) in aliases {
+        let alias = alias.to_lowercase();
+        custom.retain(|(a, _)| a != &alias);
+        custom.push((alias, name.to_string()));
+    }
+}
+
+/// Builds a syntax set from syntect's defaults plus the `.sublime-syntax`
+/// files found in `dirs` (and their sub-directories).
+///
+/// When a `dump` file is given it is used in place of compiling the
+/// grammars as long as it is newer than all of the grammar files; otherwise
+/// the grammars are compiled and the dump is (re)written for next time. Not
+/// being able to write the dump is only a warning as the compiled grammars
+/// can still be used.
+#[instrument(level = "info")]
+pub fn load_syntaxes(
+    dirs: &[PathBuf],
+    dump: Option<&Path>,
+) -> Result<SyntaxSet, CodeBlockError> {
+    if let Some(dump) = dump {
+        let dumped = fs::metadata(dump).and_then(|m| m.modified()).ok();
+        if let Some(dumped) = dumped {
+            if newest_grammar(dirs).is_none_or(|newest| newest <= dumped) {
+                match from_dump_file(dump) {
+                    Ok(set) => return Ok(set),
+                    Err(e) => {
+                        warn!("ignoring the syntax dump {:?}: {}", dump, e)
+                    }
+                }
+            }
+        }
+    }
+
+    let mut builder = SyntaxSet::load_defaults_newlines().into_builder();
+    for dir in dirs {
+        builder.add_from_folder(dir, true).map_err(|e| {
+            CodeBlockError::SyntaxLoading(dir.to_string_lossy().to_string(), e)
+        })?;
+    }
+    let set = builder.build();
+    info!("loaded {} syntaxes", set.syntaxes().len());
+
+    if let Some(dump) = dump {
+        if let Some(dir) = dump.parent().filter(|d| !d.as_os_str().is_empty()) {
+            let _ = fs::create_dir_all(dir);
+        }
+        if let Err(e) = dump_to_file(&set, dump) {
+            warn!("unable to write the syntax dump {:?}: {}", dump, e);
+        }
+    }
+
+    Ok(set)
+}
+
+/// The most recent modification time of the `.sublime-syntax` files in
+/// the directories
+fn newest_grammar(dirs: &[PathBuf]) -> Option<SystemTime> {
+    let mut newest = None;
+    let mut pending = dirs.to_vec();
+    while let Some(dir) = pending.pop() {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for path in entries.flatten().map(|e| e.path()) {
+            if path.is_dir() {
+                pending.push(path);
+            } else if path.extension().is_some_and(|e| e == "sublime-syntax") {
+                let modified =
+                    fs::metadata(&path).and_then(|m| m.modified()).ok();
+                newest = newest.max(modified);
+            }
+        }
+    }
+
+    newest
+}
+
+/// a `Grammar` is an acronym for what Sublime Text calls a Package and
+/// what Syntect calls a Language. Ultimately it represents the language
+/// parsers that can be used for code blocks.
+///
+/// - [Packages on SublimeHQ](https://github.com/sublimehq/Packages)
+/// - [Syntax Definitions](https://www.sublimetext.com/docs/syntax.html#ver-dev)
+/// - [Scope Naming](https://www.sublimetext.com/docs/scope_naming.html)
+/// - [Color Schemes](https://www.sublimetext.com/docs/scope_naming.html#color-schemes)
+#[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Clone)]
+pub struct Grammar {
+    /// the name of the syntax in the syntax set
+    pub name: String,
+}
+
+impl Grammar {
+    /// Gets a language grammar from a string representation of the
+    /// language. The matching is case insensitive and checks -- in order --
+    /// the configured aliases, the built-in aliases and then the names and
+    /// file extensions of the syntaxes in the syntax set.
+    #[instrument(level = "info")]
+    pub fn get(language: &str) -> Option<Grammar> {
+        let language = language.to_lowercase();
+        let set = syntax_set();
+        let by_name = |name: &str| {
+            set.syntaxes()
+                .iter()
+                .rev()
+                .find(|s| s.name.eq_ignore_ascii_case(name))
+                .map(|s| Grammar {
+                    name: s.name.to_string(),
+                })
+        };
+
+        let custom = CUSTOM_ALIASES
+            .read()
+            .unwrap()
+            .iter()
+            .find(|(alias, _)| alias == &language)
+            .map(|(_, name)| name.to_string());
+        if let Some(grammar) = custom.and_then(|name| by_name(&name)) {
+            return Some(grammar);
+        }
+        if let Some((_, names)) =
+            ALIASES.iter().find(|(alias, _)| *alias == language)
+        {
+            if let Some(grammar) = names.iter().find_map(|name| by_name(name)) {
+                return Some(grammar);
+            }
+        }
+
+        by_name(&language).or_else(|| {
+            set.find_syntax_by_extension(&language).map(|s| Grammar {
+                name: s.name.to_string(),
+            })
+        })
+    }
+
+    /// outputs the grammar as a `SyntaxReference` which **syntect** expects
+    #[instrument(level = "info")]
+    pub fn as_syntax_ref(&self) -> SyntaxReference {
+        let set = syntax_set();
+
+        set.find_syntax_by_name(&self.name)
+            .unwrap_or_else(|| set.find_syntax_plain_text())
+            .clone()
+    }
+}
+
+impl std::fmt::Display for Grammar {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+impl From<Grammar> for String {
+    fn from(grammar: Grammar) -> String {
+        grammar.name
+    }
+}
@@ -0,0 +1,174 @@
+use serde::{Deserialize, Serialize};
+use syntect::html::{line_tokens_to_classed_spans, ClassStyle};
+use syntect::parsing::{
+    ParseState, ScopeStack, ScopeStackOp, SyntaxReference, SyntaxSet,
+};
+use syntect::util::LinesWithEndings;
+
+use super::annotations::LineAnnotation;
+use super::errors::CodeBlockError;
+
+/// The classes which are applied to each line of a code block
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum LineClassStrategy {
+    /// lines are only wrapped when they need to be highlighted
+    None,
+    /// `line`, `line-#` and `odd` or `even`
+    Basic,
+    /// the classes of `Basic` along with a `has-{kind}` class for each
+    /// kind of token (`has-keyword`, `has-comment`, etc.) on the line
+    Full,
+}
+
+/// How the lines of a code block are wrapped when converted to HTML
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct LineOptions {
+    pub strategy: LineClassStrategy,
+    /// whether each line starts with its line number
+    pub line_numbers: bool,
+    /// the (1-based and inclusive) ranges of lines to highlight
+    pub highlight: Vec<(usize, usize)>,
+    /// the (1-based) lines which were annotated as added, removed,
+    /// focused, etc.
+    pub annotations: Vec<(usize, LineAnnotation)>,
+}
+
+impl Default for LineOptions {
+    fn default() -> Self {
+        LineOptions {
+            strategy: LineClassStrategy::None,
+            line_numbers: false,
+            highlight: vec![],
+            annotations: vec![],
+        }
+    }
+}
+
+impl LineOptions {
+    /// Whether the lines need to be wrapped at all
+    pub fn wraps_lines(&self) -> bool {
+        self.strategy != LineClassStrategy::None
+            || self.line_numbers
+            || !self.highlight.is_empty()
+            || !self.annotations.is_empty()
+    }
+
+    pub fn is_highlighted(&self, line: usize) -> bool {
+        self.highlight.iter().any(|(from, to)| (*from..=*to).contains(&line))
+    }
+
+    fn classes(&self, line: usize, kinds: &[String]) -> String {
+        let mut classes = vec![];
+        if self.strategy != LineClassStrategy::None {
+            classes.push(String::from("line"));
+            classes.push(format!("line-{}", line));
+            classes.push(String::from(if line % 2 == 1 {
+                "odd"
+            } else {
+                "even"
+            }));
+        }
+        if self.strategy == LineClassStrategy::Full {
+            classes.extend(kinds.iter().map(|k| format!("has-{}", k)));
+        }
+        if self.is_highlighted(line) {
+            classes.push(String::from("highlight"));
+        }
+        for (_, annotation) in
+            self.annotations.iter().filter(|(l, _)| *l == line)
+        {
+            classes.push(annotation.class().to_string());
+        }
+        if classes.is_empty() {
+            classes.push(String::from("line"));
+        }
+
+        classes.join(" ")
+    }
+}
+
+/// Converts code to classed HTML where every line is wrapped in its own
+/// `<span>`; syntax scopes which carry over from one line to the next are
+/// closed at the end of the line and opened again on the next.
+pub(crate) fn classed_lines(
+    code: &str,
+    syntax: &SyntaxReference,
+    syntax_set: &SyntaxSet,
+    options: &LineOptions,
+) -> Result<String, CodeBlockError> {
+    let mut state = ParseState::new(syntax);
+    let mut stack = ScopeStack::new();
+    let mut html = String::with_capacity(code.len() * 4);
+
+    for (i, line) in LinesWithEndings::from(code).enumerate() {
+        let number = i + 1;
+        let ops =
+            state.parse_line(line, syntax_set).map_err(syntect::Error::from)?;
+        let text = line.trim_end_matches(['\n', '\r']);
+        // operations at the line ending are applied at the end of the text
+        let ops: Vec<(usize, ScopeStackOp)> =
+            ops.into_iter().map(|(i, op)| (i.min(text.len()), op)).collect();
+
+        let mut reopened = String::new();
+        for scope in stack.as_slice() {
+            reopened.push_str("<span class=\"");
+            reopened.push_str(&scope.build_string().replace('.', " "));
+            reopened.push_str("\">");
+        }
+        let kinds = token_kinds(&stack, &ops);
+        let open = stack.len() as isize;
+        let (spans, delta) = line_tokens_to_classed_spans(
+            text,
+            &ops,
+            ClassStyle::Spaced,
+            &mut stack,
+        )?;
+
+        html.push_str(&format!(
+            "<span class=\"{}\">",
+            options.classes(number, &kinds)
+        ));
+        if options.line_numbers {
+            html.push_str(&format!(
+                "<span class=\"line-number\">{}</span>",
+                number
+            ));
+        }
+        html.push_str(&reopened);
+        html.push_str(&spans);
+        html.push_str(&"</span>".repeat((open + delta).max(0) as usize));
+        html.push_str("</span>\n");
+    }
+
+    Ok(html)
+}
+
+/// The kinds of tokens (the first part of their scope, such as `keyword`
+/// for `keyword.control.rust`) found on a line
+fn token_kinds(
+    stack: &ScopeStack,
+    ops: &[(usize, ScopeStackOp)],
+) -> Vec<String> {
+    let pushed = ops.iter().filter_map(|(_, op)| match op {
+        ScopeStackOp::Push(scope) => Some(*scope),
+        _ => None,
+    });
+    let mut kinds: Vec<String> = stack
+        .as_slice()
+        .iter()
+        .copied()
+        .chain(pushed)
+        .filter_map(|scope| {
+            let name = scope.build_string();
+            let kind = name.split('.').next().unwrap_or_default().to_string();
+            match kind.as_str() {
+                "source" | "text" | "meta" | "" => None,
+                _ => Some(kind),
+            }
+        })
+        .collect();
+    kinds.sort();
+    kinds.dedup();
+
+    kinds
+}
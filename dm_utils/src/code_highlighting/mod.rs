@@ -1,21 +1,37 @@
+mod annotations;
+mod detection;
 pub mod errors;
+mod grammar;
+mod lines;
+mod themes;
 
 use crate::hasher;
 use serde::{Deserialize, Serialize};
 use slugify::slugify;
-use std::collections::HashMap;
-use strum::IntoEnumIterator;
-use strum_macros::{Display, EnumIter};
 use syntect::easy::HighlightLines;
-use syntect::highlighting::{Style, Theme, ThemeSet};
+use syntect::highlighting::{Style, Theme};
 use syntect::html::{
     css_for_theme_with_class_style, ClassStyle, ClassedHTMLGenerator,
 };
-use syntect::parsing::{SyntaxReference, SyntaxSet};
+use syntect::parsing::SyntaxReference;
 use syntect::util::{as_24_bit_terminal_escaped, LinesWithEndings};
 use tracing::instrument;
 
+use self::annotations::strip_annotations;
+pub use self::annotations::LineAnnotation;
+pub use self::detection::{
+    detect_code_language, DetectedLanguage, DetectionMethod,
+};
 use self::errors::CodeBlockError;
+pub use self::grammar::{
+    add_aliases, load_syntaxes, syntax_set, use_syntaxes, Grammar,
+};
+use self::lines::classed_lines;
+pub use self::themes::{
+    find_theme, load_themes, stylesheet, theme_set, use_themes,
+    DarkModeStrategy, ThemeChoices,
+};
+pub use self::lines::{LineClassStrategy, LineOptions};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct DarkModeCache {
@@ -53,171 +69,6 @@ impl Default for DarkModeCache {
     }
 }
 
-thread_local! {
-    static SYNTAX: SyntaxSet = SyntaxSet::load_defaults_newlines();
-    static THEMES: ThemeSet = ThemeSet::load_defaults();
-}
-
-#[derive(
-    Debug, Serialize, Deserialize, Display, EnumIter, Eq, PartialEq, Clone,
-)]
-pub enum ThemeChoices {
-    InspiredGithub,
-    #[serde(rename = "Solarized (dark)")]
-    SolarizedDark,
-    #[serde(rename = "Solarized (light)")]
-    SolarizedLight,
-    #[serde(rename = "base16-eighties.dark")]
-    Base16EightiesDark,
-    #[serde(rename = "base16-mocha.dark")]
-    Base16MochaDark,
-    #[serde(rename = "base16-ocean.dark")]
-    Base16OceanDark,
-    #[serde(rename = "base16-ocean.light")]
-    Base16OceanLight,
-}
-
-/// a `Grammar` is an acronym for what Sublime Text calls a Package and
-/// what Syntect calls a Language. Ultimately it represents the language
-/// parsers that can be used for code blocks.
-///
-/// - [Packages on SublimeHQ](https://github.com/sublimehq/Packages)
-/// - [Syntax Definitions](https://www.sublimetext.com/docs/syntax.html#ver-dev)
-/// - [Scope Naming](https://www.sublimetext.com/docs/scope_naming.html)
-/// - [Color Schemes](https://www.sublimetext.com/docs/scope_naming.html#color-schemes)
-#[derive(
-    Debug, Serialize, Deserialize, Display, EnumIter, Eq, PartialEq, Clone,
-)]
-pub enum Grammar {
-    ASP,
-    ActionScript,
-    AppleScript,
-    BatchFile,
-    Binary,
-    #[serde(rename = "C#")]
-    CSharp,
-    #[serde(rename = "C / C++")]
-    C,
-    CSS,
-    Clojure,
-    D,
-    Diff,
-    Erlang,
-    #[serde(rename = "Git Formats")]
-    GitFormats,
-    Go,
-    Graphviz,
-    Groovy,
-    HTML,
-    #[serde(rename = "HTML (TCL)")]
-    HtmlTCL,
-    #[serde(rename = "HTML (ASP)")]
-    HtmlASP,
-    #[serde(rename = "HTML (Erlang)")]
-    HtmlErlang,
-    #[serde(rename = "HTML (Rails)")]
-    HtmlRails,
-    Haskell,
-    JSON,
-    Java,
-    JavaScript,
-    LaTeX,
-    Lisp,
-    Lua,
-    Makefile,
-    Markdown,
-    MultiMarkdown,
-    Matlab,
-    OCaml,
-    #[serde(rename = "Objective-C")]
-    ObjectiveC,
-    PHP,
-    // Pascal,
-    Perl,
-    Python,
-    R,
-    Rails,
-    #[serde(rename = "Regular Expression")]
-    RegExp,
-    #[serde(rename = "reStructuredText")]
-    RestructuredText,
-    Ruby,
-    #[serde(rename = "Ruby on Rails")]
-    RubyOnRails,
-    Rust,
-    SQL,
-    #[serde(rename = "SQL (Rails)")]
-    SqlRails,
-    Scala,
-    #[serde(rename = "Bourne Again Shell (bash)")]
-    Bash,
-    ShellScript,
-    TCL,
-    Text,
-    Textile,
-    XML,
-    YAML,
-}
-
-impl Grammar {
-    /// Gets a language grammar from a string representation of the
-    /// language. The matching is case insensitive and also maps some
-    /// common abbreviations to the formal name.
-    #[instrument(level = "info")]
-    pub fn get(language: &str) -> Option<Grammar> {
-        let language = language.to_string().to_lowercase();
-        for grammar in Grammar::iter() {
-            let lc = format!("{}", grammar).to_lowercase();
-            if lc.eq(&language) {
-                return Some(grammar);
-            }
-        }
-        let mut abbr: HashMap<&str, Grammar> = HashMap::new();
-        // shorthands
-        abbr.insert("js", Grammar::JavaScript);
-        abbr.insert("ts", Grammar::JavaScript);
-        abbr.insert("typescript", Grammar::JavaScript);
-        abbr.insert("cpp", Grammar::C);
-        abbr.insert("c++", Grammar::C);
-        abbr.insert("md", Grammar::Markdown);
-        abbr.insert("mdx", Grammar::Markdown);
-        // shell scripts
-        abbr.insert("sh", Grammar::ShellScript);
-        abbr.insert("bash", Grammar::ShellScript);
-        abbr.insert("zsh", Grammar::ShellScript);
-        abbr.insert("nsh", Grammar::ShellScript);
-        // regular expressions
-        abbr.insert("regex", Grammar::RegExp);
-        abbr.insert("regexp", Grammar::RegExp);
-        abbr.insert("re", Grammar::RegExp);
-
-        // check against abbreviations
-        let grammar = abbr.get(language.as_str());
-        if let Some(grammar) = grammar {
-            let grammar = grammar.clone();
-            return Some(grammar);
-        }
-
-        None
-    }
-
-    /// outputs the grammar as a `SyntaxReference` which **syntect** expects
-    #[instrument(level = "info")]
-    pub fn as_syntax_ref(&self) -> SyntaxReference {
-        SYNTAX.with(|s| {
-            let language = format!("{:?}", &self);
-
-            s.find_syntax_by_name(&language).unwrap().clone()
-        })
-    }
-}
-
-impl From<Grammar> for String {
-    fn from(grammar: Grammar) -> String {
-        format!("{:?}", grammar)
-    }
-}
-
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CssInfo {
     pub filename: String,
@@ -233,6 +84,8 @@ pub struct CodeBlock {
     /// The language the code block is expected to be
     pub language: Option<String>,
     pub hash: u64,
+    /// how the lines of the code are wrapped when converted to HTML
+    pub lines: LineOptions,
     theme: (Theme, Theme),
     console_output: DarkModeCache,
     html: DarkModeCache,
@@ -240,7 +93,7 @@ pub struct CodeBlock {
 
 impl CodeBlock {
     pub fn syntaxes() -> Vec<String> {
-        SyntaxSet::load_defaults_newlines()
+        syntax_set()
             .syntaxes()
             .iter()
             .map(|i| i.name.to_string())
@@ -248,8 +101,8 @@ impl CodeBlock {
     }
 
     pub fn new(code: &str) -> Self {
-        let light = THEMES.with(|t| t.themes["base16-ocean.light"].clone());
-        let dark = THEMES.with(|t| t.themes["base16-ocean.dark"].clone());
+        let light = find_theme(ThemeChoices::Base16OceanLight.name()).unwrap();
+        let dark = find_theme(ThemeChoices::Base16OceanDark.name()).unwrap();
 
         CodeBlock {
             code: code.to_string(),
@@ -257,6 +110,7 @@ impl CodeBlock {
             filename: None,
             language: None,
             hash: hasher::hash(code, None),
+            lines: LineOptions::default(),
             console_output: DarkModeCache::new(),
             html: DarkModeCache::new(),
         }
@@ -271,14 +125,30 @@ impl CodeBlock {
     }
 
     pub fn theme_options(&self) -> Vec<String> {
-        THEMES.with(|t| {
-            let mut options: Vec<String> = vec![];
-            t.themes.iter().for_each(|(o, _)| {
-                options.push(o.clone());
-            });
+        theme_set().themes.keys().cloned().collect()
+    }
 
-            options
-        })
+    /// Sets the themes used for light and dark mode by their names in the
+    /// theme set. Throws `CodeBlockError::InvalidTheme` if either theme is
+    /// not known.
+    pub fn with_themes(
+        mut self,
+        light: &str,
+        dark: &str,
+    ) -> Result<Self, CodeBlockError> {
+        self.theme = (find_theme(light)?, find_theme(dark)?);
+        self.console_output = DarkModeCache::new();
+
+        Ok(self)
+    }
+
+    /// A single stylesheet with the rules of both the light and dark
+    /// theme; the dark rules are scoped by the `strategy`
+    pub fn stylesheet(
+        &self,
+        strategy: DarkModeStrategy,
+    ) -> Result<String, CodeBlockError> {
+        stylesheet(&self.theme.0, &self.theme.1, strategy)
     }
 
     /// Creates a new `CodeBlock` along with an explicit
@@ -306,6 +176,26 @@ impl CodeBlock {
         cb
     }
 
+    /// Sets how the lines of the code are wrapped when converted to HTML
+    pub fn with_lines(mut self, lines: LineOptions) -> Self {
+        self.lines = lines;
+        self.html = DarkModeCache::new();
+
+        self
+    }
+
+    /// Takes the `// [!code ++]` style annotations out of the code and
+    /// turns them into classes on the annotated lines; with `diff` set the
+    /// `+` and `-` at the start of lines are treated as annotations too.
+    pub fn annotate(&mut self, diff: bool) {
+        let (code, annotations) = strip_annotations(&self.code, diff);
+        if annotations.is_empty() && code == self.code {
+            return;
+        }
+        self.mutate(&code);
+        self.lines.annotations.extend(annotations);
+    }
+
     /// Will return the file extension if it can be discerned from
     /// the "filename".
     #[instrument]
@@ -334,29 +224,46 @@ impl CodeBlock {
 
     /// Returns the expected language grammar based on any/all hints available
     pub fn determine_grammar(&self) -> Option<SyntaxReference> {
+        self.detect_language().map(|detected| {
+            Grammar {
+                name: detected.grammar,
+            }
+            .as_syntax_ref()
+        })
+    }
+
+    /// Works out the language of the code -- along with how it was worked
+    /// out and how confident we are of it -- from, in order, the language
+    /// it was given, the extension of its filename, the grammars which
+    /// recognize its first line and finally its content.
+    pub fn detect_language(&self) -> Option<DetectedLanguage> {
         if let Some(language) = &self.language {
             if let Some(grammar) = Grammar::get(language) {
-                return Some(grammar.as_syntax_ref());
+                return Some(DetectedLanguage {
+                    grammar: grammar.name,
+                    method: DetectionMethod::Language,
+                    confidence: 1.0,
+                });
             }
         };
 
-        if let Some(filename) = &self.filename {
-            let extension = filename.split('.').last();
-            if let Some(extension) = extension {
-                let grammar = SYNTAX.with(|s| {
-                    let grammar = s.find_syntax_by_extension(extension);
-                    if let Some(grammar) = grammar {
-                        let grammar = grammar.clone();
-                        Some(grammar)
-                    } else {
-                        None
-                    }
+        if let Some(extension) = self.file_extension() {
+            // syntect doesn't know every extension (e.g. `ts`) so the
+            // aliases of `Grammar` are the fallback
+            let grammar = syntax_set()
+                .find_syntax_by_extension(extension)
+                .map(|s| s.name.to_string())
+                .or_else(|| Grammar::get(extension).map(|g| g.name));
+            if let Some(grammar) = grammar {
+                return Some(DetectedLanguage {
+                    grammar,
+                    method: DetectionMethod::Filename,
+                    confidence: 0.9,
                 });
-                return grammar;
             }
         };
 
-        None
+        detect_code_language(&self.code)
     }
 
     /// provides the code with color codes embedded which are
@@ -367,22 +274,22 @@ impl CodeBlock {
             return cache.to_string();
         }
 
-        let grammar = self.determine_grammar();
-        let ps = SYNTAX.with(|s| s.clone());
+        let ps = syntax_set();
+        let grammar = self
+            .determine_grammar()
+            .unwrap_or_else(|| ps.find_syntax_plain_text().clone());
         let mut code_lines: String = "".to_string();
         let theme = if *dark_mode {
             &self.theme.1
         } else {
             &self.theme.0
         };
-        if let Some(grammar) = grammar {
-            let mut h = HighlightLines::new(&grammar, theme);
-            for line in LinesWithEndings::from(&self.code) {
-                let ranges: Vec<(Style, &str)> =
-                    h.highlight_line(line, &ps).unwrap();
-                let escaped = as_24_bit_terminal_escaped(&ranges[..], true);
-                code_lines = [code_lines, escaped].join("\n").to_string();
-            }
+        let mut h = HighlightLines::new(&grammar, theme);
+        for line in LinesWithEndings::from(&self.code) {
+            let ranges: Vec<(Style, &str)> =
+                h.highlight_line(line, &ps).unwrap();
+            let escaped = as_24_bit_terminal_escaped(&ranges[..], true);
+            code_lines = [code_lines, escaped].join("\n").to_string();
         }
         // cache
         if *dark_mode {
@@ -415,7 +322,9 @@ impl CodeBlock {
         })
     }
 
-    /// provides the code as a tokenized HTML string
+    /// provides the code as a tokenized HTML string; each line is wrapped
+    /// in its own `<span>` when the `lines` options call for it and code
+    /// without a known grammar is treated as plain text
     #[instrument]
     pub fn as_html(
         &mut self,
@@ -425,10 +334,13 @@ impl CodeBlock {
             return Ok(html.to_string());
         }
 
-        let grammar = self.determine_grammar();
-        let ps = SYNTAX.with(|s| s.clone());
-        let mut code_lines: String = "".to_string();
-        if let Some(grammar) = grammar {
+        let ps = syntax_set();
+        let grammar = self
+            .determine_grammar()
+            .unwrap_or_else(|| ps.find_syntax_plain_text().clone());
+        let code_lines = if self.lines.wraps_lines() {
+            classed_lines(&self.code, &grammar, &ps, &self.lines)?
+        } else {
             let mut html = ClassedHTMLGenerator::new_with_class_style(
                 &grammar,
                 &ps,
@@ -438,14 +350,38 @@ impl CodeBlock {
             for line in LinesWithEndings::from(&self.code) {
                 html.parse_html_for_line_which_includes_newline(line)?;
             }
-            code_lines = html.finalize();
-        }
+            html.finalize()
+        };
 
         self.html.cache(&code_lines, dark_mode);
 
         Ok(code_lines)
     }
 
+    /// provides a single line of code (such as inline code in markdown)
+    /// as a tokenized HTML string; the lines options are not used
+    #[instrument]
+    pub fn as_inline_html(&self) -> Result<String, CodeBlockError> {
+        let ps = syntax_set();
+        let grammar = self
+            .determine_grammar()
+            .unwrap_or_else(|| ps.find_syntax_plain_text().clone());
+        let mut html = ClassedHTMLGenerator::new_with_class_style(
+            &grammar,
+            &ps,
+            ClassStyle::Spaced,
+        );
+        // grammars expect the lines they parse to end with a newline
+        let line = [self.code.trim_end_matches(['\n', '\r']), "\n"].join("");
+        html.parse_html_for_line_which_includes_newline(&line)?;
+        let mut html = html.finalize();
+        if let Some(newline) = html.rfind('\n') {
+            html.remove(newline);
+        }
+
+        Ok(html)
+    }
+
     pub fn as_html_with_inline_style(
         &mut self,
         dark_mode: &bool,
@@ -480,6 +416,7 @@ impl CodeBlock {
 mod tests {
 
     use super::*;
+    use std::collections::HashMap;
 
     #[test]
     fn code_grammar_get() {
@@ -498,6 +435,62 @@ mod tests {
         }
     }
 
+    #[test]
+    fn code_grammar_aliases_fall_back_to_known_syntaxes() {
+        // there's no TypeScript grammar in the defaults
+        assert_eq!(Grammar::get("ts").unwrap().name, "JavaScript");
+        assert_eq!(Grammar::get("C#").unwrap().name, "C#");
+        assert_eq!(Grammar::get("cpp").unwrap().name, "C++");
+        assert_eq!(Grammar::get("rs").unwrap().name, "Rust");
+        assert!(Grammar::get("not-a-language").is_none());
+
+        add_aliases(&HashMap::from([(
+            String::from("Shell-Session"),
+            String::from("Bourne Again Shell (bash)"),
+        )]));
+        assert_eq!(
+            Grammar::get("shell-session").unwrap().name,
+            "Bourne Again Shell (bash)"
+        );
+    }
+
+    #[test]
+    fn code_custom_syntaxes_loaded_and_dumped() {
+        let dir = std::env::temp_dir().join("dm_utils_custom_syntaxes");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("grammars")).unwrap();
+        std::fs::write(
+            dir.join("grammars/TOML.sublime-syntax"),
+            "%YAML 1.2\n---\nname: TOML\nfile_extensions: [toml]\nscope: source.toml\ncontexts:\n  main:\n    - match: '#.*$'\n      scope: comment.line.number-sign.toml\n",
+        )
+        .unwrap();
+        let dirs = vec![dir.join("grammars")];
+        // the directory for the dump is created when it's missing
+        let dump = dir.join("cache/syntaxes.packdump");
+
+        let set = load_syntaxes(&dirs, Some(&dump)).unwrap();
+        assert!(dump.exists());
+        assert!(set.find_syntax_by_name("TOML").is_some());
+        assert!(set.find_syntax_by_name("Rust").is_some());
+        // the dump is fresh so it's used the next time
+        let set = load_syntaxes(&dirs, Some(&dump)).unwrap();
+        let toml = set.find_syntax_by_extension("toml").unwrap();
+        let mut html = ClassedHTMLGenerator::new_with_class_style(
+            toml,
+            &set,
+            ClassStyle::Spaced,
+        );
+        html.parse_html_for_line_which_includes_newline("# hi\n")
+            .unwrap();
+        assert!(html.finalize().contains("comment"));
+
+        // a dump which can't be written doesn't stop the grammars loading
+        let set = load_syntaxes(&dirs, Some(&dir)).unwrap();
+        assert!(set.find_syntax_by_name("TOML").is_some());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
     #[test]
     fn code_themes_include() {
         let options = CodeBlock::new("").theme_options();
@@ -590,6 +583,60 @@ mod tests {
         assert!(perl.is_some());
     }
 
+    #[test]
+    fn code_language_detected_without_hints() {
+        let detect = |code: &str| CodeBlock::new(code).detect_language();
+
+        let shebang = detect("#!/usr/bin/env python3\nprint('hi')\n").unwrap();
+        assert_eq!(shebang.grammar, "Python");
+        assert_eq!(shebang.method, DetectionMethod::FirstLine);
+        assert_eq!(detect("<?php\necho 1;\n").unwrap().grammar, "PHP");
+        assert_eq!(detect("<?xml version=\"1.0\"?>\n<a/>\n").unwrap().grammar, "XML");
+
+        let rust = detect(
+            "use std::fmt;\n\npub fn main() {\n    let mut a = 1;\n    println!(\"{}\", a);\n}\n",
+        )
+        .unwrap();
+        assert_eq!(rust.grammar, "Rust");
+        assert_eq!(rust.method, DetectionMethod::Content);
+        assert!(rust.confidence > 0.5 && rust.confidence <= 1.0);
+
+        let python = detect(
+            "class A:\n    def __init__(self):\n        self.a = None\n",
+        );
+        assert_eq!(python.unwrap().grammar, "Python");
+        assert_eq!(
+            detect("SELECT id FROM users WHERE name = 'a';\n").unwrap().grammar,
+            "SQL"
+        );
+        assert_eq!(detect("{\n  \"a\": [1, 2]\n}\n").unwrap().grammar, "JSON");
+
+        // too little to go on
+        assert!(detect("hello world\n").is_none());
+        assert!(detect("").is_none());
+    }
+
+    #[test]
+    fn code_language_hints_take_precedence() {
+        let block =
+            CodeBlock::new_with_filename("#!/bin/bash\necho 1\n", "build.ts");
+        let detected = block.detect_language().unwrap();
+        assert_eq!(detected.grammar, "JavaScript");
+        assert_eq!(detected.method, DetectionMethod::Filename);
+
+        let mut block = CodeBlock::new("#!/bin/bash\necho 1\n");
+        block.language = Some(String::from("rust"));
+        let detected = block.detect_language().unwrap();
+        assert_eq!(detected.grammar, "Rust");
+        assert_eq!(detected.confidence, 1.0);
+    }
+
+    #[test]
+    fn code_without_a_language_shown_for_console() {
+        let mut block = CodeBlock::new("hello world");
+        assert!(block.as_escaped_console(&true).contains("hello world"));
+    }
+
     #[test]
     fn code_escaped_for_console() {
         let code = "let foo: number = 42";
@@ -619,6 +666,180 @@ mod tests {
         assert!(html.contains("meta.link {\n color: #d08770;\n}"));
     }
 
+    #[test]
+    fn code_lines_wrapped_with_classes() {
+        let code = "// one\nlet a = \"x\";\n";
+        let mut block = CodeBlock::new_with_lang(code, "js")
+            .unwrap()
+            .with_lines(LineOptions {
+                strategy: LineClassStrategy::Full,
+                line_numbers: true,
+                highlight: vec![(2, 2)],
+                ..LineOptions::default()
+            });
+        let html = block.as_html(&false).unwrap();
+        let lines: Vec<&str> = html.lines().collect();
+
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with(
+            "<span class=\"line line-1 odd has-comment has-punctuation\"><span class=\"line-number\">1</span><span class=\"source js\">"
+        ));
+        assert!(lines[1].starts_with(
+            "<span class=\"line line-2 even has-keyword has-punctuation has-storage has-string has-variable highlight\">"
+        ));
+        // every line is balanced
+        for line in lines {
+            assert_eq!(
+                line.matches("<span").count(),
+                line.matches("</span>").count()
+            );
+        }
+    }
+
+    #[test]
+    fn code_lines_only_wrapped_when_asked() {
+        let mut block = CodeBlock::new_with_lang("let a = 1;\n", "js").unwrap();
+        assert!(!block.as_html(&false).unwrap().contains("line"));
+
+        let mut block =
+            CodeBlock::new_with_lang("let a = 1;\nlet b = 2;\n", "js")
+                .unwrap()
+                .with_lines(LineOptions {
+                    highlight: vec![(2, 2)],
+                    ..LineOptions::default()
+                });
+        let html = block.as_html(&false).unwrap();
+        assert!(html.starts_with("<span class=\"line\">"));
+        assert!(html.contains("\n<span class=\"highlight\">"));
+    }
+
+    #[test]
+    fn code_themes_chosen_by_name() {
+        let block = CodeBlock::new("")
+            .with_themes("Solarized (light)", "Base16MochaDark")
+            .unwrap();
+        assert_eq!(block.theme(&false).name.unwrap(), "Solarized (light)");
+        assert_eq!(block.theme(&true).name.unwrap(), "Base16 Mocha Dark");
+
+        assert!(matches!(
+            CodeBlock::new("").with_themes("nope", "base16-ocean.dark"),
+            Err(CodeBlockError::InvalidTheme(_))
+        ));
+    }
+
+    #[test]
+    fn code_stylesheet_scopes_dark_mode() {
+        let block = CodeBlock::new("");
+        let css = block.stylesheet(DarkModeStrategy::MediaQuery).unwrap();
+        let (light, dark) =
+            css.split_once("@media (prefers-color-scheme: dark) {").unwrap();
+        assert!(light.contains(
+            "\n.comment, .punctuation.definition.comment {\n color: #a7adba;\n}"
+        ));
+        assert!(dark.contains(
+            "\n  .comment, .punctuation.definition.comment {\n   color: #65737e;\n  }"
+        ));
+        assert!(css.ends_with("}\n}\n"));
+
+        let css = block.stylesheet(DarkModeStrategy::Class).unwrap();
+        assert!(css.contains(
+            "\n.comment, .punctuation.definition.comment {\n color: #a7adba;\n}"
+        ));
+        assert!(css.contains(
+            "\n.dark .comment, .dark .punctuation.definition.comment {\n color: #65737e;\n}"
+        ));
+    }
+
+    #[test]
+    fn code_custom_themes_loaded() {
+        let dir = std::env::temp_dir().join("dm_utils_custom_themes");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("midnight.tmTheme"),
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0"><dict>
+<key>name</key><string>Midnight</string>
+<key>settings</key><array>
+<dict><key>settings</key><dict><key>background</key><string>#000000</string><key>foreground</key><string>#FFFFFF</string></dict></dict>
+<dict><key>scope</key><string>comment</string><key>settings</key><dict><key>foreground</key><string>#00FF00</string></dict></dict>
+</array></dict></plist>"#,
+        )
+        .unwrap();
+
+        let set = load_themes(std::slice::from_ref(&dir)).unwrap();
+        assert!(set.themes.contains_key("midnight"));
+        assert!(set.themes.contains_key("base16-ocean.dark"));
+        let css = stylesheet(
+            &set.themes["base16-ocean.light"],
+            &set.themes["midnight"],
+            DarkModeStrategy::Class,
+        )
+        .unwrap();
+        assert!(css.contains(".dark .comment {\n color: #00ff00;\n}"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn code_annotations_become_line_classes() {
+        let code = "let a = 1; // [!code --]\nlet a = 2; // [!code ++]\nlet b = a; /* [!code focus:2] */\nfoo(b); // [!code error]\n# [!code warning]\n";
+        let mut block = CodeBlock::new_with_lang(code, "js").unwrap();
+        block.annotate(false);
+
+        assert_eq!(
+            block.unformatted_code(),
+            "let a = 1;\nlet a = 2;\nlet b = a;\nfoo(b);\n\n"
+        );
+        let html = block.as_html(&false).unwrap();
+        let lines: Vec<&str> = html.lines().collect();
+        assert!(lines[0].starts_with("<span class=\"diff remove\">"));
+        assert!(lines[1].starts_with("<span class=\"diff add\">"));
+        assert!(lines[2].starts_with("<span class=\"focused\">"));
+        assert!(lines[3]
+            .starts_with("<span class=\"focused highlighted error\">"));
+        assert!(lines[4].starts_with("<span class=\"highlighted warning\">"));
+        assert!(!html.contains("[!code"));
+
+        // markers which aren't in a comment are left alone
+        let mut block =
+            CodeBlock::new_with_lang("let a = \"[!code ++]\";\n", "js").unwrap();
+        block.annotate(false);
+        assert!(block.lines.annotations.is_empty());
+    }
+
+    #[test]
+    fn code_diff_lines_annotated() {
+        let code = " fn main() {\n-    old();\n+    new();\n }\n";
+        let mut block = CodeBlock::new_with_lang(code, "rust").unwrap();
+        block.annotate(true);
+
+        assert_eq!(
+            block.unformatted_code(),
+            "fn main() {\n    old();\n    new();\n}\n"
+        );
+        assert_eq!(
+            block.lines.annotations,
+            vec![(2, LineAnnotation::Removed), (3, LineAnnotation::Added)]
+        );
+        let html = block.as_html(&false).unwrap();
+        assert!(html.contains("\n<span class=\"diff add\"><span class=\"source rust\">"));
+    }
+
+    #[test]
+    fn code_inline_html_is_a_single_line() {
+        let block = CodeBlock::new_with_lang("let x = 1;", "rust").unwrap();
+        let html = block.as_inline_html().unwrap();
+
+        assert!(html.starts_with("<span class=\"source rust\"><span class=\"storage type rust\">let</span>"));
+        assert!(!html.contains('\n'));
+        assert_eq!(
+            html.matches("<span").count(),
+            html.matches("</span>").count()
+        );
+    }
+
     #[test]
     fn code_provides_css_for_styling() {
         let code = "let foo: number = 42";
@@ -0,0 +1,167 @@
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+
+use serde::{Deserialize, Serialize};
+use strum::IntoEnumIterator;
+use strum_macros::{Display, EnumIter};
+use syntect::highlighting::{Theme, ThemeSet};
+use syntect::html::{css_for_theme_with_class_style, ClassStyle};
+use tracing::{info, instrument};
+
+use super::errors::CodeBlockError;
+
+/// The themes which code can be styled with; `None` until it is first
+/// used or until custom themes are registered with `use_themes`
+static THEMES: RwLock<Option<Arc<ThemeSet>>> = RwLock::new(None);
+
+/// The themes which come with syntect
+#[derive(
+    Debug, Serialize, Deserialize, Display, EnumIter, Eq, PartialEq, Clone,
+)]
+pub enum ThemeChoices {
+    #[serde(rename = "InspiredGitHub")]
+    InspiredGithub,
+    #[serde(rename = "Solarized (dark)")]
+    SolarizedDark,
+    #[serde(rename = "Solarized (light)")]
+    SolarizedLight,
+    #[serde(rename = "base16-eighties.dark")]
+    Base16EightiesDark,
+    #[serde(rename = "base16-mocha.dark")]
+    Base16MochaDark,
+    #[serde(rename = "base16-ocean.dark")]
+    Base16OceanDark,
+    #[serde(rename = "base16-ocean.light")]
+    Base16OceanLight,
+}
+
+impl ThemeChoices {
+    /// the name of the theme in the theme set
+    pub fn name(&self) -> &'static str {
+        match self {
+            ThemeChoices::InspiredGithub => "InspiredGitHub",
+            ThemeChoices::SolarizedDark => "Solarized (dark)",
+            ThemeChoices::SolarizedLight => "Solarized (light)",
+            ThemeChoices::Base16EightiesDark => "base16-eighties.dark",
+            ThemeChoices::Base16MochaDark => "base16-mocha.dark",
+            ThemeChoices::Base16OceanDark => "base16-ocean.dark",
+            ThemeChoices::Base16OceanLight => "base16-ocean.light",
+        }
+    }
+}
+
+/// How the rules of the dark theme are told apart from the light theme's
+/// in a stylesheet
+#[derive(
+    Debug, Serialize, Deserialize, Display, Eq, PartialEq, Clone, Copy, Default,
+)]
+#[serde(rename_all = "camelCase")]
+pub enum DarkModeStrategy {
+    /// the dark rules are in a `@media (prefers-color-scheme: dark)` block
+    #[default]
+    MediaQuery,
+    /// the dark rules only apply inside of an element with the `dark`
+    /// class (usually the `<html>` element)
+    Class,
+}
+
+/// The theme set which code is styled with; unless custom themes have
+/// been registered this is syntect's default set
+pub fn theme_set() -> Arc<ThemeSet> {
+    if let Some(set) = THEMES.read().unwrap().as_ref() {
+        return set.clone();
+    }
+    let mut themes = THEMES.write().unwrap();
+
+    themes
+        .get_or_insert_with(|| Arc::new(ThemeSet::load_defaults()))
+        .clone()
+}
+
+/// Replaces the theme set which code is styled with (usually with one
+/// from `load_themes`)
+pub fn use_themes(set: ThemeSet) {
+    *THEMES.write().unwrap() = Some(Arc::new(set));
+}
+
+/// Builds a theme set from syntect's defaults plus the `.tmTheme` files
+/// found in `dirs`; a theme is named after its file (without the
+/// extension) and replaces a default theme of the same name.
+#[instrument(level = "info")]
+pub fn load_themes(dirs: &[PathBuf]) -> Result<ThemeSet, CodeBlockError> {
+    let mut set = ThemeSet::load_defaults();
+    for dir in dirs {
+        set.add_from_folder(dir).map_err(|e| {
+            CodeBlockError::ThemeLoading(dir.to_string_lossy().to_string(), e)
+        })?;
+    }
+    info!("loaded {} themes", set.themes.len());
+
+    Ok(set)
+}
+
+/// Finds a theme by its name in the theme set; the match is case
+/// insensitive and the names of the `ThemeChoices` variants (e.g.
+/// `Base16OceanDark`) are accepted too.
+pub fn find_theme(name: &str) -> Result<Theme, CodeBlockError> {
+    let set = theme_set();
+    let name = ThemeChoices::iter()
+        .find(|c| c.to_string().eq_ignore_ascii_case(name))
+        .map_or(name, |c| c.name());
+
+    set.themes
+        .get(name)
+        .or_else(|| {
+            set.themes
+                .iter()
+                .find(|(n, _)| n.eq_ignore_ascii_case(name))
+                .map(|(_, theme)| theme)
+        })
+        .cloned()
+        .ok_or_else(|| CodeBlockError::InvalidTheme(name.to_string()))
+}
+
+/// A single stylesheet for code which uses the `light` theme unless the
+/// browser (or page) is in dark mode where the `dark` theme is used
+pub fn stylesheet(
+    light: &Theme,
+    dark: &Theme,
+    strategy: DarkModeStrategy,
+) -> Result<String, CodeBlockError> {
+    let light = css_for_theme_with_class_style(light, ClassStyle::Spaced)?;
+    let dark = css_for_theme_with_class_style(dark, ClassStyle::Spaced)?;
+
+    let dark = match strategy {
+        DarkModeStrategy::MediaQuery => {
+            let indented: Vec<String> = dark
+                .lines()
+                .map(|l| match l.is_empty() {
+                    true => String::new(),
+                    false => format!("  {}", l),
+                })
+                .collect();
+            format!(
+                "@media (prefers-color-scheme: dark) {{\n{}\n}}\n",
+                indented.join("\n").trim_end()
+            )
+        }
+        DarkModeStrategy::Class => {
+            let scoped: Vec<String> = dark
+                .lines()
+                .map(|l| match l.strip_suffix(" {") {
+                    Some(selectors) => {
+                        let selectors: Vec<String> = selectors
+                            .split(", ")
+                            .map(|s| format!(".dark {}", s))
+                            .collect();
+                        format!("{} {{", selectors.join(", "))
+                    }
+                    None => l.to_string(),
+                })
+                .collect();
+            scoped.join("\n")
+        }
+    };
+
+    Ok([light, dark].join("\n"))
+}
@@ -23,6 +23,12 @@ pub fn hash(content: &str, secret: Option<&str>) -> u64 {
     }
 }
 
+/// Provides a hash for binary content (such as an image file) using the
+/// same `xxh3` hasher as `hash`.
+pub fn hash_bytes(content: &[u8]) -> u64 {
+    xxh3_64(content)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;